@@ -0,0 +1,40 @@
+use rust_parser::AST;
+use rust_parser_macros::grammar;
+
+fn main() {
+    let parser = grammar! {
+        tokens {
+            "*" = r"\*";
+            "(" = r"\(";
+            ")" = r"\)";
+            "+" = r"\+";
+            int = r"\d+";
+            #[skip] whitespace = r"\s+";
+        }
+        rules {
+            E -> E "+" T | T;
+            T -> T "*" F | F;
+            F -> "(" E ")" | int;
+        }
+    };
+
+    match parser.parse("(1 + 1) * 3 * 2") {
+        Ok(ast) => print_ast(&ast.borrow(), 0),
+        Err(err) => println!("{err}"),
+    }
+}
+
+fn print_ast(ast: &AST, level: usize) {
+    let indent = "  ".repeat(level);
+
+    match ast {
+        AST::Token { value, .. } => println!("{indent}{value}"),
+        AST::Grammar { name, children, .. } => {
+            println!("{indent}{name}");
+            for item in children.iter() {
+                print_ast(&item.borrow(), level + 1);
+            }
+        }
+        AST::Error { name, .. } => println!("{indent}<error: {name}>"),
+    }
+}
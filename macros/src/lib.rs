@@ -0,0 +1,383 @@
+use std::fmt::Write;
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use rust_parser::{
+    epsilon, nt, t, GrammarBuilder, GrammarError, LexError, NodeType, Parser, PatternKind,
+};
+
+const EPSILON: &str = "epsilon";
+
+/// Builds a [Parser](rust_parser::Parser) from grammars analysed at compile time.
+///
+/// Token names and grammar names are identifiers or string literals, so names that
+/// are not valid identifiers (like `+` or `E'`) are written as `"+"` and `"E'"`.
+///
+/// ```ignore
+/// let parser = grammar! {
+///     tokens {
+///         "+" = r"\+";
+///         int = r"\d+";
+///         #[skip] whitespace = r"\s+";
+///     }
+///     rules {
+///         E -> int "E'";
+///         "E'" -> "+" int "E'" | epsilon;
+///     }
+/// };
+/// ```
+///
+/// Invalid patterns, undefined grammars and LL(1) conflicts are reported as compile errors.
+#[proc_macro]
+pub fn grammar(input: TokenStream) -> TokenStream {
+    match expand(input) {
+        Ok(tokens) => tokens,
+        Err((message, span)) => compile_error(&message, span),
+    }
+}
+
+type Error = (String, Span);
+
+struct TokenDeclaration {
+    name: String,
+    pattern: String,
+    pattern_span: Span,
+    kind: PatternKind,
+}
+
+struct RuleDeclaration {
+    name: String,
+    span: Span,
+    variants: Vec<Vec<(String, Span)>>,
+}
+
+fn expand(input: TokenStream) -> Result<TokenStream, Error> {
+    let mut tokens = vec![];
+    let mut rules = vec![];
+
+    let mut trees = input.into_iter();
+    while let Some(tree) = trees.next() {
+        let TokenTree::Ident(section) = &tree else {
+            return Err(("expected `tokens` or `rules`".to_string(), tree.span()));
+        };
+
+        let body = match trees.next() {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => group,
+            _ => return Err((format!("expected `{{` after `{section}`"), section.span())),
+        };
+
+        match section.to_string().as_str() {
+            "tokens" => tokens.extend(parse_tokens(body.stream())?),
+            "rules" => rules.extend(parse_rules(body.stream())?),
+            _ => return Err(("expected `tokens` or `rules`".to_string(), section.span())),
+        }
+    }
+
+    let parser = build(&tokens, &rules)?;
+    let code = emit(&parser, &tokens);
+    Ok(code.parse().expect("generated code is valid Rust"))
+}
+
+fn parse_tokens(stream: TokenStream) -> Result<Vec<TokenDeclaration>, Error> {
+    let mut declarations = vec![];
+    let mut trees = stream.into_iter().peekable();
+
+    while let Some(tree) = trees.next() {
+        let mut kind = PatternKind::Token;
+        let mut tree = tree;
+
+        if let TokenTree::Punct(punct) = &tree {
+            if punct.as_char() == '#' {
+                match trees.next() {
+                    Some(TokenTree::Group(group))
+                        if group.delimiter() == Delimiter::Bracket
+                            && group.stream().to_string() == "skip" =>
+                    {
+                        kind = PatternKind::Skip;
+                    }
+                    _ => return Err(("expected `#[skip]`".to_string(), punct.span())),
+                }
+
+                tree = match trees.next() {
+                    Some(tree) => tree,
+                    None => return Err(("expected a token name".to_string(), punct.span())),
+                };
+            }
+        }
+
+        let (name, name_span) = symbol(&tree)?;
+        expect_punct(trees.next(), '=', name_span)?;
+
+        let (pattern, pattern_span) = match trees.next() {
+            Some(tree) => match string_literal(&tree) {
+                Some(pattern) => (pattern, tree.span()),
+                None => return Err(("expected a pattern string".to_string(), tree.span())),
+            },
+            None => return Err(("expected a pattern string".to_string(), name_span)),
+        };
+
+        if let Some(TokenTree::Punct(punct)) = trees.peek() {
+            if punct.as_char() == ';' {
+                trees.next();
+            }
+        }
+
+        declarations.push(TokenDeclaration {
+            name,
+            pattern,
+            pattern_span,
+            kind,
+        });
+    }
+
+    Ok(declarations)
+}
+
+fn parse_rules(stream: TokenStream) -> Result<Vec<RuleDeclaration>, Error> {
+    let mut declarations = vec![];
+    let mut trees = stream.into_iter().peekable();
+
+    while let Some(tree) = trees.next() {
+        let (name, span) = symbol(&tree)?;
+        expect_punct(trees.next(), '-', span)?;
+        expect_punct(trees.next(), '>', span)?;
+
+        let mut variants = vec![vec![]];
+        for tree in trees.by_ref() {
+            match &tree {
+                TokenTree::Punct(punct) if punct.as_char() == ';' => break,
+                TokenTree::Punct(punct) if punct.as_char() == '|' => variants.push(vec![]),
+                _ => variants.last_mut().unwrap().push(symbol(&tree)?),
+            }
+        }
+
+        declarations.push(RuleDeclaration {
+            name,
+            span,
+            variants,
+        });
+    }
+
+    Ok(declarations)
+}
+
+fn build(tokens: &[TokenDeclaration], rules: &[RuleDeclaration]) -> Result<Parser, Error> {
+    let mut builder = GrammarBuilder::new();
+    for token in tokens.iter() {
+        builder = match token.kind {
+            PatternKind::Skip => builder.skip(&token.name, &token.pattern),
+            _ => builder.token(&token.name, &token.pattern),
+        };
+    }
+
+    for rule in rules.iter() {
+        for variant in rule.variants.iter() {
+            let symbols = variant.iter().map(|(name, _)| {
+                if name == EPSILON {
+                    epsilon()
+                } else if tokens.iter().any(|token| token.name == *name) {
+                    t(name)
+                } else {
+                    nt(name)
+                }
+            });
+            builder = builder.rule(&rule.name, symbols);
+        }
+    }
+
+    builder.build().map_err(|err| {
+        let span = match &err {
+            GrammarError::Lex(LexError::InvalidPattern { name, .. }) => tokens
+                .iter()
+                .find(|token| token.name == *name)
+                .map(|token| token.pattern_span),
+            GrammarError::UndefinedGrammar { name, .. } => rules
+                .iter()
+                .flat_map(|rule| rule.variants.iter().flatten())
+                .find(|(symbol, _)| symbol == name)
+                .map(|(_, span)| *span),
+            GrammarError::Conflicts { conflicts, .. } => conflicts.first().and_then(|conflict| {
+                rules
+                    .iter()
+                    .find(|rule| rule.name == conflict.grammar)
+                    .map(|rule| rule.span)
+            }),
+            GrammarError::LeftRecursion { cycle } => cycle.first().and_then(|grammar| {
+                rules
+                    .iter()
+                    .find(|rule| rule.name == *grammar)
+                    .map(|rule| rule.span)
+            }),
+            _ => None,
+        };
+
+        (err.to_string(), span.unwrap_or_else(Span::call_site))
+    })
+}
+
+fn emit(parser: &Parser, tokens: &[TokenDeclaration]) -> String {
+    let mut code = String::new();
+    code.push_str(
+        "{ static GRAMMAR: ::rust_parser::CompiledGrammar = ::rust_parser::CompiledGrammar {",
+    );
+
+    code.push_str("tokens: &[");
+    for token in tokens.iter() {
+        let kind = match token.kind {
+            PatternKind::Skip => "Skip",
+            _ => "Token",
+        };
+        write!(
+            code,
+            "({:?}, {:?}, ::rust_parser::PatternKind::{kind}),",
+            token.name, token.pattern
+        )
+        .unwrap();
+    }
+    code.push_str("], indentation: None,");
+
+    code.push_str("grammars: &[");
+    for (grammar, variants) in parser.grammars().iter() {
+        write!(code, "({grammar:?}, &[").unwrap();
+        for variant in variants.iter() {
+            code.push_str("&[");
+            for node in variant.iter() {
+                match node {
+                    NodeType::Token { name, .. } => {
+                        write!(code, "::rust_parser::CompiledNode::Token({name:?}),").unwrap()
+                    }
+                    NodeType::Grammar { name } => {
+                        write!(code, "::rust_parser::CompiledNode::Grammar({name:?}),").unwrap()
+                    }
+                }
+            }
+            code.push_str("],");
+        }
+        code.push_str("]),");
+    }
+    code.push_str("],");
+
+    write!(
+        code,
+        "nullable: &{:?},",
+        parser.nullable().iter().collect::<Vec<_>>()
+    )
+    .unwrap();
+
+    for (field, sets) in [("first", parser.first()), ("follow", parser.follow())] {
+        write!(code, "{field}: &[").unwrap();
+        for (grammar, tokens) in sets.iter() {
+            write!(
+                code,
+                "({grammar:?}, &{:?}),",
+                tokens.iter().collect::<Vec<_>>()
+            )
+            .unwrap();
+        }
+        code.push_str("],");
+    }
+
+    write!(code, "lookahead: {},", parser.lookahead()).unwrap();
+    code.push_str("table: &[");
+    for ((grammar, lookahead), variant) in parser.table().iter() {
+        let index = parser.grammars()[grammar]
+            .iter()
+            .position(|candidate| same_variant(candidate, variant))
+            .expect("table variants come from the grammar");
+        write!(code, "({grammar:?}, &{lookahead:?}, {index}),").unwrap();
+    }
+    code.push_str("],");
+
+    write!(code, "tails: &{:?},", parser.tails().collect::<Vec<_>>()).unwrap();
+
+    code.push_str(
+        "}; ::rust_parser::Parser::from_compiled(&GRAMMAR)\
+         .expect(\"the grammar is validated at compile time\") }",
+    );
+    code
+}
+
+fn same_variant(left: &[NodeType], right: &[NodeType]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right.iter())
+            .all(|(a, b)| a.to_string() == b.to_string())
+}
+
+fn symbol(tree: &TokenTree) -> Result<(String, Span), Error> {
+    match tree {
+        TokenTree::Ident(ident) => Ok((ident.to_string(), ident.span())),
+        TokenTree::Literal(_) => match string_literal(tree) {
+            Some(value) => Ok((value, tree.span())),
+            None => Err(("expected a name or a string".to_string(), tree.span())),
+        },
+        _ => Err(("expected a name or a string".to_string(), tree.span())),
+    }
+}
+
+fn expect_punct(tree: Option<TokenTree>, expected: char, fallback: Span) -> Result<(), Error> {
+    match tree {
+        Some(TokenTree::Punct(punct)) if punct.as_char() == expected => Ok(()),
+        Some(tree) => Err((format!("expected `{expected}`"), tree.span())),
+        None => Err((format!("expected `{expected}`"), fallback)),
+    }
+}
+
+/// Returns the value of a string or raw string literal.
+fn string_literal(tree: &TokenTree) -> Option<String> {
+    let TokenTree::Literal(literal) = tree else {
+        return None;
+    };
+    let text = literal.to_string();
+
+    if let Some(raw) = text.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let inner = &raw[hashes..raw.len() - hashes];
+        return inner
+            .strip_prefix('"')
+            .and_then(|inner| inner.strip_suffix('"'))
+            .map(String::from);
+    }
+
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+
+        match chars.next()? {
+            'n' => value.push('\n'),
+            't' => value.push('\t'),
+            'r' => value.push('\r'),
+            '0' => value.push('\0'),
+            'u' => {
+                let code: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                value.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            other => value.push(other),
+        }
+    }
+    Some(value)
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+
+    let mut group = Group::new(Delimiter::Brace, TokenTree::Literal(literal).into());
+    group.set_span(span);
+
+    [
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(group),
+    ]
+    .into_iter()
+    .collect()
+}
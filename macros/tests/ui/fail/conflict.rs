@@ -0,0 +1,13 @@
+use rust_parser_macros::grammar;
+
+fn main() {
+    let _parser = grammar! {
+        tokens {
+            a = "a";
+            b = "b";
+        }
+        rules {
+            S -> a | a b;
+        }
+    };
+}
@@ -0,0 +1,12 @@
+use rust_parser_macros::grammar;
+
+fn main() {
+    let _parser = grammar! {
+        tokens {
+            a = "a";
+        }
+        rules {
+            S -> a T;
+        }
+    };
+}
@@ -0,0 +1,24 @@
+use rust_parser::AST;
+use rust_parser_macros::grammar;
+
+fn main() {
+    let parser = grammar! {
+        tokens {
+            "+" = r"\+";
+            int = r"\d+";
+            #[skip] whitespace = r"\s+";
+        }
+        rules {
+            E -> int "E'";
+            "E'" -> "+" int "E'" | epsilon;
+        }
+    };
+
+    let ast = parser.parse("1 + 2").unwrap();
+    let AST::Grammar { name, children, .. } = &*ast.borrow() else {
+        panic!("the root is not a grammar");
+    };
+    assert_eq!(name, "E");
+    assert_eq!(children.len(), 2);
+    assert!(parser.parse("1 +").is_err());
+}
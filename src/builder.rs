@@ -0,0 +1,237 @@
+use indexmap::IndexMap;
+
+use crate::error::{GrammarError, LexError};
+use crate::parser::{GrammarName, GrammarVariants, NodeType, Parser, ParserOptions, TokenName};
+use crate::tokenizer::{Indentation, Pattern, PatternKind, Tokenizer, EPSILON};
+
+/// A symbol of a grammar variant declared with [GrammarBuilder::rule].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Symbol {
+    Token(TokenName),
+    Grammar(GrammarName),
+}
+
+/// A token declared with [GrammarBuilder::token].
+pub fn t(name: &str) -> Symbol {
+    Symbol::Token(String::from(name))
+}
+
+/// A grammar declared with [GrammarBuilder::rule].
+pub fn nt(name: &str) -> Symbol {
+    Symbol::Grammar(String::from(name))
+}
+
+/// The empty variant.
+pub fn epsilon() -> Symbol {
+    Symbol::Token(String::from(EPSILON))
+}
+
+/// Defines tokens and grammars in code instead of `tokens.txt` and `grammar.txt` files.
+/// Nothing is checked until [GrammarBuilder::build] or [GrammarBuilder::finish] is called.
+#[derive(Debug, Default)]
+pub struct GrammarBuilder {
+    tokens: Vec<(TokenName, String, PatternKind)>,
+    rules: IndexMap<GrammarName, Vec<Vec<Symbol>>>,
+    indentation: Option<usize>,
+    options: ParserOptions,
+}
+
+impl GrammarBuilder {
+    pub fn new() -> GrammarBuilder {
+        GrammarBuilder::default()
+    }
+
+    /// Declares a token; earlier declarations win when several patterns match the same text.
+    pub fn token(mut self, name: &str, pattern: &str) -> GrammarBuilder {
+        self.tokens.push((
+            String::from(name),
+            String::from(pattern),
+            PatternKind::Token,
+        ));
+        self
+    }
+
+    /// Declares a token that is matched and discarded, like `%skip` in token files.
+    pub fn skip(mut self, name: &str, pattern: &str) -> GrammarBuilder {
+        self.tokens
+            .push((String::from(name), String::from(pattern), PatternKind::Skip));
+        self
+    }
+
+    /// Enables the indentation mode of the tokenizer, like `%indent` in token files.
+    pub fn indentation(mut self, tab_width: usize) -> GrammarBuilder {
+        self.indentation = Some(tab_width);
+        self
+    }
+
+    pub fn options(mut self, options: ParserOptions) -> GrammarBuilder {
+        self.options = options;
+        self
+    }
+
+    /// Adds a variant to the grammar. The first grammar is the start of the parse.
+    /// A variant without symbols derives epsilon.
+    pub fn rule(mut self, name: &str, symbols: impl IntoIterator<Item = Symbol>) -> GrammarBuilder {
+        self.rules
+            .entry(String::from(name))
+            .or_default()
+            .push(symbols.into_iter().collect());
+        self
+    }
+
+    pub fn build(mut self) -> Result<Parser, GrammarError> {
+        let options = std::mem::take(&mut self.options);
+        let (grammars, tokenizer) = self.finish()?;
+        Parser::new(grammars, tokenizer, options)
+    }
+
+    /// Returns the grammars and the tokenizer without building a parser, which ignores
+    /// [GrammarBuilder::options]. Only the tokens are checked; grammars are checked by
+    /// [Parser::new].
+    pub fn finish(
+        self,
+    ) -> Result<(IndexMap<GrammarName, GrammarVariants>, Tokenizer), GrammarError> {
+        let mut patterns = vec![];
+        for (name, pattern, kind) in self.tokens.iter() {
+            patterns.push(Pattern::new(name, pattern, *kind)?);
+        }
+
+        let indentation = match self.indentation {
+            Some(0) => {
+                return Err(GrammarError::Lex(LexError::InvalidTabWidth {
+                    value: String::from("0"),
+                }))
+            }
+            Some(tab_width) => Some(Indentation { tab_width }),
+            None => None,
+        };
+        let tokenizer = Tokenizer::new(patterns, indentation);
+
+        let mut grammars: IndexMap<GrammarName, GrammarVariants> = IndexMap::new();
+        for (rule, variants) in self.rules.into_iter() {
+            let mut grammar_variants: GrammarVariants = vec![];
+
+            for symbols in variants.into_iter() {
+                let symbols = if symbols.is_empty() {
+                    vec![epsilon()]
+                } else {
+                    symbols
+                };
+
+                let mut nodes = vec![];
+                for symbol in symbols.into_iter() {
+                    match symbol {
+                        Symbol::Token(name) => {
+                            let Some(pattern) = tokenizer
+                                .patterns
+                                .iter()
+                                .find(|pattern| pattern.name == name)
+                            else {
+                                return Err(GrammarError::UndefinedToken { name, rule });
+                            };
+
+                            nodes.push(NodeType::Token {
+                                name,
+                                pattern: pattern.clone(),
+                            });
+                        }
+                        Symbol::Grammar(name) => nodes.push(NodeType::Grammar { name }),
+                    }
+                }
+                grammar_variants.push(nodes);
+            }
+
+            grammars.insert(rule, grammar_variants);
+        }
+
+        Ok((grammars, tokenizer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grammar;
+
+    const TOKENS: &str = "int = \\d+\n+ = \\+\n( = \\(\n) = \\)\n%skip whitespace = \\s+";
+    const GRAMMAR: &str = "E -> T E'\nE' -> + T E' | epsilon\nT -> int | ( E )";
+
+    fn builder() -> GrammarBuilder {
+        GrammarBuilder::new()
+            .token("int", "\\d+")
+            .token("+", "\\+")
+            .token("(", "\\(")
+            .token(")", "\\)")
+            .skip("whitespace", "\\s+")
+            .rule("E", [nt("T"), nt("E'")])
+            .rule("E'", [t("+"), nt("T"), nt("E'")])
+            .rule("E'", [])
+            .rule("T", [t("int")])
+            .rule("T", [t("("), nt("E"), t(")")])
+    }
+
+    fn names(grammars: &IndexMap<GrammarName, GrammarVariants>) -> Vec<(String, Vec<String>)> {
+        let variant = |variant: &Vec<NodeType>| {
+            let nodes: Vec<String> = variant.iter().map(|node| node.to_string()).collect();
+            nodes.join(" ")
+        };
+        let grammar = |(name, variants): (&GrammarName, &GrammarVariants)| {
+            (name.clone(), variants.iter().map(variant).collect())
+        };
+        grammars.iter().map(grammar).collect()
+    }
+
+    fn patterns(tokenizer: &Tokenizer) -> Vec<(String, String, PatternKind)> {
+        let pattern = |p: &Pattern| (p.name.clone(), p.value.to_string(), p.kind);
+        tokenizer.patterns.iter().map(pattern).collect()
+    }
+
+    #[test]
+    fn finish_matches_text() {
+        let (grammars, tokenizer) = builder().finish().unwrap();
+
+        let mut text_tokenizer: Tokenizer = TOKENS.parse().unwrap();
+        let text = grammar::read(GRAMMAR, &mut text_tokenizer).unwrap();
+        assert_eq!(names(&grammars), names(&text.declared));
+        assert_eq!(patterns(&tokenizer), patterns(&text_tokenizer));
+    }
+
+    #[test]
+    fn build_matches_text() {
+        let built = builder().build().unwrap();
+        let text = Parser::from_str(GRAMMAR, TOKENS.parse().unwrap()).unwrap();
+
+        assert_eq!(names(built.grammars()), names(text.grammars()));
+        assert_eq!(built.first(), text.first());
+        assert_eq!(built.follow(), text.follow());
+        let table = |parser: &Parser| {
+            let cells = parser
+                .table()
+                .iter()
+                .map(|((grammar, lookahead), variant)| {
+                    let nodes: Vec<String> = variant.iter().map(|node| node.to_string()).collect();
+                    (grammar.clone(), lookahead.clone(), nodes)
+                });
+            cells.collect::<Vec<_>>()
+        };
+        assert_eq!(table(&built), table(&text));
+
+        let tree = |parser: &Parser| format!("{:?}", parser.parse("(1 + 2) + 3").unwrap());
+        assert_eq!(tree(&built), tree(&text));
+    }
+
+    #[test]
+    fn invalid_tokens() {
+        let result = builder().rule("T", [t("-")]).finish();
+        assert!(matches!(
+            result,
+            Err(GrammarError::UndefinedToken { name, rule }) if name == "-" && rule == "T"
+        ));
+
+        let result = builder().indentation(0).finish();
+        assert!(matches!(
+            result,
+            Err(GrammarError::Lex(LexError::InvalidTabWidth { .. }))
+        ));
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt::Write;
+
+use indexmap::IndexMap;
+
+use crate::parser::{Helper, Lookahead, NodeType, Parser, EOF};
+use crate::precedence::Associativity;
+use crate::tokenizer::{PatternKind, EPSILON};
+
+/// Tokenizer and table-driven driver shared by every generated module.
+const RUNTIME: &str = include_str!("codegen/runtime.rs");
+
+impl Parser {
+    /// Emits a self-contained Rust module that tokenizes and parses the input like this parser.
+    /// The module only depends on `regex` and exposes `tokenize` and `parse` functions.
+    pub fn codegen(&self) -> String {
+        generate(self)
+    }
+}
+
+fn generate(parser: &Parser) -> String {
+    let mut code = String::new();
+    writeln!(code, "// Generated by rust-parser. Do not edit.").unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "#![allow(dead_code)]").unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "use std::sync::OnceLock;").unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "use regex::Regex;").unwrap();
+    writeln!(code).unwrap();
+
+    let start = parser
+        .grammars()
+        .keys()
+        .nth(1)
+        .expect("parsers have at least one grammar");
+    writeln!(code, "const EOF: &str = {EOF:?};").unwrap();
+    writeln!(code, "const START: &str = {start:?};").unwrap();
+    writeln!(code, "const LOOKAHEAD: usize = {};", parser.lookahead()).unwrap();
+
+    let indentation = parser.tokenizer().indentation.map(|i| i.tab_width);
+    writeln!(code, "const INDENTATION: Option<usize> = {indentation:?};").unwrap();
+    let helpers: Vec<&String> = parser
+        .helpers
+        .iter()
+        .filter(|(_, helper)| **helper == Helper::Inline)
+        .map(|(name, _)| name)
+        .collect();
+    writeln!(code, "const HELPERS: &[&str] = &{helpers:?};").unwrap();
+    let tails: Vec<(&String, &String)> = parser.tails().collect();
+    writeln!(code, "const TAILS: &[(&str, &str)] = &{tails:?};").unwrap();
+    let expressions: Vec<&String> = parser.operators.expressions.keys().collect();
+    writeln!(code, "const EXPRESSIONS: &[&str] = &{expressions:?};").unwrap();
+    writeln!(code).unwrap();
+
+    writeln!(
+        code,
+        "/// Binary operators: token, precedence and whether they are right-associative."
+    )
+    .unwrap();
+    let binary: Vec<(&String, usize, bool)> = parser
+        .operators
+        .binary
+        .iter()
+        .map(|(name, (precedence, associativity))| {
+            (name, *precedence, *associativity == Associativity::Right)
+        })
+        .collect();
+    writeln!(code, "const BINARY: &[(&str, usize, bool)] = &{binary:?};").unwrap();
+    let prefix: Vec<(&String, &usize)> = parser.operators.prefix.iter().collect();
+    writeln!(code, "const PREFIX: &[(&str, usize)] = &{prefix:?};").unwrap();
+    writeln!(code).unwrap();
+
+    writeln!(
+        code,
+        "/// Token name, anchored pattern and whether matched tokens are skipped."
+    )
+    .unwrap();
+    writeln!(code, "const PATTERNS: &[(&str, &str, bool)] = &[").unwrap();
+    for pattern in parser.tokenizer().patterns.iter() {
+        if pattern.kind == PatternKind::Synthetic {
+            continue;
+        }
+        writeln!(
+            code,
+            "    ({:?}, {:?}, {}),",
+            pattern.name,
+            pattern.value.as_str(),
+            pattern.kind == PatternKind::Skip
+        )
+        .unwrap();
+    }
+    writeln!(code, "];").unwrap();
+    writeln!(code).unwrap();
+
+    let mut lookaheads: IndexMap<&str, Vec<&Lookahead>> = IndexMap::new();
+    writeln!(
+        code,
+        "fn variant(grammar: &str, lookahead: &[&str]) -> Option<&'static [Symbol]> {{"
+    )
+    .unwrap();
+    writeln!(code, "    match (grammar, lookahead) {{").unwrap();
+    for ((grammar, lookahead), variant) in parser.table().iter() {
+        if grammar == parser.grammars().keys().next().unwrap() {
+            continue;
+        }
+        lookaheads.entry(grammar).or_default().push(lookahead);
+
+        let symbols: Vec<String> = variant
+            .iter()
+            .filter_map(|node| match node {
+                NodeType::Token { name, .. } if name == EPSILON => None,
+                NodeType::Token { name, .. } => Some(format!("Symbol::Token({name:?})")),
+                NodeType::Grammar { name } => Some(format!("Symbol::Grammar({name:?})")),
+            })
+            .collect();
+        writeln!(
+            code,
+            "        ({grammar:?}, {lookahead:?}) => Some(&[{}]),",
+            symbols.join(", ")
+        )
+        .unwrap();
+    }
+    writeln!(code, "        _ => None,").unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(code, "}}").unwrap();
+    writeln!(code).unwrap();
+
+    writeln!(
+        code,
+        "fn lookaheads(grammar: &str) -> &'static [&'static [&'static str]] {{"
+    )
+    .unwrap();
+    writeln!(code, "    match grammar {{").unwrap();
+    for (grammar, entries) in lookaheads.iter() {
+        let entries: Vec<String> = entries.iter().map(|entry| format!("&{entry:?}")).collect();
+        writeln!(code, "        {grammar:?} => &[{}],", entries.join(", ")).unwrap();
+    }
+    writeln!(code, "        _ => &[],").unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(code, "}}").unwrap();
+    writeln!(code).unwrap();
+
+    code.push_str(RUNTIME);
+    code
+}
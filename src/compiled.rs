@@ -0,0 +1,142 @@
+use indexmap::{IndexMap, IndexSet};
+
+use crate::error::{GrammarError, LexError};
+use crate::parser::{GrammarVariant, Helper, NodeType, Parser, ParserOptions, EOF};
+use crate::precedence::Operators;
+use crate::tokenizer::{Indentation, Pattern, PatternKind, Tokenizer};
+
+/// A node of a variant in a [CompiledGrammar].
+#[derive(Debug, Clone, Copy)]
+pub enum CompiledNode {
+    Token(&'static str),
+    Grammar(&'static str),
+}
+
+/// Grammars analysed ahead of time, as emitted by the `grammar!` macro.
+/// Loading them skips the FIRST, FOLLOW and parsing table construction.
+#[derive(Debug)]
+pub struct CompiledGrammar {
+    /// Declared tokens: name, pattern and kind.
+    pub tokens: &'static [(&'static str, &'static str, PatternKind)],
+    pub indentation: Option<usize>,
+    /// Grammars in declaration order, starting with the internal root grammar.
+    pub grammars: &'static [(&'static str, &'static [&'static [CompiledNode]])],
+    pub nullable: &'static [&'static str],
+    pub first: &'static [(&'static str, &'static [&'static str])],
+    pub follow: &'static [(&'static str, &'static [&'static str])],
+    /// Number of tokens the parsing table looks ahead.
+    pub lookahead: usize,
+    /// Parsing table cells: grammar, lookahead tokens and the index of the variant in the grammar.
+    pub table: &'static [(&'static str, &'static [&'static str], usize)],
+    /// Tails of left-recursive grammars and the grammar each of them belongs to.
+    pub tails: &'static [(&'static str, &'static str)],
+}
+
+impl Parser {
+    pub fn from_compiled(compiled: &CompiledGrammar) -> Result<Parser, GrammarError> {
+        let mut patterns = vec![];
+        for (name, pattern, kind) in compiled.tokens.iter() {
+            patterns.push(Pattern::new(name, pattern, *kind)?);
+        }
+
+        let indentation = match compiled.indentation {
+            Some(0) => {
+                return Err(GrammarError::Lex(LexError::InvalidTabWidth {
+                    value: String::from("0"),
+                }))
+            }
+            Some(tab_width) => Some(Indentation { tab_width }),
+            None => None,
+        };
+        let tokenizer = Tokenizer::new(patterns, indentation);
+
+        let mut grammars = IndexMap::new();
+        for (grammar, variants) in compiled.grammars.iter() {
+            let mut grammar_variants = vec![];
+            for variant in variants.iter() {
+                let mut nodes: GrammarVariant = vec![];
+                for node in variant.iter() {
+                    nodes.push(compiled_node(node, &tokenizer, grammar)?);
+                }
+                grammar_variants.push(nodes);
+            }
+            grammars.insert(String::from(*grammar), grammar_variants);
+        }
+
+        let mut table = IndexMap::new();
+        for (grammar, lookahead, index) in compiled.table.iter() {
+            if let Some(variant) = grammars.get(*grammar).and_then(|v| v.get(*index)) {
+                let lookahead = lookahead.iter().map(|token| String::from(*token)).collect();
+                table.insert((String::from(*grammar), lookahead), variant.clone());
+            }
+        }
+
+        Ok(Parser {
+            grammars,
+            nullable: compiled
+                .nullable
+                .iter()
+                .map(|name| String::from(*name))
+                .collect(),
+            first: sets(compiled.first),
+            follow: sets(compiled.follow),
+            table,
+            tokenizer,
+            conflicts: vec![],
+            options: ParserOptions {
+                lookahead: compiled.lookahead,
+                ..ParserOptions::default()
+            },
+            helpers: compiled
+                .tails
+                .iter()
+                .map(|(tail, grammar)| {
+                    let grammar = String::from(*grammar);
+                    (String::from(*tail), Helper::Tail { grammar })
+                })
+                .collect(),
+            operators: Operators::default(),
+        })
+    }
+}
+
+fn compiled_node(
+    node: &CompiledNode,
+    tokenizer: &Tokenizer,
+    rule: &str,
+) -> Result<NodeType, GrammarError> {
+    match node {
+        CompiledNode::Token(name) if *name == EOF => Ok(Parser::eof()),
+        CompiledNode::Token(name) => {
+            match tokenizer
+                .patterns
+                .iter()
+                .find(|pattern| pattern.name == *name)
+            {
+                Some(pattern) => Ok(NodeType::Token {
+                    name: String::from(*name),
+                    pattern: pattern.clone(),
+                }),
+                None => Err(GrammarError::UndefinedToken {
+                    name: String::from(*name),
+                    rule: String::from(rule),
+                }),
+            }
+        }
+        CompiledNode::Grammar(name) => Ok(NodeType::Grammar {
+            name: String::from(*name),
+        }),
+    }
+}
+
+fn sets(compiled: &[(&str, &[&str])]) -> IndexMap<String, IndexSet<String>> {
+    compiled
+        .iter()
+        .map(|(grammar, tokens)| {
+            (
+                String::from(*grammar),
+                tokens.iter().map(|token| String::from(*token)).collect(),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,584 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fs::read_to_string;
+use std::rc::Rc;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::error::{GrammarError, ParseError};
+use crate::grammar;
+use crate::lr::{productions, Production};
+use crate::parser::{
+    build_nullable, cover, splice_helpers, unexpected_token, validate, GrammarName,
+    GrammarVariants, Helper, NodeType, NullableSet, TokenName, AST, EOF,
+};
+use crate::precedence::{self, Operators};
+use crate::tokenizer::{end_span, Span, Token, Tokenizer};
+
+/// Ambiguities report a derivation for at most this many alternatives of the ambiguous node.
+const DERIVATIONS: usize = 8;
+
+/// A chart parser accepting any grammar the [Parser](crate::Parser) reads, including
+/// left-recursive and ambiguous ones. Input with more than one derivation is reported as
+/// [ParseError::Ambiguous] instead of picking one of them.
+pub struct EarleyParser {
+    grammars: IndexMap<GrammarName, GrammarVariants>,
+    productions: Vec<Production>,
+    /// Indices of the productions of each grammar.
+    variants: IndexMap<GrammarName, Vec<usize>>,
+    nullable: NullableSet,
+    tokenizer: Tokenizer,
+    helpers: IndexMap<GrammarName, Helper>,
+    operators: Operators,
+}
+
+impl EarleyParser {
+    pub fn from_file(path: &str, tokenizer: Tokenizer) -> Result<EarleyParser, GrammarError> {
+        let content = match read_to_string(path) {
+            Err(e) => return Err(GrammarError::Io(e)),
+            Ok(f) => f,
+        };
+
+        EarleyParser::from_str(&content, tokenizer)
+    }
+
+    /// Reads grammars in the same format as
+    /// [Parser::from_str_with_options](crate::Parser::from_str_with_options).
+    pub fn from_str(content: &str, mut tokenizer: Tokenizer) -> Result<EarleyParser, GrammarError> {
+        let declared = grammar::read(content, &mut tokenizer)?;
+        declared.reject_annotations()?;
+        if declared.declared.is_empty() {
+            return Err(GrammarError::Empty);
+        }
+        validate(&declared.declared)?;
+
+        let grammars = declared.declared;
+        let productions = productions(&grammars);
+        let mut variants: IndexMap<GrammarName, Vec<usize>> = IndexMap::new();
+        for (index, production) in productions.iter().enumerate() {
+            variants
+                .entry(production.grammar.clone())
+                .or_default()
+                .push(index);
+        }
+
+        Ok(EarleyParser {
+            nullable: build_nullable(&grammars),
+            grammars,
+            productions,
+            variants,
+            tokenizer,
+            helpers: declared
+                .helpers
+                .into_iter()
+                .map(|name| (name, Helper::Inline))
+                .collect(),
+            operators: declared.operators,
+        })
+    }
+
+    /// Grammars in declaration order; the first one is the start grammar.
+    pub fn grammars(&self) -> &IndexMap<GrammarName, GrammarVariants> {
+        &self.grammars
+    }
+
+    pub fn productions(&self) -> &[Production] {
+        &self.productions
+    }
+
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    pub fn parse(&self, content: &str) -> Result<Rc<RefCell<AST>>, ParseError> {
+        let mut tokens = self.tokenizer.parse(content)?;
+        tokens.push(Token {
+            name: EOF.to_string(),
+            value: EOF.to_string(),
+            span: end_span(content),
+        });
+
+        let Some(start) = self.grammars.keys().next() else {
+            return Err(ParseError::NoGrammars);
+        };
+        let chart = self.recognize(start, &tokens)?;
+
+        let mut forest = Forest::new(self, &tokens, &chart);
+        let root = Key {
+            grammar: start,
+            start: 0,
+            end: tokens.len() - 1,
+        };
+
+        let mut path = vec![];
+        if let Some((ambiguous, alternatives)) = forest.ambiguity(root, &mut path) {
+            // Helpers are spliced into their parents, so the report starts at the closest
+            // grammar that stays in the tree.
+            let reported = path
+                .iter()
+                .chain([&ambiguous])
+                .rev()
+                .find(|key| !self.helpers.contains_key(key.grammar))
+                .copied()
+                .unwrap_or(root);
+
+            let derivations: Vec<Rc<RefCell<AST>>> = (0..alternatives)
+                .filter_map(|alternative| {
+                    let mut forced = Some((ambiguous, alternative));
+                    forest.build(reported, &mut vec![], &mut forced)
+                })
+                .map(|derivation| self.finish(derivation))
+                .collect();
+
+            let span = derivations
+                .first()
+                .map(|derivation| derivation.borrow().span())
+                .unwrap_or_default();
+            return Err(ParseError::Ambiguous {
+                grammar: String::from(reported.grammar),
+                span,
+                derivations,
+            });
+        }
+
+        match forest.build(root, &mut vec![], &mut None) {
+            Some(ast) => Ok(self.finish(ast)),
+            None => Err(ParseError::NoGrammars),
+        }
+    }
+
+    /// Fills one item set per token position, returning them once the start grammar
+    /// covers all of the input.
+    fn recognize(
+        &self,
+        start: &GrammarName,
+        tokens: &[Token],
+    ) -> Result<Vec<IndexSet<Item>>, ParseError> {
+        let length = tokens.len() - 1;
+        let mut chart: Vec<IndexSet<Item>> = vec![IndexSet::new(); length + 1];
+        for production in self.variants[start].iter() {
+            chart[0].insert(Item {
+                production: *production,
+                dot: 0,
+                origin: 0,
+            });
+        }
+
+        for position in 0..=length {
+            let mut index = 0;
+            while index < chart[position].len() {
+                let item = chart[position][index];
+                index += 1;
+
+                let production = &self.productions[item.production];
+                match production.body.get(item.dot) {
+                    None => {
+                        let waiting: Vec<Item> = chart[item.origin]
+                            .iter()
+                            .filter(|candidate| {
+                                matches!(self.next(candidate),
+                                    Some(NodeType::Grammar { name }) if *name == production.grammar)
+                            })
+                            .map(Item::advance)
+                            .collect();
+                        chart[position].extend(waiting);
+                    }
+                    Some(NodeType::Grammar { name }) => {
+                        for production in self.variants[name].iter() {
+                            chart[position].insert(Item {
+                                production: *production,
+                                dot: 0,
+                                origin: position,
+                            });
+                        }
+                        // Nullable grammars are completed right away, since their empty
+                        // completion may come before items waiting for them.
+                        if self.nullable.contains(name) {
+                            chart[position].insert(item.advance());
+                        }
+                    }
+                    Some(NodeType::Token { name, .. }) => {
+                        if position < length && tokens[position].name == *name {
+                            chart[position + 1].insert(item.advance());
+                        }
+                    }
+                }
+            }
+
+            if position < length && chart[position + 1].is_empty() {
+                return Err(unexpected_token(
+                    &tokens[position],
+                    self.expected(&chart[position]),
+                    None,
+                ));
+            }
+        }
+
+        let accepted = chart[length].iter().any(|item| {
+            item.origin == 0
+                && self.productions[item.production].grammar == *start
+                && self.next(item).is_none()
+        });
+        if !accepted {
+            return Err(unexpected_token(
+                &tokens[length],
+                self.expected(&chart[length]),
+                None,
+            ));
+        }
+        Ok(chart)
+    }
+
+    fn next(&self, item: &Item) -> Option<&NodeType> {
+        self.productions[item.production].body.get(item.dot)
+    }
+
+    /// Tokens that items of the set can shift.
+    fn expected(&self, items: &IndexSet<Item>) -> Vec<TokenName> {
+        let expected: IndexSet<TokenName> = items
+            .iter()
+            .filter_map(|item| match self.next(item) {
+                Some(NodeType::Token { name, .. }) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        expected.into_iter().collect()
+    }
+
+    fn finish(&self, ast: Rc<RefCell<AST>>) -> Rc<RefCell<AST>> {
+        if !self.helpers.is_empty() {
+            splice_helpers(&ast, &self.helpers);
+        }
+        if !self.operators.expressions.is_empty() {
+            precedence::fold(&ast, &self.operators);
+        }
+        ast
+    }
+}
+
+/// A production with a dot before the next symbol to match, started at the origin position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Item {
+    production: usize,
+    dot: usize,
+    origin: usize,
+}
+
+impl Item {
+    fn advance(&self) -> Item {
+        Item {
+            dot: self.dot + 1,
+            ..*self
+        }
+    }
+}
+
+/// A grammar deriving the tokens between two positions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key<'a> {
+    grammar: &'a str,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Child<'a> {
+    Token(usize),
+    Grammar(Key<'a>),
+}
+
+/// One way to split the tokens of a [Key] between the symbols of a production.
+#[derive(Debug, Clone)]
+struct Alternative<'a> {
+    production: usize,
+    children: Vec<Child<'a>>,
+}
+
+/// Derivations recovered from the completed items of the chart.
+struct Forest<'a> {
+    parser: &'a EarleyParser,
+    tokens: &'a [Token],
+    /// Completed productions by production index, start and end.
+    completed: IndexSet<(usize, usize, usize)>,
+    /// Ends of the completed grammars by grammar name and start.
+    ends: IndexMap<(&'a str, usize), BTreeSet<usize>>,
+    alternatives: IndexMap<Key<'a>, Vec<Alternative<'a>>>,
+}
+
+impl<'a> Forest<'a> {
+    fn new(parser: &'a EarleyParser, tokens: &'a [Token], chart: &[IndexSet<Item>]) -> Forest<'a> {
+        let mut completed = IndexSet::new();
+        let mut ends: IndexMap<(&str, usize), BTreeSet<usize>> = IndexMap::new();
+        for (end, items) in chart.iter().enumerate() {
+            for item in items.iter().filter(|item| parser.next(item).is_none()) {
+                let grammar = parser.productions[item.production].grammar.as_str();
+                completed.insert((item.production, item.origin, end));
+                ends.entry((grammar, item.origin)).or_default().insert(end);
+            }
+        }
+
+        Forest {
+            parser,
+            tokens,
+            completed,
+            ends,
+            alternatives: IndexMap::new(),
+        }
+    }
+
+    fn alternatives(&mut self, key: Key<'a>) -> &[Alternative<'a>] {
+        if !self.alternatives.contains_key(&key) {
+            let mut alternatives = vec![];
+            for production in self.parser.variants[key.grammar].iter() {
+                if self.completed.contains(&(*production, key.start, key.end)) {
+                    let mut children = vec![];
+                    self.split(
+                        *production,
+                        key.start,
+                        key.end,
+                        &mut children,
+                        &mut alternatives,
+                    );
+                }
+            }
+            self.alternatives.insert(key, alternatives);
+        }
+        &self.alternatives[&key]
+    }
+
+    /// Collects every way the rest of the production matches the tokens up to the end.
+    fn split(
+        &self,
+        production: usize,
+        position: usize,
+        end: usize,
+        children: &mut Vec<Child<'a>>,
+        alternatives: &mut Vec<Alternative<'a>>,
+    ) {
+        if alternatives.len() >= DERIVATIONS {
+            return;
+        }
+
+        let body = &self.parser.productions[production].body;
+        match body.get(children.len()) {
+            None if position == end => alternatives.push(Alternative {
+                production,
+                children: children.clone(),
+            }),
+            Some(NodeType::Token { name, .. })
+                if position < end && self.tokens[position].name == *name =>
+            {
+                children.push(Child::Token(position));
+                self.split(production, position + 1, end, children, alternatives);
+                children.pop();
+            }
+            Some(NodeType::Grammar { name }) => {
+                let Some((grammar, _)) = self.parser.variants.get_key_value(name) else {
+                    return;
+                };
+                let Some(ends) = self.ends.get(&(grammar.as_str(), position)) else {
+                    return;
+                };
+                for next in ends.range(position..=end) {
+                    children.push(Child::Grammar(Key {
+                        grammar,
+                        start: position,
+                        end: *next,
+                    }));
+                    self.split(production, *next, end, children, alternatives);
+                    children.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Finds the first node, top-down, with more than one alternative, along with the nodes
+    /// above it. Nodes with a single alternative can't form a cycle, so the search ends.
+    fn ambiguity(&mut self, key: Key<'a>, path: &mut Vec<Key<'a>>) -> Option<(Key<'a>, usize)> {
+        let alternatives = self.alternatives(key);
+        if alternatives.len() > 1 {
+            return Some((key, alternatives.len()));
+        }
+
+        let children: Vec<Key> = alternatives
+            .first()?
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                Child::Grammar(key) => Some(*key),
+                Child::Token(_) => None,
+            })
+            .collect();
+
+        path.push(key);
+        for child in children {
+            if let Some(found) = self.ambiguity(child, path) {
+                return Some(found);
+            }
+        }
+        path.pop();
+        None
+    }
+
+    /// Builds the first derivation of the key that doesn't revisit a node on the stack.
+    /// The forced alternative is used once, for the first node with its key.
+    fn build(
+        &mut self,
+        key: Key<'a>,
+        stack: &mut Vec<Key<'a>>,
+        forced: &mut Option<(Key<'a>, usize)>,
+    ) -> Option<Rc<RefCell<AST>>> {
+        if stack.contains(&key) {
+            return None;
+        }
+
+        let mut alternatives = self.alternatives(key).to_vec();
+        match forced {
+            Some((node, alternative)) if *node == key => {
+                alternatives = vec![alternatives.swap_remove(*alternative)];
+                *forced = None;
+            }
+            // A cycle through a forced node unrolls once before being cut.
+            _ => stack.push(key),
+        }
+
+        let mut built = None;
+        for alternative in alternatives.iter() {
+            let mut children = vec![];
+            for child in alternative.children.iter() {
+                let node = match child {
+                    Child::Token(position) => {
+                        Some(Rc::new(RefCell::new((&self.tokens[*position]).into())))
+                    }
+                    Child::Grammar(child) => self.build(*child, stack, forced),
+                };
+                match node {
+                    Some(node) => children.push(node),
+                    None => break,
+                }
+            }
+
+            if children.len() == alternative.children.len() {
+                let production = &self.parser.productions[alternative.production];
+                let spans: Vec<Span> = children.iter().map(|child| child.borrow().span()).collect();
+                built = Some(Rc::new(RefCell::new(AST::Grammar {
+                    name: production.grammar.clone(),
+                    span: cover(&spans).unwrap_or(self.tokens[key.start].span.empty()),
+                    children,
+                })));
+                break;
+            }
+        }
+
+        if stack.last() == Some(&key) {
+            stack.pop();
+        }
+        built
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser(tokens: &str, grammar: &str) -> EarleyParser {
+        EarleyParser::from_str(grammar, tokens.parse().unwrap()).unwrap()
+    }
+
+    fn shape(ast: &AST) -> String {
+        match ast {
+            AST::Token { value, .. } => value.clone(),
+            AST::Grammar { name, children, .. } => {
+                let children: Vec<String> = children.iter().map(|c| shape(&c.borrow())).collect();
+                format!("{name}({})", children.join(" "))
+            }
+            AST::Error { name, .. } => format!("!{name}"),
+        }
+    }
+
+    #[test]
+    fn left_recursion() {
+        let parser = parser(
+            "num = [0-9]+\n+ = \\+\n%skip whitespace = \\s+",
+            "E -> E + T | T\nT -> num",
+        );
+        let ast = parser.parse("1 + 2 + 3").unwrap();
+        assert_eq!(shape(&ast.borrow()), "E(E(E(T(1)) + T(2)) + T(3))");
+        assert_eq!(shape(&parser.parse("1").unwrap().borrow()), "E(T(1))");
+        assert!(parser.parse("1 +").is_err());
+        assert!(parser.parse("+ 1").is_err());
+    }
+
+    #[test]
+    fn nullable_rules() {
+        let parser = parser(
+            "b = b\n%skip whitespace = \\s+",
+            "A -> B B\nB -> b | epsilon",
+        );
+        assert_eq!(shape(&parser.parse("").unwrap().borrow()), "A(B() B())");
+        assert_eq!(
+            shape(&parser.parse("b b").unwrap().borrow()),
+            "A(B(b) B(b))"
+        );
+        assert!(parser.parse("b b b").is_err());
+
+        // A single b could be either of the two, so it is ambiguous.
+        match parser.parse("b") {
+            Err(ParseError::Ambiguous {
+                grammar,
+                derivations,
+                ..
+            }) => {
+                assert_eq!(grammar, "A");
+                let mut shapes: Vec<String> =
+                    derivations.iter().map(|d| shape(&d.borrow())).collect();
+                shapes.sort();
+                assert_eq!(shapes, ["A(B() B(b))", "A(B(b) B())"]);
+            }
+            other => panic!("b is not ambiguous: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn ambiguous_sum() {
+        let parser = parser(
+            "num = [0-9]+\n+ = \\+\n%skip whitespace = \\s+",
+            "E -> E + E | num",
+        );
+        assert_eq!(
+            shape(&parser.parse("1 + 2").unwrap().borrow()),
+            "E(E(1) + E(2))"
+        );
+
+        let Err(error) = parser.parse("1 + 2 + 3") else {
+            panic!("1 + 2 + 3 is not ambiguous");
+        };
+        assert_eq!(
+            error.to_string(),
+            "Ambiguous input: E at 1:1 has 2 alternatives at its first ambiguous node."
+        );
+        let ParseError::Ambiguous {
+            grammar,
+            span,
+            derivations,
+        } = error
+        else {
+            unreachable!();
+        };
+        assert_eq!(grammar, "E");
+        assert_eq!((span.line, span.column), (1, 1));
+        let mut shapes: Vec<String> = derivations.iter().map(|d| shape(&d.borrow())).collect();
+        shapes.sort();
+        assert_eq!(
+            shapes,
+            ["E(E(1) + E(E(2) + E(3)))", "E(E(E(1) + E(2)) + E(3))"]
+        );
+
+        // 1+2+3+4 has five derivations, but only the root's alternatives are reported.
+        match parser.parse("1 + 2 + 3 + 4") {
+            Err(ParseError::Ambiguous { derivations, .. }) => assert_eq!(derivations.len(), 3),
+            other => panic!("1 + 2 + 3 + 4 is not ambiguous: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::rc::Rc;
+
+use crate::lr::{LrAlgorithm, LrConflict};
+use crate::parser::{Conflict, AST, MAX_LOOKAHEAD};
+use crate::tokenizer::{Span, Token};
+
+/// Errors produced while reading token declarations or splitting the input into tokens.
+#[derive(Debug)]
+pub enum LexError {
+    Io(io::Error),
+    InvalidDeclaration { line: usize },
+    InvalidPattern { name: String, pattern: String },
+    InvalidTabWidth { value: String },
+    UnknownToken { value: String, span: Span },
+    InconsistentDedent { span: Span },
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::Io(e) => write!(f, "Unable to open the specified file: {e}"),
+            LexError::InvalidDeclaration { line } => write!(
+                f,
+                "The file must contain token declarations with NAME = PATTERN format, \
+                 line {line} is not one."
+            ),
+            LexError::InvalidPattern { name, pattern } => write!(
+                f,
+                "Unable to parse {name} token - {pattern} is an incorrect regular expression."
+            ),
+            LexError::InvalidTabWidth { value } => write!(
+                f,
+                "Unable to parse %indent - {value} is an incorrect tab width."
+            ),
+            LexError::UnknownToken { value, .. } if value.trim().is_empty() => write!(
+                f,
+                "Unknown token '{}'. Whitespace is only skipped implicitly without %skip \
+                 patterns; declare one like `%skip whitespace = \\s+`.",
+                value.escape_default()
+            ),
+            LexError::UnknownToken { value, .. } => write!(f, "Unknown token {value}."),
+            LexError::InconsistentDedent { span } => write!(
+                f,
+                "Inconsistent dedent on line {}, column {}.",
+                span.line, span.column
+            ),
+        }
+    }
+}
+
+impl Error for LexError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LexError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Errors produced while reading a grammar and building its parsing table.
+#[derive(Debug)]
+pub enum GrammarError {
+    Io(io::Error),
+    Lex(LexError),
+    Empty,
+    /// A group that isn't closed, or a `)` without a group to close.
+    InvalidSyntax {
+        line: usize,
+    },
+    /// A word starting with `)` that isn't `)`, `)*`, `)+` or `)?`.
+    InvalidOperator {
+        line: usize,
+        word: String,
+    },
+    /// Symbols after `%prefer`, `%avoid` or `%reject`, which must end a variant.
+    MisplacedAnnotation {
+        line: usize,
+    },
+    InvalidDirective {
+        line: usize,
+    },
+    /// `%prefer`, `%avoid` or `%reject` given to a parser other than the GLR one.
+    UnsupportedAnnotation {
+        grammar: String,
+        annotation: String,
+    },
+    UndefinedGrammar {
+        name: String,
+        rule: String,
+    },
+    UndefinedToken {
+        name: String,
+        rule: String,
+    },
+    /// Left recursion that can't be rewritten, as the path from a grammar back to itself.
+    LeftRecursion {
+        cycle: Vec<String>,
+    },
+    /// The grammar is not LL(k) for the configured lookahead. `minimum` is the smallest
+    /// lookahead up to [MAX_LOOKAHEAD] that resolves every conflict, if there is one.
+    Conflicts {
+        lookahead: usize,
+        minimum: Option<usize>,
+        conflicts: Vec<Conflict>,
+    },
+    LrConflicts {
+        algorithm: LrAlgorithm,
+        conflicts: Vec<LrConflict>,
+    },
+}
+
+impl Display for GrammarError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GrammarError::Io(e) => write!(f, "Unable to open the specified file: {e}"),
+            GrammarError::Lex(e) => write!(f, "{e}"),
+            GrammarError::Empty => write!(f, "The grammar doesn't have any rules."),
+            GrammarError::InvalidSyntax { line } => {
+                write!(f, "Unbalanced parentheses in the grammar on line {line}.")
+            }
+            GrammarError::InvalidOperator { line, word } => write!(
+                f,
+                "Unable to close a group with {word} in the grammar on line {line}, \
+                 expected ), )*, )+ or )?."
+            ),
+            GrammarError::MisplacedAnnotation { line } => write!(
+                f,
+                "An annotation must end a variant outside of groups in the grammar on line {line}."
+            ),
+            GrammarError::InvalidDirective { line } => {
+                write!(f, "Invalid directive in the grammar on line {line}.")
+            }
+            GrammarError::UnsupportedAnnotation {
+                grammar,
+                annotation,
+            } => write!(
+                f,
+                "{annotation} in {grammar} is only supported by the GLR parser."
+            ),
+            GrammarError::UndefinedGrammar { name, rule } => {
+                write!(f, "Grammar {name} used in {rule} is not defined.")
+            }
+            GrammarError::UndefinedToken { name, rule } => {
+                write!(f, "Token {name} used in {rule} is not defined.")
+            }
+            GrammarError::LeftRecursion { cycle } => {
+                write!(
+                    f,
+                    "Left recursion can't be eliminated: {}",
+                    cycle.join(" -> ")
+                )
+            }
+            GrammarError::Conflicts {
+                lookahead,
+                minimum,
+                conflicts,
+            } => {
+                write!(f, "The grammar is not LL({lookahead}):")?;
+                for conflict in conflicts.iter() {
+                    write!(f, "\n{conflict}")?;
+                }
+                match minimum {
+                    Some(minimum) => write!(f, "\nIt is LL({minimum})."),
+                    None => {
+                        let limit = MAX_LOOKAHEAD.max(*lookahead);
+                        write!(f, "\nIt is not LL(k) for any k up to {limit}.")
+                    }
+                }
+            }
+            GrammarError::LrConflicts {
+                algorithm,
+                conflicts,
+            } => {
+                write!(f, "The grammar is not {algorithm}:")?;
+                for conflict in conflicts.iter() {
+                    write!(f, "\n{conflict}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for GrammarError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GrammarError::Io(e) => Some(e),
+            GrammarError::Lex(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Errors produced while parsing the input with a grammar.
+#[derive(Debug)]
+pub enum ParseError {
+    Lex(LexError),
+    NoGrammars,
+    UnexpectedEndOfStream {
+        span: Span,
+    },
+    UnexpectedToken {
+        token: Box<Token>,
+        /// Tokens the parser was able to accept at this position.
+        expected: Vec<String>,
+        /// The grammar that was being expanded, if any.
+        rule: Option<String>,
+    },
+    /// The input can be derived in several ways. The derivations are trees of the grammar
+    /// that covers the ambiguous part of the input, one for each alternative of the first
+    /// ambiguous node found top-down, up to a fixed limit. They are not every derivation of
+    /// the input: other nodes may be ambiguous too.
+    Ambiguous {
+        grammar: String,
+        span: Span,
+        derivations: Vec<Rc<RefCell<AST>>>,
+    },
+    /// Disambiguation filters removed every derivation of the input.
+    Rejected {
+        span: Span,
+    },
+}
+
+impl ParseError {
+    /// Location of the error in the input, when it is known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::Lex(LexError::UnknownToken { span, .. })
+            | ParseError::Lex(LexError::InconsistentDedent { span }) => Some(*span),
+            ParseError::UnexpectedEndOfStream { span } => Some(*span),
+            ParseError::UnexpectedToken { token, .. } => Some(token.span),
+            ParseError::Ambiguous { span, .. } | ParseError::Rejected { span } => Some(*span),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Lex(e) => write!(f, "{e}"),
+            ParseError::NoGrammars => write!(f, "Parser doesn't have any grammars."),
+            ParseError::UnexpectedEndOfStream { span } => write!(
+                f,
+                "Unexpected end of stream at {}:{}.",
+                span.line, span.column
+            ),
+            ParseError::UnexpectedToken {
+                token, expected, ..
+            } => {
+                write!(
+                    f,
+                    "Unexpected token '{}' at {}:{}",
+                    token.value.escape_default(),
+                    token.span.line,
+                    token.span.column
+                )?;
+
+                if !expected.is_empty() {
+                    let names: Vec<String> = expected.iter().map(|name| quote(name)).collect();
+                    write!(f, ", expected one of: {}", names.join(", "))?;
+                }
+                Ok(())
+            }
+            ParseError::Ambiguous {
+                grammar,
+                span,
+                derivations,
+            } => write!(
+                f,
+                "Ambiguous input: {grammar} at {}:{} has {} alternatives at its first ambiguous node.",
+                span.line,
+                span.column,
+                derivations.len()
+            ),
+            ParseError::Rejected { span } => write!(
+                f,
+                "Every derivation of the input at {}:{} is rejected.",
+                span.line, span.column
+            ),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Lex(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Errors produced while converting an [AST](crate::AST) into the types emitted by
+/// [Parser::typed_ast](crate::Parser::typed_ast).
+#[derive(Debug)]
+pub enum AstError {
+    UnexpectedNode {
+        expected: String,
+        found: String,
+        span: Span,
+    },
+}
+
+impl Display for AstError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AstError::UnexpectedNode {
+                expected,
+                found,
+                span,
+            } => write!(
+                f,
+                "Expected {expected} at {}:{}, found {found}.",
+                span.line, span.column
+            ),
+        }
+    }
+}
+
+impl Error for AstError {}
+
+impl From<LexError> for GrammarError {
+    fn from(value: LexError) -> Self {
+        GrammarError::Lex(value)
+    }
+}
+
+impl From<LexError> for ParseError {
+    fn from(value: LexError) -> Self {
+        ParseError::Lex(value)
+    }
+}
+
+/// Quotes token names made of punctuation so they stand out in messages.
+fn quote(name: &str) -> String {
+    if name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        String::from(name)
+    } else {
+        format!("'{name}'")
+    }
+}
@@ -0,0 +1,191 @@
+use indexmap::IndexMap;
+
+use crate::parser::{is_epsilon, GrammarName, GrammarVariant, GrammarVariants, Helper, NodeType};
+use crate::tokenizer::{Tokenizer, EPSILON};
+
+/// Moves prefixes shared by several variants of a grammar into a helper grammar:
+/// `V -> number | number . number` becomes `V -> number V~1` and `V~1 -> epsilon | . number`.
+/// The helpers are spliced into their parent, so the AST keeps the shape of the declared rule.
+pub(crate) fn factor(
+    grammars: &mut IndexMap<GrammarName, GrammarVariants>,
+) -> IndexMap<GrammarName, Helper> {
+    let mut helpers = IndexMap::new();
+    let mut pending: Vec<GrammarName> = grammars.keys().rev().cloned().collect();
+
+    while let Some(grammar) = pending.pop() {
+        let variants = &grammars[&grammar];
+        let Some((first, shared)) = variants.iter().enumerate().find_map(|(index, variant)| {
+            let shared: Vec<usize> = (index..variants.len())
+                .filter(|other| starts_alike(variant, &variants[*other]))
+                .collect();
+            (shared.len() > 1).then_some((index, shared))
+        }) else {
+            continue;
+        };
+
+        let prefix = common_prefix(shared.iter().map(|index| &variants[*index]));
+        let helper = helper_name(grammars, &grammar);
+
+        let suffixes: GrammarVariants = shared
+            .iter()
+            .map(|index| match &variants[*index][prefix..] {
+                [] => vec![NodeType::Token {
+                    name: String::from(EPSILON),
+                    pattern: Tokenizer::epsilon(),
+                }],
+                suffix => suffix.to_vec(),
+            })
+            .collect();
+
+        let mut factored = variants[first][..prefix].to_vec();
+        factored.push(NodeType::Grammar {
+            name: helper.clone(),
+        });
+
+        let mut rewritten = vec![];
+        for (index, variant) in variants.iter().enumerate() {
+            if index == first {
+                rewritten.push(factored.clone());
+            } else if !shared.contains(&index) {
+                rewritten.push(variant.clone());
+            }
+        }
+
+        grammars.insert(grammar.clone(), rewritten);
+        grammars.insert(helper.clone(), suffixes);
+        helpers.insert(helper.clone(), Helper::Inline);
+
+        // Other variants of the grammar and the suffixes may share prefixes of their own.
+        pending.push(helper);
+        pending.push(grammar);
+    }
+
+    helpers
+}
+
+fn starts_alike(left: &GrammarVariant, right: &GrammarVariant) -> bool {
+    match (left.first(), right.first()) {
+        (Some(left), Some(right)) => same_node(left, right) && !is_epsilon(left),
+        _ => false,
+    }
+}
+
+fn common_prefix<'a>(mut variants: impl Iterator<Item = &'a GrammarVariant>) -> usize {
+    let Some(first) = variants.next() else {
+        return 0;
+    };
+    variants.fold(first.len(), |length, variant| {
+        first
+            .iter()
+            .zip(variant.iter())
+            .take(length)
+            .take_while(|(left, right)| same_node(left, right))
+            .count()
+    })
+}
+
+fn same_node(left: &NodeType, right: &NodeType) -> bool {
+    match (left, right) {
+        (NodeType::Token { name: left, .. }, NodeType::Token { name: right, .. })
+        | (NodeType::Grammar { name: left }, NodeType::Grammar { name: right }) => left == right,
+        _ => false,
+    }
+}
+
+/// Names helpers after the declared rule, like the helpers of EBNF operators.
+fn helper_name(grammars: &IndexMap<GrammarName, GrammarVariants>, grammar: &str) -> GrammarName {
+    let rule = grammar.split('~').next().unwrap_or(grammar);
+    (1..)
+        .map(|count| format!("{rule}~{count}"))
+        .find(|name| !grammars.contains_key(name))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, ParserOptions, AST};
+
+    fn parser(grammar: &str) -> Parser {
+        let tokens =
+            "a = a\nb = b\nc = c\nd = d\ne = e\nn = [0-9]\n. = \\.\n%skip whitespace = \\s+";
+        let options = ParserOptions {
+            left_factor: true,
+            ..ParserOptions::default()
+        };
+        Parser::from_str_with_options(grammar, tokens.parse().unwrap(), options).unwrap()
+    }
+
+    fn rules(parser: &Parser) -> Vec<String> {
+        let mut rules = vec![];
+        for (name, variants) in parser.grammars().iter().skip(1) {
+            for variant in variants.iter() {
+                let nodes: Vec<String> = variant.iter().map(|node| node.to_string()).collect();
+                rules.push(format!("{name} -> {}", nodes.join(" ")));
+            }
+        }
+        rules
+    }
+
+    fn shape(ast: &AST) -> String {
+        match ast {
+            AST::Token { value, .. } => value.clone(),
+            AST::Grammar { name, children, .. } => {
+                let children: Vec<String> = children.iter().map(|c| shape(&c.borrow())).collect();
+                format!("{name}({})", children.join(" "))
+            }
+            AST::Error { name, .. } => format!("!{name}"),
+        }
+    }
+
+    #[test]
+    fn common_prefix() {
+        let parser = parser("V -> n | n . n");
+        assert_eq!(
+            rules(&parser),
+            ["V -> n V~1", "V~1 -> epsilon", "V~1 -> . n"]
+        );
+
+        assert_eq!(shape(&parser.parse("1").unwrap().borrow()), "V(1)");
+        assert_eq!(shape(&parser.parse("1.2").unwrap().borrow()), "V(1 . 2)");
+    }
+
+    #[test]
+    fn nested_prefixes() {
+        let parser = parser("S -> a b c | a b d | a e | d");
+        assert_eq!(
+            rules(&parser),
+            [
+                "S -> a S~1",
+                "S -> d",
+                "S~1 -> b S~2",
+                "S~1 -> e",
+                "S~2 -> c",
+                "S~2 -> d",
+            ]
+        );
+
+        assert_eq!(shape(&parser.parse("a b d").unwrap().borrow()), "S(a b d)");
+        assert_eq!(shape(&parser.parse("a e").unwrap().borrow()), "S(a e)");
+        assert_eq!(shape(&parser.parse("d").unwrap().borrow()), "S(d)");
+    }
+
+    #[test]
+    fn helper_names_after_ebnf() {
+        // `b?` already uses S~1.
+        let parser = parser("S -> a b? | a c");
+        assert_eq!(
+            rules(&parser),
+            [
+                "S -> a S~2",
+                "S~1 -> b",
+                "S~1 -> epsilon",
+                "S~2 -> S~1",
+                "S~2 -> c"
+            ]
+        );
+
+        assert_eq!(shape(&parser.parse("a").unwrap().borrow()), "S(a)");
+        assert_eq!(shape(&parser.parse("a b").unwrap().borrow()), "S(a b)");
+        assert_eq!(shape(&parser.parse("a c").unwrap().borrow()), "S(a c)");
+    }
+}
@@ -0,0 +1,946 @@
+use std::cell::RefCell;
+use std::fs::read_to_string;
+use std::rc::Rc;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::error::{GrammarError, ParseError};
+use crate::grammar;
+use crate::lr::{productions, Action, Automaton, LrAlgorithm, Production};
+use crate::parser::{
+    build_first, build_follow, build_nullable, cover, splice_helpers, unexpected_token, validate,
+    GrammarName, GrammarVariants, Helper, NodeType, Parser, TokenName, AST, EOF, ROOT,
+};
+use crate::precedence::{self, Operators};
+use crate::tokenizer::{end_span, Span, Token, Tokenizer};
+
+/// Ambiguities report a derivation for at most this many alternatives of the ambiguous node.
+const DERIVATIONS: usize = 8;
+
+/// Filters written after a variant and applied by [Forest::disambiguate].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Annotation {
+    /// Where alternatives compete, only the preferred variants are kept.
+    Prefer,
+    /// Where alternatives compete, the variant is dropped unless nothing else is left.
+    Avoid,
+    /// Derivations using the variant are dropped.
+    Reject,
+}
+
+/// A generalized LR parser following every action of conflicting LALR(1) table cells
+/// on a graph-structured stack, so all parses of the input end up in a [Forest].
+pub struct GlrParser {
+    grammars: IndexMap<GrammarName, GrammarVariants>,
+    productions: Vec<Production>,
+    states: usize,
+    action: IndexMap<(usize, TokenName), Vec<Action>>,
+    goto: IndexMap<(usize, GrammarName), usize>,
+    tokenizer: Tokenizer,
+    /// Annotations by production index.
+    annotations: IndexMap<usize, Annotation>,
+    helpers: IndexMap<GrammarName, Helper>,
+    operators: Operators,
+}
+
+impl GlrParser {
+    pub fn from_file(path: &str, tokenizer: Tokenizer) -> Result<GlrParser, GrammarError> {
+        let content = match read_to_string(path) {
+            Err(e) => return Err(GrammarError::Io(e)),
+            Ok(f) => f,
+        };
+
+        GlrParser::from_str(&content, tokenizer)
+    }
+
+    /// Reads grammars in the same format as [Parser::from_str_with_options], along with
+    /// `%prefer`, `%avoid` and `%reject` annotations on variants.
+    pub fn from_str(content: &str, mut tokenizer: Tokenizer) -> Result<GlrParser, GrammarError> {
+        let declared = grammar::read(content, &mut tokenizer)?;
+        let Some(start) = declared.declared.keys().next() else {
+            return Err(GrammarError::Empty);
+        };
+        validate(&declared.declared)?;
+
+        let mut grammars = IndexMap::from([(
+            String::from(ROOT),
+            vec![vec![
+                NodeType::Grammar {
+                    name: start.clone(),
+                },
+                Parser::eof(),
+            ]],
+        )]);
+        grammars.extend(declared.declared);
+
+        let variants = grammars.iter().flat_map(|(grammar, variants)| {
+            (0..variants.len()).map(move |variant| (grammar.clone(), variant))
+        });
+        let annotations = variants
+            .enumerate()
+            .filter_map(|(index, variant)| Some((index, *declared.annotations.get(&variant)?)))
+            .collect();
+
+        let productions = productions(&grammars);
+        let nullable = build_nullable(&grammars);
+        let first = build_first(&grammars);
+        let follow = build_follow(&grammars, &first);
+
+        let automaton = Automaton::build(&productions, &nullable, &first, LrAlgorithm::Lalr);
+        let (action, goto) = automaton.candidates(&productions, &follow, LrAlgorithm::Lalr);
+
+        Ok(GlrParser {
+            grammars,
+            productions,
+            states: automaton.states(),
+            action,
+            goto,
+            tokenizer,
+            annotations,
+            helpers: declared
+                .helpers
+                .into_iter()
+                .map(|name| (name, Helper::Inline))
+                .collect(),
+            operators: declared.operators,
+        })
+    }
+
+    /// Grammars in declaration order, starting with the internal root grammar.
+    pub fn grammars(&self) -> &IndexMap<GrammarName, GrammarVariants> {
+        &self.grammars
+    }
+
+    /// Every variant of every grammar; [Action::Reduce] and [Packed] refer to them by index.
+    pub fn productions(&self) -> &[Production] {
+        &self.productions
+    }
+
+    /// Number of states of the LALR(1) automaton.
+    pub fn states(&self) -> usize {
+        self.states
+    }
+
+    /// Every action of each ACTION table cell; cells with more than one action fork the stack.
+    pub fn action(&self) -> &IndexMap<(usize, TokenName), Vec<Action>> {
+        &self.action
+    }
+
+    pub fn goto(&self) -> &IndexMap<(usize, GrammarName), usize> {
+        &self.goto
+    }
+
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    /// Parses the content into a forest of all its derivations.
+    pub fn parse(&self, content: &str) -> Result<Forest, ParseError> {
+        let mut tokens = self.tokenizer.parse(content)?;
+        tokens.push(Token {
+            name: EOF.to_string(),
+            value: EOF.to_string(),
+            span: end_span(content),
+        });
+
+        let mut stack = Stack {
+            parser: self,
+            tokens: &tokens,
+            vertices: vec![Vertex {
+                state: 0,
+                level: 0,
+                edges: vec![],
+            }],
+            nodes: vec![],
+            grammars: IndexMap::new(),
+        };
+        let mut frontier = IndexMap::from([(0, 0)]);
+
+        for (position, token) in tokens.iter().enumerate() {
+            stack.reduce(&mut frontier, position);
+
+            let accepted = frontier
+                .keys()
+                .any(|state| self.actions(*state, token).contains(&Action::Accept));
+            if accepted {
+                break;
+            }
+
+            let mut next = IndexMap::new();
+            let mut shifted = None;
+            for (state, vertex) in frontier.iter() {
+                for action in self.actions(*state, token).iter() {
+                    let Action::Shift(target) = action else {
+                        continue;
+                    };
+                    let node =
+                        *shifted.get_or_insert_with(|| stack.add(ForestNode::Token(token.clone())));
+                    let next = *next
+                        .entry(*target)
+                        .or_insert_with(|| stack.vertex(*target, position + 1));
+                    stack.vertices[next].edges.push((*vertex, node));
+                }
+            }
+
+            if next.is_empty() {
+                let expected: IndexSet<TokenName> = self
+                    .action
+                    .keys()
+                    .filter(|(state, _)| frontier.contains_key(state))
+                    .map(|(_, token)| token.clone())
+                    .collect();
+                return Err(unexpected_token(
+                    token,
+                    expected.into_iter().collect(),
+                    None,
+                ));
+            }
+            frontier = next;
+        }
+
+        let start = &self.productions[0].body[0].to_string();
+        let Some(root) = stack.grammars.get(&(start.as_str(), 0, tokens.len() - 1)) else {
+            return Err(ParseError::NoGrammars);
+        };
+
+        Ok(Forest {
+            root: *root,
+            nodes: stack.nodes,
+            annotations: self.annotations.clone(),
+            helpers: self.helpers.clone(),
+            operators: self.operators.clone(),
+        })
+    }
+
+    fn actions(&self, state: usize, token: &Token) -> &[Action] {
+        match self.action.get(&(state, token.name.clone())) {
+            Some(actions) => actions,
+            None => &[],
+        }
+    }
+}
+
+/// A state pushed at a token position, linked to the vertices below it by the forest node
+/// of the symbol between them.
+struct Vertex {
+    state: usize,
+    level: usize,
+    edges: Vec<(usize, usize)>,
+}
+
+/// The graph-structured stack, building the forest while it reduces.
+struct Stack<'a> {
+    parser: &'a GlrParser,
+    tokens: &'a [Token],
+    vertices: Vec<Vertex>,
+    nodes: Vec<ForestNode>,
+    /// Forest nodes of grammars by name, start and end position.
+    grammars: IndexMap<(&'a str, usize, usize), usize>,
+}
+
+impl<'a> Stack<'a> {
+    fn vertex(&mut self, state: usize, level: usize) -> usize {
+        self.vertices.push(Vertex {
+            state,
+            level,
+            edges: vec![],
+        });
+        self.vertices.len() - 1
+    }
+
+    fn add(&mut self, node: ForestNode) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Applies every reduction of the frontier until no new edge appears, so reductions
+    /// through edges added by other reductions aren't missed.
+    fn reduce(&mut self, frontier: &mut IndexMap<usize, usize>, position: usize) {
+        let token = &self.tokens[position];
+        loop {
+            let mut changed = false;
+            let mut index = 0;
+            while index < frontier.len() {
+                let vertex = frontier[index];
+                index += 1;
+
+                let reductions: Vec<usize> = self
+                    .parser
+                    .actions(self.vertices[vertex].state, token)
+                    .iter()
+                    .filter_map(|action| match action {
+                        Action::Reduce(production) => Some(*production),
+                        _ => None,
+                    })
+                    .collect();
+
+                for production in reductions {
+                    let parser = self.parser;
+                    let Production { grammar, body } = &parser.productions[production];
+                    for (children, end) in self.paths(vertex, body.len()) {
+                        let start = self.vertices[end].level;
+                        let node = self.pack(grammar, start, position, production, children);
+
+                        let state = self.vertices[end].state;
+                        let Some(target) = parser.goto.get(&(state, grammar.clone())) else {
+                            continue;
+                        };
+                        let next = match frontier.get(target) {
+                            Some(next) => *next,
+                            None => {
+                                let next = self.vertex(*target, position);
+                                frontier.insert(*target, next);
+                                next
+                            }
+                        };
+                        if !self.vertices[next].edges.contains(&(end, node)) {
+                            self.vertices[next].edges.push((end, node));
+                            changed = true;
+                        }
+                    }
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+    }
+
+    /// Paths of the given length down from the vertex, as the forest nodes along them
+    /// in input order and the vertex they end at.
+    fn paths(&self, vertex: usize, length: usize) -> Vec<(Vec<usize>, usize)> {
+        if length == 0 {
+            return vec![(vec![], vertex)];
+        }
+
+        let mut paths = vec![];
+        for (target, node) in self.vertices[vertex].edges.iter() {
+            for (mut children, end) in self.paths(*target, length - 1) {
+                children.push(*node);
+                paths.push((children, end));
+            }
+        }
+        paths
+    }
+
+    /// Adds the children as an alternative of the grammar node, creating the node if needed.
+    fn pack(
+        &mut self,
+        grammar: &'a str,
+        start: usize,
+        end: usize,
+        production: usize,
+        children: Vec<usize>,
+    ) -> usize {
+        let packed = Packed {
+            production,
+            children,
+        };
+
+        if let Some(node) = self.grammars.get(&(grammar, start, end)) {
+            if let ForestNode::Grammar { alternatives, .. } = &mut self.nodes[*node] {
+                if !alternatives.contains(&packed) {
+                    alternatives.push(packed);
+                }
+            }
+            return *node;
+        }
+
+        let spans: Vec<Span> = packed
+            .children
+            .iter()
+            .map(|child| self.nodes[*child].span())
+            .collect();
+        let node = self.add(ForestNode::Grammar {
+            name: String::from(grammar),
+            span: cover(&spans).unwrap_or(self.tokens[start].span.empty()),
+            alternatives: vec![packed],
+        });
+        self.grammars.insert((grammar, start, end), node);
+        node
+    }
+}
+
+/// A shared packed parse forest: every grammar deriving the same part of the input is a
+/// single node, and each way it derives that part is one of its [Packed] alternatives.
+#[derive(Debug, Clone)]
+pub struct Forest {
+    root: usize,
+    nodes: Vec<ForestNode>,
+    annotations: IndexMap<usize, Annotation>,
+    helpers: IndexMap<GrammarName, Helper>,
+    operators: Operators,
+}
+
+#[derive(Debug, Clone)]
+pub enum ForestNode {
+    Token(Token),
+    Grammar {
+        name: GrammarName,
+        span: Span,
+        alternatives: Vec<Packed>,
+    },
+}
+
+impl ForestNode {
+    pub fn span(&self) -> Span {
+        match self {
+            ForestNode::Token(token) => token.span,
+            ForestNode::Grammar { span, .. } => *span,
+        }
+    }
+}
+
+/// A production with the forest nodes of its symbols.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packed {
+    pub production: usize,
+    pub children: Vec<usize>,
+}
+
+impl Forest {
+    /// Index of the node deriving the whole input.
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    pub fn nodes(&self) -> &[ForestNode] {
+        &self.nodes
+    }
+
+    /// Number of derivations of the input.
+    pub fn count(&self) -> Count {
+        let mut counts = vec![Counting::Pending; self.nodes.len()];
+        self.count_node(self.root, &mut counts)
+    }
+
+    fn count_node(&self, node: usize, counts: &mut [Counting]) -> Count {
+        match counts[node] {
+            Counting::Done(count) => return count,
+            Counting::Visiting => return Count::Infinite,
+            Counting::Pending => {}
+        }
+
+        let ForestNode::Grammar { alternatives, .. } = &self.nodes[node] else {
+            return Count::Finite(1);
+        };
+
+        counts[node] = Counting::Visiting;
+        let mut count = Count::Finite(0);
+        for packed in alternatives.iter() {
+            let product = packed
+                .children
+                .iter()
+                .fold(Count::Finite(1), |product, child| {
+                    product.mul(self.count_node(*child, counts))
+                });
+            count = count.add(product);
+        }
+        counts[node] = Counting::Done(count);
+        count
+    }
+
+    /// Applies the annotations of the variants: derivations using a `%reject` variant are
+    /// dropped, then wherever alternatives compete, `%prefer` variants win over the others
+    /// and `%avoid` variants lose to them.
+    pub fn disambiguate(&mut self) {
+        let annotations = &self.annotations;
+        let is = |packed: &Packed, annotation: Annotation| {
+            annotations.get(&packed.production) == Some(&annotation)
+        };
+
+        for node in self.nodes.iter_mut() {
+            if let ForestNode::Grammar { alternatives, .. } = node {
+                alternatives.retain(|packed| !is(packed, Annotation::Reject));
+            }
+        }
+        // Rejecting may leave nodes without derivations, which can't compete below.
+        prune(&mut self.nodes);
+
+        for node in self.nodes.iter_mut() {
+            let ForestNode::Grammar { alternatives, .. } = node else {
+                continue;
+            };
+            if alternatives.len() > 1
+                && alternatives
+                    .iter()
+                    .any(|packed| is(packed, Annotation::Prefer))
+            {
+                alternatives.retain(|packed| is(packed, Annotation::Prefer));
+            }
+            if alternatives.len() > 1
+                && alternatives
+                    .iter()
+                    .any(|packed| !is(packed, Annotation::Avoid))
+            {
+                alternatives.retain(|packed| !is(packed, Annotation::Avoid));
+            }
+        }
+        prune(&mut self.nodes);
+    }
+
+    /// Enumerates up to `limit` trees of the input. Trees deriving a node from itself
+    /// are left out.
+    pub fn trees(&self, limit: usize) -> Vec<Rc<RefCell<AST>>> {
+        self.derivations(self.root, &mut vec![], limit)
+            .iter()
+            .map(|derivation| self.finish(self.tree(derivation)))
+            .collect()
+    }
+
+    fn derivations(
+        &self,
+        node: usize,
+        stack: &mut Vec<usize>,
+        limit: usize,
+    ) -> Vec<Rc<Derivation>> {
+        let ForestNode::Grammar { alternatives, .. } = &self.nodes[node] else {
+            return vec![Rc::new(Derivation {
+                node,
+                children: vec![],
+            })];
+        };
+        if stack.contains(&node) {
+            return vec![];
+        }
+
+        stack.push(node);
+        let mut derivations = vec![];
+        for packed in alternatives.iter() {
+            let mut partial: Vec<Vec<Rc<Derivation>>> = vec![vec![]];
+            for child in packed.children.iter() {
+                let options = self.derivations(*child, stack, limit);
+                partial = partial
+                    .iter()
+                    .flat_map(|children| {
+                        options.iter().map(|option| {
+                            let mut children = children.clone();
+                            children.push(option.clone());
+                            children
+                        })
+                    })
+                    .take(limit - derivations.len())
+                    .collect();
+            }
+
+            derivations.extend(
+                partial
+                    .into_iter()
+                    .map(|children| Rc::new(Derivation { node, children })),
+            );
+            if derivations.len() >= limit {
+                break;
+            }
+        }
+        stack.pop();
+        derivations
+    }
+
+    fn tree(&self, derivation: &Derivation) -> Rc<RefCell<AST>> {
+        let ast = match &self.nodes[derivation.node] {
+            ForestNode::Token(token) => token.into(),
+            ForestNode::Grammar { name, span, .. } => AST::Grammar {
+                name: name.clone(),
+                span: *span,
+                children: derivation
+                    .children
+                    .iter()
+                    .map(|child| self.tree(child))
+                    .collect(),
+            },
+        };
+        Rc::new(RefCell::new(ast))
+    }
+
+    /// Collapses the forest into its only tree. Forests with several derivations, usually
+    /// left after [Forest::disambiguate], are reported as [ParseError::Ambiguous].
+    pub fn ast(&self) -> Result<Rc<RefCell<AST>>, ParseError> {
+        let rejected = ParseError::Rejected {
+            span: self.nodes[self.root].span(),
+        };
+
+        let mut path = vec![];
+        if let Some(ambiguous) = self.ambiguity(self.root, &mut path) {
+            // Helpers are spliced into their parents, so the report starts at the closest
+            // grammar that stays in the tree.
+            let reported = path
+                .iter()
+                .chain([&ambiguous])
+                .rev()
+                .find(|node| !self.is_helper(**node))
+                .copied()
+                .unwrap_or(self.root);
+
+            let ForestNode::Grammar { name, .. } = &self.nodes[reported] else {
+                return Err(rejected);
+            };
+            let alternatives = match &self.nodes[ambiguous] {
+                ForestNode::Grammar { alternatives, .. } => alternatives.len(),
+                ForestNode::Token(_) => 0,
+            };
+
+            // One tree per alternative of the ambiguous node; [Forest::count] has the total.
+            let derivations: Vec<Rc<RefCell<AST>>> = (0..alternatives.min(DERIVATIONS))
+                .filter_map(|alternative| {
+                    let mut forced = Some((ambiguous, alternative));
+                    self.build(reported, &mut vec![], &mut forced)
+                })
+                .map(|derivation| self.finish(derivation))
+                .collect();
+
+            return Err(ParseError::Ambiguous {
+                grammar: name.clone(),
+                span: self.nodes[reported].span(),
+                derivations,
+            });
+        }
+
+        match self.build(self.root, &mut vec![], &mut None) {
+            Some(ast) => Ok(self.finish(ast)),
+            None => Err(rejected),
+        }
+    }
+
+    fn is_helper(&self, node: usize) -> bool {
+        match &self.nodes[node] {
+            ForestNode::Grammar { name, .. } => self.helpers.contains_key(name),
+            ForestNode::Token(_) => false,
+        }
+    }
+
+    /// Finds the first node, top-down, with more than one alternative, along with the nodes
+    /// above it. Nodes with a single alternative can't form a cycle, so the search ends.
+    fn ambiguity(&self, node: usize, path: &mut Vec<usize>) -> Option<usize> {
+        let ForestNode::Grammar { alternatives, .. } = &self.nodes[node] else {
+            return None;
+        };
+        if alternatives.len() > 1 {
+            return Some(node);
+        }
+
+        path.push(node);
+        for child in alternatives.first()?.children.iter() {
+            if let Some(found) = self.ambiguity(*child, path) {
+                return Some(found);
+            }
+        }
+        path.pop();
+        None
+    }
+
+    /// Builds the first derivation of the node that doesn't revisit a node on the stack.
+    /// The forced alternative is used once, for the first time its node is built.
+    fn build(
+        &self,
+        node: usize,
+        stack: &mut Vec<usize>,
+        forced: &mut Option<(usize, usize)>,
+    ) -> Option<Rc<RefCell<AST>>> {
+        let (name, span, alternatives) = match &self.nodes[node] {
+            ForestNode::Token(token) => return Some(Rc::new(RefCell::new(token.into()))),
+            ForestNode::Grammar {
+                name,
+                span,
+                alternatives,
+            } => (name, span, alternatives),
+        };
+        if stack.contains(&node) {
+            return None;
+        }
+
+        let alternatives = match forced {
+            Some((target, alternative)) if *target == node => {
+                let alternatives = &alternatives[*alternative..*alternative + 1];
+                *forced = None;
+                alternatives
+            }
+            // A cycle through a forced node unrolls once before being cut.
+            _ => {
+                stack.push(node);
+                alternatives
+            }
+        };
+
+        let mut built = None;
+        for packed in alternatives.iter() {
+            let mut children = vec![];
+            for child in packed.children.iter() {
+                match self.build(*child, stack, forced) {
+                    Some(child) => children.push(child),
+                    None => break,
+                }
+            }
+
+            if children.len() == packed.children.len() {
+                built = Some(Rc::new(RefCell::new(AST::Grammar {
+                    name: name.clone(),
+                    span: *span,
+                    children,
+                })));
+                break;
+            }
+        }
+
+        if stack.last() == Some(&node) {
+            stack.pop();
+        }
+        built
+    }
+
+    fn finish(&self, ast: Rc<RefCell<AST>>) -> Rc<RefCell<AST>> {
+        if !self.helpers.is_empty() {
+            splice_helpers(&ast, &self.helpers);
+        }
+        if !self.operators.expressions.is_empty() {
+            precedence::fold(&ast, &self.operators);
+        }
+        ast
+    }
+}
+
+/// Drops alternatives using nodes that are left without any finite derivation.
+fn prune(nodes: &mut [ForestNode]) {
+    let mut productive: Vec<bool> = nodes
+        .iter()
+        .map(|node| matches!(node, ForestNode::Token(_)))
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, node) in nodes.iter().enumerate() {
+            let ForestNode::Grammar { alternatives, .. } = node else {
+                continue;
+            };
+            if !productive[index]
+                && alternatives
+                    .iter()
+                    .any(|packed| packed.children.iter().all(|child| productive[*child]))
+            {
+                productive[index] = true;
+                changed = true;
+            }
+        }
+    }
+
+    for node in nodes.iter_mut() {
+        if let ForestNode::Grammar { alternatives, .. } = node {
+            alternatives.retain(|packed| packed.children.iter().all(|child| productive[*child]));
+        }
+    }
+}
+
+/// Number of derivations in a [Forest].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Count {
+    Finite(usize),
+    /// Finitely many, but more than `usize::MAX`.
+    Overflow,
+    /// A grammar derives itself, so there are infinitely many.
+    Infinite,
+}
+
+impl Count {
+    fn add(self, other: Count) -> Count {
+        match (self, other) {
+            (Count::Infinite, _) | (_, Count::Infinite) => Count::Infinite,
+            (Count::Overflow, _) | (_, Count::Overflow) => Count::Overflow,
+            (Count::Finite(left), Count::Finite(right)) => left
+                .checked_add(right)
+                .map_or(Count::Overflow, Count::Finite),
+        }
+    }
+
+    fn mul(self, other: Count) -> Count {
+        match (self, other) {
+            (Count::Finite(0), _) | (_, Count::Finite(0)) => Count::Finite(0),
+            (Count::Infinite, _) | (_, Count::Infinite) => Count::Infinite,
+            (Count::Overflow, _) | (_, Count::Overflow) => Count::Overflow,
+            (Count::Finite(left), Count::Finite(right)) => left
+                .checked_mul(right)
+                .map_or(Count::Overflow, Count::Finite),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Counting {
+    Pending,
+    Visiting,
+    Done(Count),
+}
+
+/// One tree of the forest, sharing subtrees with the other enumerated trees.
+struct Derivation {
+    node: usize,
+    children: Vec<Rc<Derivation>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EarleyParser, LrParser, PegParser};
+
+    const SUMS: &str = "num = [0-9]+\n+ = \\+\n* = \\*\n%skip whitespace = \\s+";
+    const IFS: &str = "if = if\nthen = then\nelse = else\nid = [a-z]+\n%skip whitespace = \\s+";
+
+    fn parser(tokens: &str, grammar: &str) -> GlrParser {
+        GlrParser::from_str(grammar, tokens.parse().unwrap()).unwrap()
+    }
+
+    fn shape(ast: &AST) -> String {
+        match ast {
+            AST::Token { value, .. } => value.clone(),
+            AST::Grammar { name, children, .. } => {
+                let children: Vec<String> = children.iter().map(|c| shape(&c.borrow())).collect();
+                format!("{name}({})", children.join(" "))
+            }
+            AST::Error { name, .. } => format!("!{name}"),
+        }
+    }
+
+    fn sum(operands: usize) -> String {
+        let operands: Vec<String> = (1..=operands).map(|n| n.to_string()).collect();
+        operands.join(" + ")
+    }
+
+    /// Disambiguates the forest of the input and returns its only tree.
+    fn disambiguated(parser: &GlrParser, content: &str) -> String {
+        let mut forest = parser.parse(content).unwrap();
+        forest.disambiguate();
+        assert_eq!(forest.count(), Count::Finite(1), "{content}");
+        let ast = forest.ast().unwrap();
+        let ast = ast.borrow();
+        shape(&ast)
+    }
+
+    #[test]
+    fn catalan_counts() {
+        let parser = parser(SUMS, "E -> E + E | num");
+        for (operands, count) in [(1, 1), (2, 1), (3, 2), (4, 5), (5, 14), (6, 42)] {
+            let forest = parser.parse(&sum(operands)).unwrap();
+            assert_eq!(forest.count(), Count::Finite(count), "{operands} operands");
+            assert_eq!(forest.trees(100).len(), count, "{operands} operands");
+        }
+        assert_eq!(parser.parse(&sum(6)).unwrap().trees(8).len(), 8);
+
+        let ast = parser.parse("1 + 2").unwrap().ast().unwrap();
+        assert_eq!(shape(&ast.borrow()), "E(E(1) + E(2))");
+        assert!(parser.parse("1 +").is_err());
+    }
+
+    #[test]
+    fn overflow_and_infinite_counts() {
+        // C(36) still fits in 64 bits, C(37) doesn't.
+        let sums = parser(SUMS, "E -> E + E | num");
+        let count = sums.parse(&sum(37)).unwrap().count();
+        assert_eq!(count, Count::Finite(11_959_798_385_860_453_492));
+        assert_eq!(sums.parse(&sum(38)).unwrap().count(), Count::Overflow);
+
+        let cyclic = parser(SUMS, "E -> E | num");
+        let forest = cyclic.parse("1").unwrap();
+        assert_eq!(forest.count(), Count::Infinite);
+        let trees: Vec<String> = forest.trees(8).iter().map(|t| shape(&t.borrow())).collect();
+        assert_eq!(trees, ["E(1)"]);
+
+        assert_eq!(Count::Overflow.add(Count::Infinite), Count::Infinite);
+        assert_eq!(Count::Overflow.mul(Count::Finite(2)), Count::Overflow);
+        assert_eq!(Count::Infinite.mul(Count::Finite(0)), Count::Finite(0));
+        assert_eq!(
+            Count::Finite(usize::MAX).add(Count::Finite(1)),
+            Count::Overflow
+        );
+        assert_ne!(Count::Overflow, Count::Infinite);
+    }
+
+    #[test]
+    fn prefer() {
+        let parser = parser(
+            IFS,
+            "S -> if E then S %prefer | if E then S else S | id\nE -> id",
+        );
+        let content = "if a then if b then c else d";
+        assert_eq!(parser.parse(content).unwrap().count(), Count::Finite(2));
+        assert_eq!(
+            disambiguated(&parser, content),
+            "S(if E(a) then S(if E(b) then S(c) else S(d)))"
+        );
+    }
+
+    #[test]
+    fn avoid() {
+        let parser = parser(
+            IFS,
+            "S -> if E then S %avoid | if E then S else S | id\nE -> id",
+        );
+        assert_eq!(
+            disambiguated(&parser, "if a then if b then c else d"),
+            "S(if E(a) then S(if E(b) then S(c)) else S(d))"
+        );
+        // Without competition the avoided variant is kept.
+        assert_eq!(
+            disambiguated(&parser, "if a then b"),
+            "S(if E(a) then S(b))"
+        );
+    }
+
+    #[test]
+    fn reject() {
+        let sums = parser(SUMS, "E -> E + T | T + E %reject | T\nT -> num");
+        let content = "1 + 2 + 3";
+        assert_eq!(sums.parse(content).unwrap().count(), Count::Finite(4));
+        assert_eq!(disambiguated(&sums, content), "E(E(E(T(1)) + T(2)) + T(3))");
+
+        let rejecting = parser(SUMS, "E -> T + E %reject | T\nT -> num");
+        let mut forest = rejecting.parse("1 + 2").unwrap();
+        forest.disambiguate();
+        assert_eq!(forest.count(), Count::Finite(0));
+        assert!(matches!(forest.ast(), Err(ParseError::Rejected { .. })));
+    }
+
+    #[test]
+    fn ambiguous_after_filters() {
+        let parser = parser(SUMS, "E -> E + E | E * E %avoid | num");
+        assert_eq!(
+            disambiguated(&parser, "1 + 2 * 3"),
+            "E(E(1) + E(E(2) * E(3)))"
+        );
+
+        let mut forest = parser.parse("1 + 2 + 3").unwrap();
+        forest.disambiguate();
+        assert_eq!(forest.count(), Count::Finite(2));
+        let Err(error) = forest.ast() else {
+            panic!("1 + 2 + 3 is not ambiguous");
+        };
+        assert_eq!(
+            error.to_string(),
+            "Ambiguous input: E at 1:1 has 2 alternatives at its first ambiguous node."
+        );
+        let ParseError::Ambiguous { derivations, .. } = error else {
+            unreachable!();
+        };
+        let mut shapes: Vec<String> = derivations.iter().map(|d| shape(&d.borrow())).collect();
+        shapes.sort();
+        assert_eq!(
+            shapes,
+            ["E(E(1) + E(E(2) + E(3)))", "E(E(E(1) + E(2)) + E(3))"]
+        );
+    }
+
+    #[test]
+    fn annotations_on_other_parsers() {
+        let grammar = "S -> if E then S %prefer | if E then S else S | id\nE -> id";
+        let message = "%prefer in S is only supported by the GLR parser.";
+
+        let errors = [
+            Parser::from_str(grammar, IFS.parse().unwrap()).err(),
+            LrParser::from_str(grammar, IFS.parse().unwrap()).err(),
+            EarleyParser::from_str(grammar, IFS.parse().unwrap()).err(),
+            PegParser::from_str(grammar, IFS.parse().unwrap()).err(),
+        ];
+        for error in errors {
+            assert_eq!(error.map(|e| e.to_string()).as_deref(), Some(message));
+        }
+    }
+}
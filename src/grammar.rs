@@ -0,0 +1,659 @@
+use indexmap::{IndexMap, IndexSet};
+
+use crate::error::GrammarError;
+use crate::glr::Annotation;
+use crate::parser::{GrammarName, GrammarVariant, GrammarVariants, NodeType};
+use crate::peg::Predicate;
+use crate::precedence::{Associativity, Operators};
+use crate::tokenizer::{Tokenizer, EPSILON};
+
+const LEFT: &str = "%left";
+const RIGHT: &str = "%right";
+const PREFIX: &str = "%prefix";
+const EXPRESSION: &str = "%expr";
+const PREFER: &str = "%prefer";
+const AVOID: &str = "%avoid";
+const REJECT: &str = "%reject";
+
+/// Grammars read from a grammar file.
+pub(crate) struct Grammars {
+    pub(crate) declared: IndexMap<GrammarName, GrammarVariants>,
+    /// Grammars introduced for EBNF operators; their children are spliced into the parent node.
+    pub(crate) helpers: IndexSet<GrammarName>,
+    pub(crate) operators: Operators,
+    /// Annotations of declared variants by grammar name and variant index.
+    pub(crate) annotations: IndexMap<(GrammarName, usize), Annotation>,
+    /// Helpers introduced for `&X` and `!X`, which only [read_peg] accepts.
+    pub(crate) predicates: IndexMap<GrammarName, Predicate>,
+}
+
+impl Grammars {
+    /// Fails on the first annotation, for parsers that have no ambiguities to filter.
+    pub(crate) fn reject_annotations(&self) -> Result<(), GrammarError> {
+        let Some(((grammar, _), annotation)) = self.annotations.first() else {
+            return Ok(());
+        };
+        let annotation = match annotation {
+            Annotation::Prefer => PREFER,
+            Annotation::Avoid => AVOID,
+            Annotation::Reject => REJECT,
+        };
+        Err(GrammarError::UnsupportedAnnotation {
+            grammar: grammar.clone(),
+            annotation: String::from(annotation),
+        })
+    }
+}
+
+/// Reads grammars in the `Name -> Variant | Variant` format, one per line.
+///
+/// Variants may use EBNF operators, which are desugared into helper grammars:
+/// `X*` becomes `H -> X H | epsilon`, `X+` becomes `X H` with the same `H`,
+/// `X?` becomes `H -> X | epsilon` and `( a | b c )` becomes `H -> a | b c`.
+/// Words matching a token name are always tokens, so `(` or `a*` declared as tokens are not operators.
+///
+/// `%left`, `%right` and `%prefix` lines declare operator tokens, binding tighter on later lines,
+/// and `E -> %expr Operand` declares an expression of operands joined by these operators.
+/// The expression is an LL(1) list; operators are grouped by precedence once it is parsed.
+///
+/// A variant may end with `%prefer`, `%avoid` or `%reject`, which only the
+/// [GlrParser](crate::GlrParser) uses to filter ambiguous parses; the other parsers
+/// reject them with [Grammars::reject_annotations].
+///
+/// Quoted words like `"model"` or `'['` are tokens matching that text,
+/// added by [Tokenizer::register_literals].
+pub(crate) fn read(content: &str, tokenizer: &mut Tokenizer) -> Result<Grammars, GrammarError> {
+    tokenizer.register_literals(content)?;
+    read_grammars(content, tokenizer, false)
+}
+
+/// Reads grammars like [read], also accepting the `&X` and `!X` predicates of parsing
+/// expression grammars. Each predicate becomes a helper `H -> X`.
+pub(crate) fn read_peg(content: &str, tokenizer: &mut Tokenizer) -> Result<Grammars, GrammarError> {
+    tokenizer.register_literals(content)?;
+    read_grammars(content, tokenizer, true)
+}
+
+/// Returns the quoted words of the rules and operator lines, without their quotes.
+pub(crate) fn literals(content: &str) -> IndexSet<&str> {
+    let mut literals = IndexSet::new();
+    for line in content.lines() {
+        let directive = line.split_whitespace().next();
+        let words = match declaration(line) {
+            Some((_, pattern)) => pattern,
+            None if matches!(directive, Some(LEFT | RIGHT | PREFIX)) => line,
+            None => continue,
+        };
+
+        for word in words.split_whitespace() {
+            literals.extend(quoted(word));
+        }
+    }
+
+    literals
+}
+
+/// Splits a rule line at its first `->` into the trimmed name and the body.
+fn declaration(line: &str) -> Option<(&str, &str)> {
+    if matches!(line.split_whitespace().next(), Some(LEFT | RIGHT | PREFIX)) {
+        return None;
+    }
+    let (name, pattern) = line.split_once("->")?;
+    Some((name.trim(), pattern)).filter(|(name, _)| !name.is_empty())
+}
+
+/// Returns the text of a word in double or single quotes.
+fn literal(word: &str) -> Option<&str> {
+    ['"', '\'']
+        .into_iter()
+        .find_map(|quote| word.strip_prefix(quote)?.strip_suffix(quote))
+        .filter(|text| !text.is_empty())
+}
+
+/// Returns the literal of a word, ignoring a leading predicate and a trailing operator.
+fn quoted(word: &str) -> Option<&str> {
+    let word = word.strip_prefix(['&', '!']).unwrap_or(word);
+    literal(word).or_else(|| word.strip_suffix(['*', '+', '?']).and_then(literal))
+}
+
+fn read_grammars(
+    content: &str,
+    tokenizer: &Tokenizer,
+    predicates: bool,
+) -> Result<Grammars, GrammarError> {
+    let mut grammars = Grammars {
+        declared: IndexMap::new(),
+        helpers: IndexSet::new(),
+        operators: read_operators(content, tokenizer)?,
+        annotations: IndexMap::new(),
+        predicates: IndexMap::new(),
+    };
+
+    for (index, line) in content.lines().enumerate() {
+        if let Some((name, pattern)) = declaration(line) {
+            let words: Vec<&str> = pattern.split_whitespace().collect();
+            if words.first() == Some(&EXPRESSION) {
+                let [_, operand] = words[..] else {
+                    return Err(GrammarError::InvalidDirective { line: index + 1 });
+                };
+                let (variants, helpers) = expression(name, operand, &grammars.operators, tokenizer);
+                grammars.declared.insert(String::from(name), variants);
+                for (helper, variants) in helpers.into_iter() {
+                    grammars.helpers.insert(helper.clone());
+                    grammars.declared.insert(helper, variants);
+                }
+                grammars
+                    .operators
+                    .expressions
+                    .insert(String::from(name), String::from(operand));
+                continue;
+            }
+
+            let mut rule = Rule {
+                name,
+                line: index + 1,
+                tokenizer,
+                pieces: split(pattern, tokenizer),
+                position: 0,
+                depth: 0,
+                helpers: vec![],
+                annotations: vec![],
+                predicates: predicates.then(Vec::new),
+            };
+
+            let variants = rule.alternatives()?;
+            match rule.pieces.get(rule.position) {
+                None => {}
+                Some(Piece::Close | Piece::CloseRepeat(_)) => {
+                    return Err(GrammarError::InvalidSyntax { line: rule.line });
+                }
+                // Only an annotation stops the variants before the end or a `)`.
+                Some(_) => return Err(GrammarError::MisplacedAnnotation { line: rule.line }),
+            }
+
+            grammars.declared.insert(String::from(name), variants);
+            for (variant, annotation) in rule.annotations.into_iter() {
+                grammars
+                    .annotations
+                    .insert((String::from(name), variant), annotation);
+            }
+            grammars
+                .predicates
+                .extend(rule.predicates.into_iter().flatten());
+            for (helper, variants) in rule.helpers.into_iter() {
+                grammars.helpers.insert(helper.clone());
+                grammars.declared.insert(helper, variants);
+            }
+        }
+    }
+
+    Ok(grammars)
+}
+
+/// Reads the `%left`, `%right` and `%prefix` lines.
+fn read_operators(content: &str, tokenizer: &Tokenizer) -> Result<Operators, GrammarError> {
+    let mut operators = Operators::default();
+
+    for (index, line) in content.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        let associativity = match words.next() {
+            Some(LEFT) => Some(Associativity::Left),
+            Some(RIGHT) => Some(Associativity::Right),
+            Some(PREFIX) => None,
+            _ => continue,
+        };
+
+        let precedence = index + 1;
+        let mut declared = false;
+        for word in words {
+            let word = literal(word).unwrap_or(word);
+            if !tokenizer.patterns.iter().any(|token| token.name == word) {
+                return Err(GrammarError::UndefinedToken {
+                    name: String::from(word),
+                    rule: String::from(line.trim()),
+                });
+            }
+
+            let name = String::from(word);
+            match associativity {
+                Some(associativity) => {
+                    operators.binary.insert(name, (precedence, associativity));
+                }
+                None => {
+                    operators.prefix.insert(name, precedence);
+                }
+            }
+            declared = true;
+        }
+
+        if !declared {
+            return Err(GrammarError::InvalidDirective { line: index + 1 });
+        }
+    }
+
+    Ok(operators)
+}
+
+/// Desugars `E -> %expr A` into `E -> E~1 E~2`, `E~1 -> prefix E~1 | A` and
+/// `E~2 -> binary E~1 E~2 | epsilon`, which parse the operands and operators as a flat list.
+fn expression(
+    name: &str,
+    operand: &str,
+    operators: &Operators,
+    tokenizer: &Tokenizer,
+) -> (GrammarVariants, Vec<(GrammarName, GrammarVariants)>) {
+    let prefixed = NodeType::Grammar {
+        name: format!("{name}~1"),
+    };
+    let rest = NodeType::Grammar {
+        name: format!("{name}~2"),
+    };
+
+    let mut operands: GrammarVariants = operators
+        .prefix
+        .keys()
+        .map(|operator| vec![node(tokenizer, operator), prefixed.clone()])
+        .collect();
+    operands.push(vec![node(tokenizer, operand)]);
+
+    let mut binary: GrammarVariants = operators
+        .binary
+        .keys()
+        .map(|operator| vec![node(tokenizer, operator), prefixed.clone(), rest.clone()])
+        .collect();
+    binary.push(vec![node(tokenizer, EPSILON)]);
+
+    (
+        vec![vec![prefixed.clone(), rest.clone()]],
+        vec![(prefixed.to_string(), operands), (rest.to_string(), binary)],
+    )
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Piece<'a> {
+    Symbol(&'a str),
+    Alternative,
+    Open,
+    Close,
+    Repeat(&'a str, Operator),
+    CloseRepeat(Operator),
+    /// A `)` followed by something other than an operator.
+    InvalidClose(&'a str),
+    Annotation(Annotation),
+    Predicate(Predicate, &'a str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    ZeroOrMore,
+    OneOrMore,
+    Optional,
+}
+
+impl Operator {
+    fn parse(c: char) -> Option<Operator> {
+        match c {
+            '*' => Some(Operator::ZeroOrMore),
+            '+' => Some(Operator::OneOrMore),
+            '?' => Some(Operator::Optional),
+            _ => None,
+        }
+    }
+}
+
+/// Splits a rule body into symbols, `|` separators, group parentheses and operators.
+fn split<'a>(pattern: &'a str, tokenizer: &Tokenizer) -> Vec<Piece<'a>> {
+    let is_token = |word: &str| tokenizer.patterns.iter().any(|token| token.name == word);
+    let mut pieces = vec![];
+
+    for word in pattern.split_whitespace() {
+        if is_token(word) {
+            pieces.push(Piece::Symbol(word));
+            continue;
+        }
+        if quoted(word).is_some() {
+            pieces.push(piece(word));
+            continue;
+        }
+
+        for (position, part) in word.split('|').enumerate() {
+            if position > 0 {
+                pieces.push(Piece::Alternative);
+            }
+            if !part.is_empty() {
+                pieces.push(piece(part));
+            }
+        }
+    }
+
+    pieces
+}
+
+/// Turns one word of a rule body, without `|` separators, into a piece.
+fn piece(word: &str) -> Piece<'_> {
+    if let Some(text) = literal(word) {
+        return Piece::Symbol(text);
+    }
+
+    let annotation = match word {
+        PREFER => Some(Annotation::Prefer),
+        AVOID => Some(Annotation::Avoid),
+        REJECT => Some(Annotation::Reject),
+        _ => None,
+    };
+    if let Some(annotation) = annotation {
+        return Piece::Annotation(annotation);
+    }
+
+    let mut chars = word.chars();
+    let predicate = match chars.next() {
+        Some('&') => Some(Predicate::And),
+        Some('!') => Some(Predicate::Not),
+        _ => None,
+    };
+    if let Some(predicate) = predicate.filter(|_| !chars.as_str().is_empty()) {
+        let name = chars.as_str();
+        return Piece::Predicate(predicate, literal(name).unwrap_or(name));
+    }
+
+    let mut chars = word.chars();
+    let operator = chars.next_back().and_then(Operator::parse);
+    match (word, operator) {
+        ("(", _) => Piece::Open,
+        (")", _) => Piece::Close,
+        (_, Some(operator)) if chars.as_str() == ")" => Piece::CloseRepeat(operator),
+        _ if word.starts_with(')') => Piece::InvalidClose(word),
+        (_, Some(operator)) if !chars.as_str().is_empty() => {
+            let name = chars.as_str();
+            Piece::Repeat(literal(name).unwrap_or(name), operator)
+        }
+        _ => Piece::Symbol(word),
+    }
+}
+
+/// Recursive descent over the pieces of one rule body.
+struct Rule<'a> {
+    name: &'a str,
+    line: usize,
+    tokenizer: &'a Tokenizer,
+    pieces: Vec<Piece<'a>>,
+    position: usize,
+    /// Number of groups around the current position.
+    depth: usize,
+    helpers: Vec<(GrammarName, GrammarVariants)>,
+    annotations: Vec<(usize, Annotation)>,
+    /// Predicate helpers, or `None` when predicates aren't allowed.
+    predicates: Option<Vec<(GrammarName, Predicate)>>,
+}
+
+impl Rule<'_> {
+    fn alternatives(&mut self) -> Result<GrammarVariants, GrammarError> {
+        let mut variants = vec![self.sequence()?];
+        self.annotation(0)?;
+        while self.pieces.get(self.position) == Some(&Piece::Alternative) {
+            self.position += 1;
+            variants.push(self.sequence()?);
+            self.annotation(variants.len() - 1)?;
+        }
+        Ok(variants)
+    }
+
+    /// Reads the annotation ending a variant, which groups can't have.
+    fn annotation(&mut self, variant: usize) -> Result<(), GrammarError> {
+        let Some(Piece::Annotation(annotation)) = self.pieces.get(self.position) else {
+            return Ok(());
+        };
+        if self.depth > 0 {
+            return Err(GrammarError::MisplacedAnnotation { line: self.line });
+        }
+
+        self.annotations.push((variant, *annotation));
+        self.position += 1;
+        Ok(())
+    }
+
+    fn sequence(&mut self) -> Result<GrammarVariant, GrammarError> {
+        let mut nodes = vec![];
+
+        while let Some(piece) = self.pieces.get(self.position) {
+            match piece {
+                Piece::Alternative
+                | Piece::Close
+                | Piece::CloseRepeat(_)
+                | Piece::Annotation(_) => break,
+                Piece::InvalidClose(word) => {
+                    return Err(GrammarError::InvalidOperator {
+                        line: self.line,
+                        word: word.to_string(),
+                    });
+                }
+                Piece::Symbol(name) => {
+                    nodes.push(self.node(name));
+                    self.position += 1;
+                }
+                Piece::Predicate(predicate, name) => {
+                    let (predicate, name) = (*predicate, *name);
+                    if self.predicates.is_none() {
+                        return Err(GrammarError::InvalidDirective { line: self.line });
+                    }
+                    self.position += 1;
+
+                    let helper = self.helper(vec![vec![self.node(name)]]);
+                    if let Some(predicates) = self.predicates.as_mut() {
+                        predicates.push((helper.to_string(), predicate));
+                    }
+                    nodes.push(helper);
+                }
+                Piece::Repeat(name, operator) => {
+                    let (name, operator) = (*name, *operator);
+                    self.position += 1;
+                    let item = vec![self.node(name)];
+                    nodes.extend(self.repeat(item, operator));
+                }
+                Piece::Open => {
+                    self.position += 1;
+                    self.depth += 1;
+                    let variants = self.alternatives()?;
+                    self.depth -= 1;
+                    let operator = match self.pieces.get(self.position) {
+                        Some(Piece::Close) => None,
+                        Some(Piece::CloseRepeat(operator)) => Some(*operator),
+                        _ => return Err(GrammarError::InvalidSyntax { line: self.line }),
+                    };
+                    self.position += 1;
+
+                    match operator {
+                        // A group with a single variant is repeated without a helper of its own.
+                        Some(operator) if variants.len() == 1 => {
+                            let item = variants.into_iter().next().unwrap();
+                            nodes.extend(self.repeat(item, operator));
+                        }
+                        Some(operator) => {
+                            let group = self.helper(variants);
+                            nodes.extend(self.repeat(vec![group], operator));
+                        }
+                        None => nodes.push(self.helper(variants)),
+                    }
+                }
+            }
+        }
+
+        if nodes.is_empty() {
+            // `( a | )` and a trailing `|` derive epsilon, like an explicit `epsilon`.
+            nodes.push(self.node(EPSILON));
+        }
+        Ok(nodes)
+    }
+
+    fn repeat(&mut self, item: GrammarVariant, operator: Operator) -> Vec<NodeType> {
+        let epsilon = vec![self.node(EPSILON)];
+        match operator {
+            Operator::Optional => vec![self.helper(vec![item, epsilon])],
+            Operator::ZeroOrMore => vec![self.list(item)],
+            Operator::OneOrMore => {
+                let mut nodes = item.clone();
+                nodes.push(self.list(item));
+                nodes
+            }
+        }
+    }
+
+    /// Declares `H -> item H | epsilon`.
+    fn list(&mut self, item: GrammarVariant) -> NodeType {
+        let name = self.helper_name();
+        let mut variant = item;
+        variant.push(NodeType::Grammar { name: name.clone() });
+
+        self.helpers
+            .push((name.clone(), vec![variant, vec![self.node(EPSILON)]]));
+        NodeType::Grammar { name }
+    }
+
+    fn helper(&mut self, variants: GrammarVariants) -> NodeType {
+        let name = self.helper_name();
+        self.helpers.push((name.clone(), variants));
+        NodeType::Grammar { name }
+    }
+
+    fn helper_name(&self) -> GrammarName {
+        format!("{}~{}", self.name, self.helpers.len() + 1)
+    }
+
+    fn node(&self, name: &str) -> NodeType {
+        node(self.tokenizer, name)
+    }
+}
+
+fn node(tokenizer: &Tokenizer, name: &str) -> NodeType {
+    match tokenizer.patterns.iter().find(|token| token.name == name) {
+        Some(pattern) => NodeType::Token {
+            name: pattern.name.clone(),
+            pattern: pattern.clone(),
+        },
+        None => NodeType::Grammar {
+            name: String::from(name),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, AST};
+
+    const TOKENS: &str = "a = a\nb = b\nc = c\nd = d\ne = e\n%skip whitespace = \\s+";
+    const EBNF: &str = "S -> a? b* c+ ( a | b ) ( d ( b | c )* )+ T\nT -> ( a b )? e";
+
+    fn rules(grammars: &Grammars) -> Vec<String> {
+        let mut rules = vec![];
+        for (name, variants) in grammars.declared.iter() {
+            for variant in variants.iter() {
+                let nodes: Vec<String> = variant.iter().map(|node| node.to_string()).collect();
+                rules.push(format!("{name} -> {}", nodes.join(" ")));
+            }
+        }
+        rules
+    }
+
+    fn shape(ast: &AST) -> String {
+        match ast {
+            AST::Token { value, .. } => value.clone(),
+            AST::Grammar { name, children, .. } => {
+                let children: Vec<String> = children.iter().map(|c| shape(&c.borrow())).collect();
+                format!("{name}({})", children.join(" "))
+            }
+            AST::Error { name, .. } => format!("!{name}"),
+        }
+    }
+
+    #[test]
+    fn ebnf_helpers() {
+        let mut tokenizer: Tokenizer = TOKENS.parse().unwrap();
+        let Ok(grammars) = read(EBNF, &mut tokenizer) else {
+            panic!("{EBNF} was not read");
+        };
+
+        assert_eq!(
+            rules(&grammars),
+            [
+                "S -> S~1 S~2 c S~3 S~4 d S~6 S~7 T",
+                "S~1 -> a",
+                "S~1 -> epsilon",
+                "S~2 -> b S~2",
+                "S~2 -> epsilon",
+                "S~3 -> c S~3",
+                "S~3 -> epsilon",
+                "S~4 -> a",
+                "S~4 -> b",
+                // The nested group is a helper of its own, numbered before the list using it.
+                "S~5 -> b",
+                "S~5 -> c",
+                "S~6 -> S~5 S~6",
+                "S~6 -> epsilon",
+                "S~7 -> d S~6 S~7",
+                "S~7 -> epsilon",
+                "T -> T~1 e",
+                "T~1 -> a b",
+                "T~1 -> epsilon",
+            ]
+        );
+        let helpers: Vec<&str> = grammars.helpers.iter().map(|name| name.as_str()).collect();
+        assert_eq!(
+            helpers,
+            ["S~1", "S~2", "S~3", "S~4", "S~5", "S~6", "S~7", "T~1"]
+        );
+    }
+
+    #[test]
+    fn ebnf_ast() {
+        let parser = Parser::from_str(EBNF, TOKENS.parse().unwrap()).unwrap();
+
+        // Helpers are spliced into the grammar using them.
+        let ast = parser.parse("c a d e").unwrap();
+        assert_eq!(shape(&ast.borrow()), "S(c a d T(e))");
+        let ast = parser.parse("a b b c c b d b c d d a b e").unwrap();
+        assert_eq!(shape(&ast.borrow()), "S(a b b c c b d b c d d T(a b e))");
+
+        assert!(parser.parse("c a e").is_err());
+    }
+
+    fn error(grammar: &str) -> GrammarError {
+        let mut tokenizer: Tokenizer = "a = a\nb = b".parse().unwrap();
+        match read(grammar, &mut tokenizer) {
+            Ok(..) => panic!("{grammar} was read without errors"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(matches!(
+            error("S -> ( a | b"),
+            GrammarError::InvalidSyntax { line: 1 }
+        ));
+        assert!(matches!(
+            error("S -> a\nT -> a ) b"),
+            GrammarError::InvalidSyntax { line: 2 }
+        ));
+        assert!(matches!(
+            error("S -> a )*"),
+            GrammarError::InvalidSyntax { line: 1 }
+        ));
+
+        let err = error("S -> ( a | b )x");
+        assert!(matches!(&err, GrammarError::InvalidOperator { word, .. } if word == ")x"));
+        assert_eq!(
+            err.to_string(),
+            "Unable to close a group with )x in the grammar on line 1, expected ), )*, )+ or )?."
+        );
+
+        assert!(matches!(
+            error("S -> a %prefer b"),
+            GrammarError::MisplacedAnnotation { line: 1 }
+        ));
+        assert!(matches!(
+            error("S -> ( a %avoid ) b"),
+            GrammarError::MisplacedAnnotation { line: 1 }
+        ));
+    }
+}
@@ -0,0 +1,33 @@
+mod builder;
+mod codegen;
+mod compiled;
+mod earley;
+mod error;
+mod factoring;
+mod glr;
+mod grammar;
+mod lr;
+mod parser;
+mod peg;
+mod precedence;
+mod recursion;
+mod report;
+mod tokenizer;
+mod typed;
+
+pub use builder::{epsilon, nt, t, GrammarBuilder, Symbol};
+pub use compiled::{CompiledGrammar, CompiledNode};
+pub use earley::EarleyParser;
+pub use error::{AstError, GrammarError, LexError, ParseError};
+pub use glr::{Count, Forest, ForestNode, GlrParser, Packed};
+pub use lr::{Action, LrAlgorithm, LrConflict, LrConflictKind, LrOptions, LrParser, Production};
+pub use parser::{
+    Conflict, ConflictKind, ConflictPolicy, FirstSet, FollowSet, GrammarName, GrammarVariant,
+    GrammarVariants, Lookahead, NodeType, NullableSet, Parser, ParserOptions, ParsingTable,
+    Recovered, TokenName, AST, MAX_LOOKAHEAD,
+};
+pub use peg::{PegOptions, PegParser};
+pub use report::{report, Backend, ReportOptions};
+pub use tokenizer::{
+    Indentation, Pattern, PatternKind, Span, Token, Tokenizer, DEDENT, INDENT, NEWLINE,
+};
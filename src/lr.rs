@@ -0,0 +1,755 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::fs::read_to_string;
+use std::rc::Rc;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::error::{GrammarError, ParseError};
+use crate::grammar;
+use crate::parser::{
+    build_first, build_follow, build_nullable, cover, is_epsilon, sequence_first, splice_helpers,
+    unexpected_token, validate, ConflictPolicy, FirstSet, FollowSet, GrammarName, GrammarVariant,
+    GrammarVariants, Helper, NodeType, NullableSet, Parser, TokenName, AST, EOF, ROOT,
+};
+use crate::precedence::{self, Operators};
+use crate::tokenizer::{end_span, Span, Token, Tokenizer, EPSILON};
+
+/// Which item sets the ACTION and GOTO tables are built from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LrAlgorithm {
+    /// LR(0) item sets, reducing on every token in the FOLLOW set of the grammar.
+    Slr,
+    /// LR(1) item sets merged when they only differ in lookahead tokens.
+    #[default]
+    Lalr,
+    /// LR(1) item sets.
+    Canonical,
+}
+
+impl Display for LrAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LrAlgorithm::Slr => write!(f, "SLR(1)"),
+            LrAlgorithm::Lalr => write!(f, "LALR(1)"),
+            LrAlgorithm::Canonical => write!(f, "LR(1)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LrOptions {
+    pub algorithm: LrAlgorithm,
+    /// Conflicts that aren't rejected prefer shifting; reduce/reduce conflicts keep
+    /// the variant declared first, or last with [ConflictPolicy::LastWins].
+    pub conflicts: ConflictPolicy,
+}
+
+/// A variant of a grammar, numbered in declaration order. Epsilon is left out of the body.
+#[derive(Debug, Clone)]
+pub struct Production {
+    pub grammar: GrammarName,
+    pub body: GrammarVariant,
+}
+
+impl Display for Production {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.body.is_empty() {
+            return write!(f, "{} -> {EPSILON}", self.grammar);
+        }
+        let body: Vec<String> = self.body.iter().map(|node| node.to_string()).collect();
+        write!(f, "{} -> {}", self.grammar, body.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Shift(usize),
+    /// Reduce by the production with this index.
+    Reduce(usize),
+    Accept,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LrConflictKind {
+    ShiftReduce,
+    ReduceReduce,
+    /// A production can be reduced at the end of the input, where the start grammar is accepted.
+    AcceptReduce,
+}
+
+/// Several actions claim the same ACTION table cell.
+#[derive(Debug, Clone)]
+pub struct LrConflict {
+    pub kind: LrConflictKind,
+    pub state: usize,
+    pub token: TokenName,
+    /// The productions that could be reduced.
+    pub productions: Vec<Production>,
+}
+
+impl Display for LrConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut actions: Vec<String> = self
+            .productions
+            .iter()
+            .map(|production| format!("reduce {production}"))
+            .collect();
+        let kind = match self.kind {
+            LrConflictKind::ShiftReduce => {
+                actions.insert(0, String::from("shift"));
+                "Shift/reduce"
+            }
+            LrConflictKind::ReduceReduce => "Reduce/reduce",
+            LrConflictKind::AcceptReduce => {
+                actions.insert(0, String::from("accept"));
+                "Accept/reduce"
+            }
+        };
+
+        write!(
+            f,
+            "{kind} conflict in state {} on {}: {}",
+            self.state,
+            self.token,
+            actions.join(" | ")
+        )
+    }
+}
+
+/// A bottom-up parser driven by ACTION and GOTO tables, reading the same grammar files
+/// as [Parser] and producing the same [AST]. Left recursion needs no rewriting here.
+pub struct LrParser {
+    grammars: IndexMap<GrammarName, GrammarVariants>,
+    productions: Vec<Production>,
+    states: usize,
+    action: IndexMap<(usize, TokenName), Action>,
+    goto: IndexMap<(usize, GrammarName), usize>,
+    tokenizer: Tokenizer,
+    conflicts: Vec<LrConflict>,
+    options: LrOptions,
+    helpers: IndexMap<GrammarName, Helper>,
+    operators: Operators,
+}
+
+impl LrParser {
+    pub fn from_file(path: &str, tokenizer: Tokenizer) -> Result<LrParser, GrammarError> {
+        LrParser::from_file_with_options(path, tokenizer, LrOptions::default())
+    }
+
+    pub fn from_file_with_options(
+        path: &str,
+        tokenizer: Tokenizer,
+        options: LrOptions,
+    ) -> Result<LrParser, GrammarError> {
+        let content = match read_to_string(path) {
+            Err(e) => return Err(GrammarError::Io(e)),
+            Ok(f) => f,
+        };
+
+        LrParser::from_str_with_options(&content, tokenizer, options)
+    }
+
+    pub fn from_str(content: &str, tokenizer: Tokenizer) -> Result<LrParser, GrammarError> {
+        LrParser::from_str_with_options(content, tokenizer, LrOptions::default())
+    }
+
+    /// Reads grammars in the same format as [Parser::from_str_with_options].
+    pub fn from_str_with_options(
+        content: &str,
+        mut tokenizer: Tokenizer,
+        options: LrOptions,
+    ) -> Result<LrParser, GrammarError> {
+        let declared = grammar::read(content, &mut tokenizer)?;
+        declared.reject_annotations()?;
+        let Some(start) = declared.declared.keys().next() else {
+            return Err(GrammarError::Empty);
+        };
+        validate(&declared.declared)?;
+
+        let mut grammars = IndexMap::from([(
+            String::from(ROOT),
+            vec![vec![
+                NodeType::Grammar {
+                    name: start.clone(),
+                },
+                Parser::eof(),
+            ]],
+        )]);
+        grammars.extend(declared.declared);
+
+        let productions = productions(&grammars);
+
+        let nullable = build_nullable(&grammars);
+        let first = build_first(&grammars);
+        let follow = build_follow(&grammars, &first);
+
+        let automaton = Automaton::build(&productions, &nullable, &first, options.algorithm);
+        let (action, goto, conflicts) =
+            automaton.tables(&productions, &follow, options.algorithm, options.conflicts);
+        if options.conflicts == ConflictPolicy::Reject && !conflicts.is_empty() {
+            return Err(GrammarError::LrConflicts {
+                algorithm: options.algorithm,
+                conflicts,
+            });
+        }
+
+        Ok(LrParser {
+            grammars,
+            productions,
+            states: automaton.states(),
+            action,
+            goto,
+            tokenizer,
+            conflicts,
+            options,
+            helpers: declared
+                .helpers
+                .into_iter()
+                .map(|name| (name, Helper::Inline))
+                .collect(),
+            operators: declared.operators,
+        })
+    }
+
+    /// Grammars in declaration order, starting with the internal root grammar.
+    pub fn grammars(&self) -> &IndexMap<GrammarName, GrammarVariants> {
+        &self.grammars
+    }
+
+    /// Every variant of every grammar; [Action::Reduce] refers to them by index.
+    pub fn productions(&self) -> &[Production] {
+        &self.productions
+    }
+
+    /// Number of states of the automaton.
+    pub fn states(&self) -> usize {
+        self.states
+    }
+
+    pub fn action(&self) -> &IndexMap<(usize, TokenName), Action> {
+        &self.action
+    }
+
+    pub fn goto(&self) -> &IndexMap<(usize, GrammarName), usize> {
+        &self.goto
+    }
+
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    pub fn algorithm(&self) -> LrAlgorithm {
+        self.options.algorithm
+    }
+
+    /// Conflicts found while building the ACTION table and resolved by the [ConflictPolicy].
+    pub fn conflicts(&self) -> &[LrConflict] {
+        &self.conflicts
+    }
+
+    pub fn parse(&self, content: &str) -> Result<Rc<RefCell<AST>>, ParseError> {
+        let mut tokens = self.tokenizer.parse(content)?;
+        tokens.push(Token {
+            name: EOF.to_string(),
+            value: EOF.to_string(),
+            span: end_span(content),
+        });
+
+        let mut states = vec![0];
+        let mut nodes: Vec<Rc<RefCell<AST>>> = vec![];
+        let mut position = 0;
+
+        loop {
+            let state = *states.last().unwrap();
+            let token = &tokens[position];
+
+            match self.action.get(&(state, token.name.clone())) {
+                Some(Action::Shift(next)) => {
+                    nodes.push(Rc::new(RefCell::new(token.into())));
+                    states.push(*next);
+                    position += 1;
+                }
+                Some(Action::Reduce(index)) => {
+                    let production = &self.productions[*index];
+                    let children = nodes.split_off(nodes.len() - production.body.len());
+                    states.truncate(states.len() - production.body.len());
+
+                    let spans: Vec<Span> =
+                        children.iter().map(|child| child.borrow().span()).collect();
+                    nodes.push(Rc::new(RefCell::new(AST::Grammar {
+                        name: production.grammar.clone(),
+                        span: cover(&spans).unwrap_or(token.span.empty()),
+                        children,
+                    })));
+
+                    let state = *states.last().unwrap();
+                    match self.goto.get(&(state, production.grammar.clone())) {
+                        Some(next) => states.push(*next),
+                        None => return Err(unexpected_token(token, vec![], None)),
+                    }
+                }
+                Some(Action::Accept) => break,
+                None => {
+                    let expected = self
+                        .action
+                        .keys()
+                        .filter(|(candidate, _)| *candidate == state)
+                        .map(|(_, token)| token.clone())
+                        .collect();
+                    return Err(unexpected_token(token, expected, None));
+                }
+            }
+        }
+
+        let Some(root) = nodes.pop() else {
+            return Err(ParseError::NoGrammars);
+        };
+        if !self.helpers.is_empty() {
+            splice_helpers(&root, &self.helpers);
+        }
+        if !self.operators.expressions.is_empty() {
+            precedence::fold(&root, &self.operators);
+        }
+        Ok(root)
+    }
+}
+
+/// Numbers the variants of every grammar in declaration order.
+pub(crate) fn productions(grammars: &IndexMap<GrammarName, GrammarVariants>) -> Vec<Production> {
+    grammars
+        .iter()
+        .flat_map(|(grammar, variants)| {
+            variants.iter().map(|variant| Production {
+                grammar: grammar.clone(),
+                body: variant
+                    .iter()
+                    .filter(|node| !is_epsilon(node))
+                    .cloned()
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// An LR(0) item when the lookahead is empty, an LR(1) item otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Item {
+    production: usize,
+    dot: usize,
+    lookahead: TokenName,
+}
+
+type ItemSet = BTreeSet<Item>;
+
+pub(crate) struct Automaton {
+    states: Vec<ItemSet>,
+    /// Transitions of each state on grammar and token names.
+    transitions: Vec<IndexMap<String, usize>>,
+}
+
+impl Automaton {
+    pub(crate) fn build(
+        productions: &[Production],
+        nullable: &NullableSet,
+        first: &FirstSet,
+        algorithm: LrAlgorithm,
+    ) -> Automaton {
+        let closure = Closure {
+            productions,
+            nullable,
+            first,
+            lookahead: algorithm != LrAlgorithm::Slr,
+        };
+
+        let lookahead = match closure.lookahead {
+            true => String::from(EOF),
+            false => String::new(),
+        };
+        let start = closure.close(BTreeSet::from([Item {
+            production: 0,
+            dot: 0,
+            lookahead,
+        }]));
+
+        let mut states: IndexSet<ItemSet> = IndexSet::from([start]);
+        let mut transitions = vec![];
+        let mut index = 0;
+        while index < states.len() {
+            let mut kernels: IndexMap<String, ItemSet> = IndexMap::new();
+            for item in states[index].iter() {
+                let Some(node) = productions[item.production].body.get(item.dot) else {
+                    continue;
+                };
+                // The end of the input is accepted, there is no state after it.
+                if matches!(node, NodeType::Token { name, .. } if name == EOF) {
+                    continue;
+                }
+                kernels.entry(node.to_string()).or_default().insert(Item {
+                    dot: item.dot + 1,
+                    ..item.clone()
+                });
+            }
+
+            let mut edges = IndexMap::new();
+            for (symbol, kernel) in kernels.into_iter() {
+                let (target, _) = states.insert_full(closure.close(kernel));
+                edges.insert(symbol, target);
+            }
+            transitions.push(edges);
+            index += 1;
+        }
+
+        let automaton = Automaton {
+            states: states.into_iter().collect(),
+            transitions,
+        };
+        match algorithm {
+            LrAlgorithm::Lalr => automaton.merge_cores(),
+            _ => automaton,
+        }
+    }
+
+    /// Merges states with the same LR(0) items, uniting their lookahead tokens.
+    fn merge_cores(self) -> Automaton {
+        let core = |state: &ItemSet| -> BTreeSet<(usize, usize)> {
+            state
+                .iter()
+                .map(|item| (item.production, item.dot))
+                .collect()
+        };
+
+        let mut cores: IndexMap<BTreeSet<(usize, usize)>, ItemSet> = IndexMap::new();
+        let mut merged = vec![];
+        for state in self.states.iter() {
+            let entry = cores.entry(core(state));
+            merged.push(entry.index());
+            entry.or_default().extend(state.iter().cloned());
+        }
+
+        let mut transitions = vec![IndexMap::new(); cores.len()];
+        for (state, edges) in self.transitions.into_iter().enumerate() {
+            for (symbol, target) in edges.into_iter() {
+                transitions[merged[state]].insert(symbol, merged[target]);
+            }
+        }
+
+        Automaton {
+            states: cores.into_values().collect(),
+            transitions,
+        }
+    }
+
+    pub(crate) fn states(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Every action of each ACTION table cell, before conflicts are resolved.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn candidates(
+        &self,
+        productions: &[Production],
+        follow: &FollowSet,
+        algorithm: LrAlgorithm,
+    ) -> (
+        IndexMap<(usize, TokenName), Vec<Action>>,
+        IndexMap<(usize, GrammarName), usize>,
+    ) {
+        let mut candidates: IndexMap<(usize, TokenName), Vec<Action>> = IndexMap::new();
+        let mut goto = IndexMap::new();
+        let mut add = |state: usize, token: &str, action: Action| {
+            let cell = candidates.entry((state, token.to_string())).or_default();
+            if !cell.contains(&action) {
+                cell.push(action);
+            }
+        };
+
+        for (state, items) in self.states.iter().enumerate() {
+            for item in items.iter() {
+                let production = &productions[item.production];
+                match production.body.get(item.dot) {
+                    Some(NodeType::Token { name, .. }) if name == EOF => {
+                        add(state, name, Action::Accept)
+                    }
+                    Some(NodeType::Token { name, .. }) => {
+                        add(state, name, Action::Shift(self.transitions[state][name]))
+                    }
+                    Some(NodeType::Grammar { name }) => {
+                        goto.insert((state, name.clone()), self.transitions[state][name]);
+                    }
+                    None if algorithm == LrAlgorithm::Slr => {
+                        for token in follow[&production.grammar].iter() {
+                            add(state, token, Action::Reduce(item.production));
+                        }
+                    }
+                    None => add(state, &item.lookahead, Action::Reduce(item.production)),
+                }
+            }
+        }
+
+        (candidates, goto)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn tables(
+        &self,
+        productions: &[Production],
+        follow: &FollowSet,
+        algorithm: LrAlgorithm,
+        policy: ConflictPolicy,
+    ) -> (
+        IndexMap<(usize, TokenName), Action>,
+        IndexMap<(usize, GrammarName), usize>,
+        Vec<LrConflict>,
+    ) {
+        let (candidates, goto) = self.candidates(productions, follow, algorithm);
+
+        let mut action = IndexMap::new();
+        let mut conflicts = vec![];
+        for ((state, token), mut cell) in candidates.into_iter() {
+            if cell.len() > 1 {
+                let kind = if cell.iter().any(|action| matches!(action, Action::Shift(_))) {
+                    LrConflictKind::ShiftReduce
+                } else if cell.contains(&Action::Accept) {
+                    LrConflictKind::AcceptReduce
+                } else {
+                    LrConflictKind::ReduceReduce
+                };
+                conflicts.push(LrConflict {
+                    kind,
+                    state,
+                    token: token.clone(),
+                    productions: cell
+                        .iter()
+                        .filter_map(|action| match action {
+                            Action::Reduce(index) => Some(productions[*index].clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                });
+            }
+
+            // Shifting wins, then the production declared first or last.
+            cell.sort_by_key(|action| match action {
+                Action::Reduce(index) if policy == ConflictPolicy::LastWins => usize::MAX - index,
+                Action::Reduce(index) => *index,
+                _ => 0,
+            });
+            if let Some(chosen) = cell.first() {
+                action.insert((state, token), *chosen);
+            }
+        }
+
+        (action, goto, conflicts)
+    }
+}
+
+struct Closure<'a> {
+    productions: &'a [Production],
+    nullable: &'a NullableSet,
+    first: &'a FirstSet,
+    lookahead: bool,
+}
+
+impl Closure<'_> {
+    fn close(&self, kernel: ItemSet) -> ItemSet {
+        let mut items = kernel;
+        let mut pending: Vec<Item> = items.iter().cloned().collect();
+
+        while let Some(item) = pending.pop() {
+            let body = &self.productions[item.production].body;
+            let Some(NodeType::Grammar { name }) = body.get(item.dot) else {
+                continue;
+            };
+
+            let lookaheads: IndexSet<TokenName> = if self.lookahead {
+                let (mut tokens, nullable) =
+                    sequence_first(&body[item.dot + 1..], self.nullable, self.first);
+                if nullable {
+                    tokens.insert(item.lookahead.clone());
+                }
+                tokens
+            } else {
+                IndexSet::from([String::new()])
+            };
+
+            for (index, production) in self.productions.iter().enumerate() {
+                if production.grammar != *name {
+                    continue;
+                }
+                for lookahead in lookaheads.iter() {
+                    let item = Item {
+                        production: index,
+                        dot: 0,
+                        lookahead: lookahead.clone(),
+                    };
+                    if items.insert(item.clone()) {
+                        pending.push(item);
+                    }
+                }
+            }
+        }
+
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [LrAlgorithm; 3] =
+        [LrAlgorithm::Slr, LrAlgorithm::Lalr, LrAlgorithm::Canonical];
+
+    fn parser(
+        tokens: &str,
+        grammar: &str,
+        algorithm: LrAlgorithm,
+    ) -> Result<LrParser, GrammarError> {
+        let options = LrOptions {
+            algorithm,
+            ..LrOptions::default()
+        };
+        LrParser::from_str_with_options(grammar, tokens.parse().unwrap(), options)
+    }
+
+    fn conflicts(tokens: &str, grammar: &str, algorithm: LrAlgorithm) -> Vec<LrConflict> {
+        match parser(tokens, grammar, algorithm) {
+            Err(GrammarError::LrConflicts { conflicts, .. }) => conflicts,
+            Err(err) => panic!("{grammar} has another error: {err}"),
+            Ok(..) => panic!("{grammar} has no conflicts under {algorithm}"),
+        }
+    }
+
+    fn shape(ast: &AST) -> String {
+        match ast {
+            AST::Token { value, .. } => value.clone(),
+            AST::Grammar { name, children, .. } => {
+                let children: Vec<String> = children.iter().map(|c| shape(&c.borrow())).collect();
+                format!("{name}({})", children.join(" "))
+            }
+            AST::Error { name, .. } => format!("!{name}"),
+        }
+    }
+
+    #[test]
+    fn left_recursion() {
+        let tokens = "int = [0-9]+\n+ = \\+\n%skip whitespace = \\s+";
+        let grammar = "E -> E + T | T\nT -> int";
+        assert!(Parser::from_str(grammar, tokens.parse().unwrap())
+            .unwrap()
+            .tails()
+            .any(|(tail, _)| tail == "E~tail"));
+
+        for algorithm in ALGORITHMS {
+            let parser = parser(tokens, grammar, algorithm).unwrap();
+            assert!(parser.conflicts().is_empty(), "{algorithm}");
+            let ast = parser.parse("1 + 2 + 3").unwrap();
+            assert_eq!(
+                shape(&ast.borrow()),
+                "E(E(E(T(1)) + T(2)) + T(3))",
+                "{algorithm}"
+            );
+            assert!(parser.parse("1 +").is_err(), "{algorithm}");
+        }
+    }
+
+    #[test]
+    fn merged_lookaheads() {
+        let tokens = "a = a\nb = b\nc = c\nd = d\ne = e";
+        let grammar = "S -> a A d | b B d | a B e | b A e\nA -> c\nB -> c";
+
+        let parser = parser(tokens, grammar, LrAlgorithm::Canonical).unwrap();
+        assert!(parser.parse("acd").is_ok());
+        assert!(parser.parse("bce").is_ok());
+
+        // Merging the states after `a c` and `b c` mixes up their lookaheads.
+        let lalr = conflicts(tokens, grammar, LrAlgorithm::Lalr);
+        assert!(!lalr.is_empty());
+        for conflict in lalr.iter() {
+            assert_eq!(conflict.kind, LrConflictKind::ReduceReduce);
+            let productions: Vec<String> =
+                conflict.productions.iter().map(|p| p.to_string()).collect();
+            assert_eq!(productions, ["A -> c", "B -> c"]);
+        }
+        let tokens: Vec<&str> = lalr.iter().map(|c| c.token.as_str()).collect();
+        assert_eq!(tokens, ["d", "e"]);
+    }
+
+    #[test]
+    fn dangling_else() {
+        let tokens = "if = if\nthen = then\nelse = else\nid = [a-z]+\n%skip whitespace = \\s+";
+        let grammar = "S -> if E then S | if E then S else S | id\nE -> id";
+
+        for algorithm in ALGORITHMS {
+            let conflicts = conflicts(tokens, grammar, algorithm);
+            assert_eq!(conflicts.len(), 1, "{algorithm}");
+            let conflict = &conflicts[0];
+            assert_eq!(conflict.kind, LrConflictKind::ShiftReduce);
+            assert_eq!(conflict.token, "else");
+            let productions: Vec<String> =
+                conflict.productions.iter().map(|p| p.to_string()).collect();
+            assert_eq!(productions, ["S -> if E then S"]);
+        }
+
+        let conflicts = conflicts(tokens, grammar, LrAlgorithm::Lalr);
+        assert_eq!(
+            conflicts[0].to_string(),
+            format!(
+                "Shift/reduce conflict in state {} on else: shift | reduce S -> if E then S",
+                conflicts[0].state
+            )
+        );
+        // The state is the one after `if E then S`.
+        let parser = LrParser::from_str_with_options(
+            grammar,
+            tokens.parse().unwrap(),
+            LrOptions {
+                algorithm: LrAlgorithm::Lalr,
+                conflicts: ConflictPolicy::FirstWins,
+            },
+        )
+        .unwrap();
+        let state = conflicts[0].state;
+        assert!(matches!(
+            parser.action()[&(state, String::from("else"))],
+            Action::Shift(_)
+        ));
+        assert!(matches!(
+            parser.action()[&(state, String::from(EOF))],
+            Action::Reduce(index) if parser.productions()[index].to_string() == "S -> if E then S"
+        ));
+
+        // Shifting binds the else to the nearest if.
+        let ast = parser.parse("if a then if b then c else d").unwrap();
+        assert_eq!(
+            shape(&ast.borrow()),
+            "S(if E(a) then S(if E(b) then S(c) else S(d)))"
+        );
+    }
+
+    #[test]
+    fn accept_reduce() {
+        for algorithm in ALGORITHMS {
+            let conflicts = conflicts("a = a", "S -> S | a", algorithm);
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].kind, LrConflictKind::AcceptReduce);
+            assert_eq!(conflicts[0].token, EOF);
+            assert!(conflicts[0]
+                .to_string()
+                .ends_with("on $: accept | reduce S -> S"));
+        }
+    }
+
+    #[test]
+    fn no_state_after_the_end() {
+        let parser = parser("a = a", "S -> a", LrAlgorithm::Canonical).unwrap();
+        // __ROOT -> . S $, S -> a ., __ROOT -> S . $
+        assert_eq!(parser.states(), 3);
+        assert!(parser
+            .action()
+            .values()
+            .all(|action| *action != Action::Reduce(0)));
+    }
+}
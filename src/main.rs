@@ -1,116 +1,254 @@
-use clap::Parser as CLIParser;
-use std::fs::read_to_string;
+use clap::{Parser as CLIParser, Subcommand, ValueEnum};
+use std::fs::{read_to_string, write};
 
-use crate::parser::{Parser, AST};
-use crate::tokenizer::Tokenizer;
-
-mod parser;
-mod tokenizer;
+use rust_parser::{
+    report, Backend as ReportBackend, ConflictPolicy, LrAlgorithm, Parser, ParserOptions,
+    PegOptions, ReportOptions, Tokenizer,
+};
 
 #[derive(CLIParser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    token_path: String,
-    grammar_path: String,
-    content_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(required = true)]
+    token_path: Option<String>,
+    #[arg(required = true)]
+    grammar_path: Option<String>,
+    #[arg(required = true)]
+    content_path: Option<String>,
+
+    /// How to resolve conflicts in the parsing table
+    #[arg(long, value_enum, default_value_t = Conflicts::Reject)]
+    conflicts: Conflicts,
+
+    /// Parsing algorithm
+    #[arg(long, value_enum, default_value_t = Backend::Ll)]
+    backend: Backend,
+
+    /// Factor common prefixes of variants into helper grammars
+    #[arg(long)]
+    left_factor: bool,
+
+    /// Tokens of lookahead in the parsing table, the k in LL(k)
+    #[arg(long, default_value_t = 1)]
+    lookahead: usize,
+
+    /// Match token patterns where the grammar expects them instead of tokenizing first
+    /// (PEG only)
+    #[arg(long)]
+    scannerless: bool,
+
+    /// Keep parsing after errors and report all of them (LL(k) only)
+    #[arg(long)]
+    recover: bool,
+
+    /// Print source spans of the AST nodes
+    #[arg(long)]
+    spans: bool,
 }
 
-fn main() {
-    let args = Cli::parse();
-
-    let tokenizer = Tokenizer::from_file(args.token_path.as_str()).unwrap();
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a standalone Rust parser module that only depends on regex
+    Codegen {
+        token_path: String,
+        grammar_path: String,
+
+        /// Write the module to this file instead of the standard output
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// How to resolve LL(k) conflicts in the parsing table
+        #[arg(long, value_enum, default_value_t = Conflicts::Reject)]
+        conflicts: Conflicts,
+
+        /// Factor common prefixes of variants into helper grammars
+        #[arg(long)]
+        left_factor: bool,
+
+        /// Tokens of lookahead in the parsing table, the k in LL(k)
+        #[arg(long, default_value_t = 1)]
+        lookahead: usize,
+    },
+    /// Generate Rust types for the grammars with conversions from the AST
+    Types {
+        token_path: String,
+        grammar_path: String,
+
+        /// Write the module to this file instead of the standard output
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// How to resolve LL(k) conflicts in the parsing table
+        #[arg(long, value_enum, default_value_t = Conflicts::Reject)]
+        conflicts: Conflicts,
+
+        /// Factor common prefixes of variants into helper grammars
+        #[arg(long)]
+        left_factor: bool,
+
+        /// Tokens of lookahead in the parsing table, the k in LL(k)
+        #[arg(long, default_value_t = 1)]
+        lookahead: usize,
+    },
+}
 
-    println!("Patterns: ");
-    for pattern in tokenizer.patterns.iter() {
-        println!("{} = {}", pattern.name, pattern.value);
-    }
+#[derive(Clone, Copy, ValueEnum)]
+enum Backend {
+    /// Table-driven LL(k), with k set by --lookahead
+    #[value(alias = "ll1")]
+    Ll,
+    /// SLR(1) tables from LR(0) item sets
+    Slr,
+    /// LALR(1) tables from merged LR(1) item sets
+    Lalr,
+    /// Canonical LR(1) tables
+    Lr1,
+    /// Earley chart parsing of any grammar, reporting ambiguous input
+    Earley,
+    /// Generalized LR parsing into a forest of all derivations, filtered by
+    /// %prefer, %avoid and %reject
+    Glr,
+    /// Packrat parsing with ordered choice and &X / !X predicates
+    Peg,
+}
 
-    let content = read_to_string(args.content_path.as_str()).expect(
-        format!(
-            "Unable to open the specified file: {}",
-            args.content_path.as_str()
-        )
-        .as_str(),
-    );
-
-    println!();
-    println!("Tokens: ");
-    match tokenizer.parse(content.as_str()) {
-        Ok(result) => {
-            for token in result.iter() {
-                println!("{}", token)
-            }
-        }
-        Err(err) => {
-            println!("{}", err);
+impl From<Backend> for ReportBackend {
+    fn from(value: Backend) -> Self {
+        match value {
+            Backend::Ll => ReportBackend::Ll,
+            Backend::Slr => ReportBackend::Lr(LrAlgorithm::Slr),
+            Backend::Lalr => ReportBackend::Lr(LrAlgorithm::Lalr),
+            Backend::Lr1 => ReportBackend::Lr(LrAlgorithm::Canonical),
+            Backend::Earley => ReportBackend::Earley,
+            Backend::Glr => ReportBackend::Glr,
+            Backend::Peg => ReportBackend::Peg,
         }
     }
+}
 
-    let parser = Parser::from_file(args.grammar_path.as_str(), tokenizer).unwrap();
-
-    println!();
-    println!("Grammars: ");
-    for (name, variants) in parser.grammars.iter() {
-        for variant in variants.iter() {
-            let body: String = variant
-                .iter()
-                .map(|variant| variant.to_string())
-                .collect::<Vec<String>>()
-                .join(" ");
+#[derive(Clone, Copy, ValueEnum)]
+enum Conflicts {
+    Reject,
+    First,
+    Last,
+}
 
-            println!("{} -> {}", name, body);
+impl From<Conflicts> for ConflictPolicy {
+    fn from(value: Conflicts) -> Self {
+        match value {
+            Conflicts::Reject => ConflictPolicy::Reject,
+            Conflicts::First => ConflictPolicy::FirstWins,
+            Conflicts::Last => ConflictPolicy::LastWins,
         }
     }
+}
 
-    println!();
-    println!("FIRST:");
-    for (k, v) in parser.first.iter() {
-        let values: Vec<&str> = v.iter().map(|value| value.as_str()).collect();
-        println!("FIRST({}) = {}", k, values.join(", "))
-    }
+fn main() {
+    let args = Cli::parse();
 
-    println!();
-    println!("FOLLOW:");
-    for (k, v) in parser.follow.iter() {
-        let values: Vec<&str> = v.iter().map(|value| value.as_str()).collect();
-        println!("FOLLOW({}) = {}", k, values.join(", "));
+    match args.command {
+        Some(Command::Codegen {
+            token_path,
+            grammar_path,
+            output,
+            conflicts,
+            left_factor,
+            lookahead,
+        }) => {
+            let options = ParserOptions {
+                conflicts: conflicts.into(),
+                left_factor,
+                lookahead,
+                ..ParserOptions::default()
+            };
+            generate(&token_path, &grammar_path, output, options, Parser::codegen);
+            return;
+        }
+        Some(Command::Types {
+            token_path,
+            grammar_path,
+            output,
+            conflicts,
+            left_factor,
+            lookahead,
+        }) => {
+            let options = ParserOptions {
+                conflicts: conflicts.into(),
+                left_factor,
+                lookahead,
+                ..ParserOptions::default()
+            };
+            generate(
+                &token_path,
+                &grammar_path,
+                output,
+                options,
+                Parser::typed_ast,
+            );
+            return;
+        }
+        None => {}
     }
 
-    println!();
-    println!("PARSING TABLE:");
-    for ((grammar_name, token_name), variant) in parser.table.iter() {
-        let nodes: Vec<String> = variant.iter().map(|node| node.to_string()).collect();
-        println!(
-            "({grammar_name}, {token_name}) = {grammar_name} -> {}",
-            nodes.join(" ")
+    let token_path = args.token_path.unwrap();
+    let grammar_path = args.grammar_path.unwrap();
+    let content_path = args.content_path.unwrap();
+
+    let tokenizer = Tokenizer::from_file(token_path.as_str()).unwrap();
+    let grammar = read_to_string(grammar_path.as_str()).unwrap_or_else(|_| {
+        panic!(
+            "Unable to open the specified file: {}",
+            grammar_path.as_str()
         )
-    }
+    });
+    let content = read_to_string(content_path.as_str()).unwrap_or_else(|_| {
+        panic!(
+            "Unable to open the specified file: {}",
+            content_path.as_str()
+        )
+    });
+
+    let options = ReportOptions {
+        backend: args.backend.into(),
+        parser: ParserOptions {
+            conflicts: args.conflicts.into(),
+            left_factor: args.left_factor,
+            lookahead: args.lookahead,
+            ..ParserOptions::default()
+        },
+        peg: PegOptions {
+            scannerless: args.scannerless,
+        },
+        recover: args.recover,
+        spans: args.spans,
+    };
+    print!("{}", report(tokenizer, &grammar, &content, &options));
+}
 
-    let result = parser.parse(content.as_str());
-    println!();
-    match result {
-        Ok(ast) => {
-            println!("Result: ");
-            print_ast(&ast.borrow(), 0);
-        }
+fn generate(
+    token_path: &str,
+    grammar_path: &str,
+    output: Option<String>,
+    options: ParserOptions,
+    generator: fn(&Parser) -> String,
+) {
+    let tokenizer = Tokenizer::from_file(token_path).unwrap();
+    let parser = match Parser::from_file_with_options(grammar_path, tokenizer, options) {
+        Ok(parser) => parser,
         Err(err) => {
-            println!("Parsing error:");
-            println!("{}", err);
+            eprintln!("Grammar error:");
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
-    }
-}
-
-fn print_ast(ast: &AST, level: usize) {
-    let indent = "  ".repeat(level);
+    };
 
-    match &ast {
-        AST::Token { value, .. } => {
-            println!("{indent}{value}");
-        }
-        AST::Grammar { name, children } => {
-            println!("{indent}{name}");
-            for item in children.iter() {
-                print_ast(&item.borrow(), level + 1);
-            }
-        }
+    let code = generator(&parser);
+    match output {
+        Some(path) => write(&path, code)
+            .unwrap_or_else(|_| panic!("Unable to write the specified file: {}", path)),
+        None => print!("{}", code),
     }
 }
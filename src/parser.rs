@@ -1,33 +1,143 @@
 use std::cell::RefCell;
 use std::fmt::{Display, Formatter};
 use std::fs::read_to_string;
-use std::ops::DerefMut;
+use std::io::Read;
+use std::ops::{DerefMut, RangeInclusive};
 use std::rc::Rc;
 
 use indexmap::{IndexMap, IndexSet};
 use regex::Regex;
 
-use crate::tokenizer::{Pattern, Token, Tokenizer, EPSILON};
+use crate::error::{GrammarError, ParseError};
+use crate::factoring;
+use crate::grammar;
+use crate::precedence::{self, Operators};
+use crate::recursion;
+use crate::tokenizer::{end_span, Pattern, PatternKind, Span, Token, Tokenizer, EPSILON};
 
 pub struct Parser {
-    pub grammars: IndexMap<String, GrammarVariants>,
+    pub(crate) grammars: IndexMap<String, GrammarVariants>,
+    pub(crate) nullable: NullableSet,
     pub(crate) first: FirstSet,
     pub(crate) follow: FollowSet,
     pub(crate) table: ParsingTable,
     pub(crate) tokenizer: Tokenizer,
+    pub(crate) conflicts: Vec<Conflict>,
+    pub(crate) options: ParserOptions,
+    /// Grammars introduced while reading or rewriting the declared ones.
+    pub(crate) helpers: IndexMap<GrammarName, Helper>,
+    pub(crate) operators: Operators,
 }
 
-const EOF: &'static str = "$";
-const ROOT: &'static str = "__ROOT";
+/// How a grammar that wasn't declared by the user shows up in the AST.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Helper {
+    /// Its children are spliced into the parent node.
+    Inline,
+    /// The tail of a left-recursive grammar; its levels are folded into left-nested nodes.
+    Tail { grammar: GrammarName },
+}
+
+pub(crate) const EOF: &str = "$";
+pub(crate) const ROOT: &str = "__ROOT";
 
 pub type GrammarVariants = Vec<GrammarVariant>;
 
-type GrammarVariant = Vec<NodeType>;
-type TokenName = String;
-type GrammarName = String;
-type FirstSet = IndexMap<GrammarName, IndexSet<TokenName>>;
-type FollowSet = IndexMap<GrammarName, IndexSet<TokenName>>;
-type ParsingTable = IndexMap<(GrammarName, TokenName), GrammarVariant>;
+pub type GrammarVariant = Vec<NodeType>;
+pub type TokenName = String;
+pub type GrammarName = String;
+pub type NullableSet = IndexSet<GrammarName>;
+pub type FirstSet = IndexMap<GrammarName, IndexSet<TokenName>>;
+pub type FollowSet = IndexMap<GrammarName, IndexSet<TokenName>>;
+/// The next tokens of the input, at most [ParserOptions::lookahead] of them.
+/// Shorter sequences end with the end of the input.
+pub type Lookahead = Vec<TokenName>;
+pub type ParsingTable = IndexMap<(GrammarName, Lookahead), GrammarVariant>;
+type LookaheadSet = IndexMap<GrammarName, IndexSet<Lookahead>>;
+
+/// The largest lookahead tried when looking for the one a grammar needs.
+pub const MAX_LOOKAHEAD: usize = 4;
+
+/// Decides what happens when two variants compete for the same parsing table cell.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Refuse to build a parser for a grammar that is not LL(k).
+    #[default]
+    Reject,
+    /// Keep the variant declared first in the grammar file.
+    FirstWins,
+    /// Keep the variant declared last in the grammar file.
+    LastWins,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    pub conflicts: ConflictPolicy,
+    /// Number of tokens the parsing table looks at to choose a variant, the k in LL(k).
+    /// Values below 1 are treated as 1.
+    pub lookahead: usize,
+    /// Extra tokens to synchronize on during error recovery, in addition to FOLLOW sets.
+    pub sync: IndexSet<TokenName>,
+    /// Factor prefixes shared by several variants of a grammar into helper grammars.
+    /// The AST keeps the shape of the declared rules.
+    pub left_factor: bool,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            conflicts: ConflictPolicy::default(),
+            lookahead: 1,
+            sync: IndexSet::new(),
+            left_factor: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Several variants start with the same token.
+    FirstFirst,
+    /// A nullable variant competes with a token that may follow the grammar.
+    FirstFollow,
+}
+
+/// Several variants of a grammar claim the same lookahead.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub grammar: GrammarName,
+    pub lookahead: Lookahead,
+    pub variants: Vec<GrammarVariant>,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            ConflictKind::FirstFirst => "FIRST/FIRST",
+            ConflictKind::FirstFollow => "FIRST/FOLLOW",
+        };
+        let variants: Vec<String> = self
+            .variants
+            .iter()
+            .map(|variant| {
+                variant
+                    .iter()
+                    .map(|node| node.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .collect();
+
+        write!(
+            f,
+            "{kind} conflict in {} on {}: {}",
+            self.grammar,
+            self.lookahead.join(" "),
+            variants.join(" | ")
+        )
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum NodeType {
@@ -49,124 +159,310 @@ impl Display for NodeType {
 }
 
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum AST {
     Token {
         name: String,
         value: String,
+        span: Span,
     },
+    /// Spans of grammars cover their first and last non-empty children.
+    /// Grammars that derived epsilon get an empty span located at the next token.
     Grammar {
         name: String,
         children: Vec<Rc<RefCell<AST>>>,
+        span: Span,
     },
+    /// A grammar or a token that could not be parsed, together with the input skipped to recover.
+    Error {
+        name: String,
+        skipped: Vec<Rc<RefCell<AST>>>,
+        span: Span,
+    },
+}
+
+impl AST {
+    pub fn span(&self) -> Span {
+        match self {
+            AST::Token { span, .. } | AST::Grammar { span, .. } | AST::Error { span, .. } => *span,
+        }
+    }
+}
+
+/// Result of [Parser::parse_recovering]: the partial tree and every error found on the way.
+#[derive(Debug)]
+pub struct Recovered {
+    pub ast: Option<Rc<RefCell<AST>>>,
+    pub errors: Vec<ParseError>,
 }
 
 impl Parser {
-    pub fn from_file(path: &str, tokenizer: Tokenizer) -> Result<Parser, String> {
+    pub fn from_file(path: &str, tokenizer: Tokenizer) -> Result<Parser, GrammarError> {
+        Parser::from_file_with_options(path, tokenizer, ParserOptions::default())
+    }
+
+    pub fn from_file_with_options(
+        path: &str,
+        tokenizer: Tokenizer,
+        options: ParserOptions,
+    ) -> Result<Parser, GrammarError> {
         let content = match read_to_string(path) {
-            Err(e) => return Err(format!("Unable to open the specified file: {e}")),
+            Err(e) => return Err(GrammarError::Io(e)),
             Ok(f) => f,
         };
 
-        let grammar_declaration = Regex::new(r"(?<name>.+)\s*->\s*(?<pattern>.*)").unwrap();
+        Parser::from_str_with_options(&content, tokenizer, options)
+    }
 
-        let mut grammars: IndexMap<String, GrammarVariants> = IndexMap::new();
+    pub fn from_reader(
+        mut reader: impl Read,
+        tokenizer: Tokenizer,
+    ) -> Result<Parser, GrammarError> {
+        let mut content = String::new();
+        if let Err(e) = reader.read_to_string(&mut content) {
+            return Err(GrammarError::Io(e));
+        }
 
-        for (index, line) in content.lines().enumerate() {
-            if let Some(capture) = grammar_declaration.captures(line) {
-                let (_, [name, pattern]) = capture.extract();
+        Parser::from_str(&content, tokenizer)
+    }
 
-                let name = name.trim();
-                let variant_patterns: Vec<&str> = pattern.split("|").map(|s| s.trim()).collect();
+    pub fn from_str(content: &str, tokenizer: Tokenizer) -> Result<Parser, GrammarError> {
+        Parser::from_str_with_options(content, tokenizer, ParserOptions::default())
+    }
 
-                let mut variants: GrammarVariants = vec![];
-                for variant_pattern in variant_patterns {
-                    let body = variant_pattern.split(" ");
-                    let mut nodes: Vec<NodeType> = vec![];
-                    for item in body {
-                        if let Some(pattern) =
-                            tokenizer.patterns.iter().find(|token| token.name == item)
-                        {
-                            nodes.push(NodeType::Token {
-                                name: pattern.name.clone(),
-                                pattern: pattern.clone(),
-                            });
-                        } else {
-                            nodes.push(NodeType::Grammar {
-                                name: String::from(item),
-                            })
-                        }
-                    }
+    /// Reads grammars in the `Name -> Variant | Variant` format, one per line.
+    /// The first grammar is the start of the parse.
+    /// Variants may use the `*`, `+` and `?` operators and `( a | b )` groups.
+    /// Direct left recursion like `E -> E + T | T` is rewritten, keeping left-nested ASTs.
+    /// Operators declared with `%left`, `%right` and `%prefix` are used by `E -> %expr Operand`,
+    /// which the table parses as a flat list and which is nested by precedence after parsing.
+    pub fn from_str_with_options(
+        content: &str,
+        mut tokenizer: Tokenizer,
+        options: ParserOptions,
+    ) -> Result<Parser, GrammarError> {
+        let grammars = grammar::read(content, &mut tokenizer)?;
+        grammars.reject_annotations()?;
 
-                    variants.push(nodes);
-                }
+        let mut parser = Parser::new(grammars.declared, tokenizer, options)?;
+        parser.helpers.extend(
+            grammars
+                .helpers
+                .into_iter()
+                .map(|name| (name, Helper::Inline)),
+        );
+        parser.operators = grammars.operators;
+        Ok(parser)
+    }
 
-                if index == 0 {
-                    // Insert the root grammar as a parse start
-                    grammars.insert(
-                        String::from(ROOT),
-                        vec![vec![
-                            NodeType::Grammar {
-                                name: String::from(name),
-                            },
-                            Parser::eof(),
-                        ]],
-                    );
-                }
+    /// Builds the parser for grammars whose first entry is the start of the parse,
+    /// like the ones returned by [GrammarBuilder::finish](crate::GrammarBuilder::finish).
+    pub fn new(
+        mut declared: IndexMap<GrammarName, GrammarVariants>,
+        tokenizer: Tokenizer,
+        mut options: ParserOptions,
+    ) -> Result<Parser, GrammarError> {
+        options.lookahead = options.lookahead.max(1);
 
-                grammars.insert(String::from(name), variants);
-            }
+        if declared.is_empty() {
+            return Err(GrammarError::Empty);
         }
 
-        let mut first = IndexMap::new();
-        let mut follow = IndexMap::new();
+        validate(&declared)?;
 
-        for grammar in grammars.keys() {
-            build_first(grammar, &grammars, &mut first);
+        let mut helpers = IndexMap::new();
+        if options.left_factor {
+            helpers.extend(factoring::factor(&mut declared));
         }
+        helpers.extend(recursion::eliminate(&mut declared)?);
 
-        for grammar in grammars.keys() {
-            build_follow(grammar, &grammars, &first, &mut follow);
+        let start = declared.keys().next().unwrap();
+
+        // Insert the root grammar as a parse start
+        let mut grammars = IndexMap::from([(
+            String::from(ROOT),
+            vec![vec![
+                NodeType::Grammar {
+                    name: start.clone(),
+                },
+                Parser::eof(),
+            ]],
+        )]);
+        grammars.extend(declared);
+
+        let nullable = build_nullable(&grammars);
+        let first = build_first(&grammars);
+        let follow = build_follow(&grammars, &first);
+
+        let (table, conflicts) =
+            build_parsing_table(&grammars, options.lookahead, options.conflicts);
+        if options.conflicts == ConflictPolicy::Reject && !conflicts.is_empty() {
+            return Err(GrammarError::Conflicts {
+                lookahead: options.lookahead,
+                minimum: minimum_lookahead(&grammars, options.lookahead + 1..=MAX_LOOKAHEAD),
+                conflicts,
+            });
         }
 
-        let table = build_parsing_table(&grammars, &first, &follow);
         Ok(Parser {
             grammars,
+            nullable,
             first,
             follow,
             table,
             tokenizer,
+            conflicts,
+            options,
+            helpers,
+            operators: Operators::default(),
         })
     }
 
-    fn eof() -> NodeType {
+    /// Grammars in declaration order, starting with the internal root grammar.
+    pub fn grammars(&self) -> &IndexMap<GrammarName, GrammarVariants> {
+        &self.grammars
+    }
+
+    /// Grammars that may derive an empty sequence of tokens.
+    pub fn nullable(&self) -> &NullableSet {
+        &self.nullable
+    }
+
+    pub fn first(&self) -> &FirstSet {
+        &self.first
+    }
+
+    pub fn follow(&self) -> &FollowSet {
+        &self.follow
+    }
+
+    pub fn table(&self) -> &ParsingTable {
+        &self.table
+    }
+
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    /// Conflicts found while building the parsing table and resolved by the [ConflictPolicy].
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Number of tokens the parsing table looks ahead.
+    pub fn lookahead(&self) -> usize {
+        self.options.lookahead
+    }
+
+    /// The smallest k for which the grammars are LL(k) without conflicts,
+    /// or `None` if they need more than [MAX_LOOKAHEAD] tokens.
+    pub fn minimum_lookahead(&self) -> Option<usize> {
+        if self.conflicts.is_empty() {
+            let smaller = minimum_lookahead(&self.grammars, 1..=self.options.lookahead - 1);
+            return Some(smaller.unwrap_or(self.options.lookahead));
+        }
+
+        minimum_lookahead(&self.grammars, self.options.lookahead + 1..=MAX_LOOKAHEAD)
+    }
+
+    /// Grammars introduced to eliminate left recursion, with the grammar each of them belongs to.
+    pub fn tails(&self) -> impl Iterator<Item = (&GrammarName, &GrammarName)> {
+        self.helpers
+            .iter()
+            .filter_map(|(tail, helper)| match helper {
+                Helper::Tail { grammar } => Some((tail, grammar)),
+                Helper::Inline => None,
+            })
+    }
+
+    /// Names of the next tokens used to look up the parsing table.
+    fn lookahead_at(&self, tokens: &[Token], position: usize) -> Lookahead {
+        tokens[position..]
+            .iter()
+            .take(self.options.lookahead)
+            .map(|token| token.name.clone())
+            .collect()
+    }
+
+    /// Finds the offset of the first lookahead token that no entry of the grammar accepts,
+    /// together with the tokens its entries accept there.
+    fn mismatch(&self, grammar: &str, lookahead: &[TokenName]) -> (usize, Vec<TokenName>) {
+        let entries: Vec<&Lookahead> = self
+            .table
+            .keys()
+            .filter(|(name, _)| name == grammar)
+            .map(|(_, entry)| entry)
+            .collect();
+
+        let mut offset = 0;
+        loop {
+            let expected: IndexSet<TokenName> = entries
+                .iter()
+                .filter(|entry| entry.len() > offset && entry[..offset] == lookahead[..offset])
+                .map(|entry| entry[offset].clone())
+                .collect();
+
+            if offset + 1 < lookahead.len() && expected.contains(&lookahead[offset]) {
+                offset += 1;
+                continue;
+            }
+
+            return (offset, expected.into_iter().collect());
+        }
+    }
+
+    pub(crate) fn eof() -> NodeType {
         NodeType::Token {
             name: EOF.to_string(),
             pattern: Pattern {
                 name: EOF.to_string(),
                 value: Regex::new("").unwrap(),
+                kind: PatternKind::Token,
             },
         }
     }
 
-    pub fn parse(&self, content: &str) -> Result<Rc<RefCell<AST>>, String> {
-        let result = self.tokenizer.parse(content);
+    pub fn parse(&self, content: &str) -> Result<Rc<RefCell<AST>>, ParseError> {
+        let mut errors = vec![];
+        let result = self.drive(content, &mut errors, false);
 
-        if let Err(err) = result {
-            return Err(err);
+        match errors.pop() {
+            Some(error) => Err(error),
+            None => result.ok_or(ParseError::NoGrammars),
         }
+    }
+
+    /// Parses the content without stopping at the first error.
+    ///
+    /// On a parsing table miss the input is skipped until a token in the FOLLOW set of the
+    /// current grammar (or in [ParserOptions::sync]) is found and the grammar is replaced with
+    /// an [AST::Error] node. Missing tokens are replaced with empty [AST::Error] nodes.
+    pub fn parse_recovering(&self, content: &str) -> Recovered {
+        let mut errors = vec![];
+        let ast = self.drive(content, &mut errors, true);
+        Recovered { ast, errors }
+    }
 
-        let mut tokens = result.unwrap();
+    fn drive(
+        &self,
+        content: &str,
+        errors: &mut Vec<ParseError>,
+        recover: bool,
+    ) -> Option<Rc<RefCell<AST>>> {
+        let mut tokens = match self.tokenizer.parse(content) {
+            Ok(tokens) => tokens,
+            Err(err) => {
+                errors.push(err.into());
+                return None;
+            }
+        };
         tokens.push(Token {
             name: EOF.to_string(),
             value: EOF.to_string(),
-            line: 0,
-            column: 0,
+            span: end_span(content),
         });
 
-        let eof = Rc::new(RefCell::new(AST::Token {
-            name: EOF.to_string(),
-            value: EOF.to_string(),
-        }));
+        let eof = Rc::new(RefCell::new((&tokens[tokens.len() - 1]).into()));
         let mut stack: Vec<Rc<RefCell<AST>>> = vec![eof];
 
         let root;
@@ -174,285 +470,1058 @@ impl Parser {
             root = Rc::new(RefCell::new(AST::Grammar {
                 name: root_name.clone(),
                 children: vec![],
+                span: Span::default(),
             }));
             stack.push(root.clone());
         } else {
-            return Err(String::from("Parser doesn't have any grammars."));
+            errors.push(ParseError::NoGrammars);
+            return None;
         }
 
-        let mut buffer = tokens.iter();
-        let mut next_token: &Token;
-
-        if let Some(value) = buffer.next() {
-            next_token = value;
-        } else {
-            return Err(String::from("Unexpected end of stream."));
-        }
+        let mut position = 0;
 
         loop {
-            if let Some(rc) = stack.pop() {
-                let mut ast = rc.borrow_mut();
-                match &mut ast.deref_mut() {
-                    AST::Grammar {
-                        name,
-                        ref mut children,
-                    } => match self.table.get(&(name.clone(), next_token.name.clone())) {
+            let next_token = &tokens[position];
+
+            let Some(rc) = stack.pop() else {
+                errors.push(unexpected_token(next_token, vec![], None));
+                return None;
+            };
+
+            let mut ast = rc.borrow_mut();
+            match ast.deref_mut() {
+                AST::Grammar {
+                    name,
+                    children,
+                    span,
+                } => {
+                    if children.is_empty() {
+                        *span = next_token.span.empty();
+                    }
+
+                    let lookahead = self.lookahead_at(&tokens, position);
+                    match self.table.get(&(name.clone(), lookahead)) {
                         Some(variant) => {
-                            if is_epsilon(variant) {
+                            if is_epsilon_variant(variant) {
                                 continue;
-                            } else {
-                                let mut nodes = variant.clone();
-                                nodes.reverse();
-
-                                for variant_node in nodes.iter() {
-                                    let child: Rc<RefCell<AST>>;
-
-                                    match variant_node {
-                                        NodeType::Token { name, .. } => {
-                                            child = Rc::new(RefCell::new(AST::Token {
-                                                name: name.clone(),
-                                                value: String::new(),
-                                            }));
-                                        }
-                                        NodeType::Grammar { name } => {
-                                            child = Rc::new(RefCell::new(AST::Grammar {
-                                                name: name.clone(),
-                                                children: vec![],
-                                            }));
-                                        }
-                                    }
+                            }
 
-                                    let clone = child.clone();
-                                    children.insert(0, child);
-                                    stack.push(clone);
-                                }
+                            let nodes: Vec<Rc<RefCell<AST>>> = variant
+                                .iter()
+                                .map(|node| Rc::new(RefCell::new(node.into())))
+                                .collect();
+
+                            for node in nodes.iter().rev() {
+                                stack.push(node.clone());
                             }
+                            children.extend(nodes);
                         }
                         None => {
-                            return unexpected_token(&next_token);
-                        }
-                    },
-                    AST::Token { name, .. } => {
-                        if name.clone().eq(&next_token.name.clone()) {
-                            if name == EOF {
-                                break;
+                            let lookahead = self.lookahead_at(&tokens, position);
+                            let (offset, expected) = self.mismatch(name, &lookahead);
+                            let token = &tokens[position + offset];
+                            report(errors, unexpected_token(token, expected, Some(name)));
+                            if !recover {
+                                return None;
                             }
 
-                            *ast = AST::Token {
-                                name: name.clone(),
-                                value: next_token.value.clone(),
-                            };
+                            let name = name.clone();
+                            let mut skipped = vec![];
+                            loop {
+                                let token = &tokens[position];
+                                let lookahead = self.lookahead_at(&tokens, position);
+                                if self.table.contains_key(&(name.clone(), lookahead)) {
+                                    // The grammar can start here: keep the skipped input and expand it again.
+                                    if let AST::Grammar { children, .. } = ast.deref_mut() {
+                                        children.push(Rc::new(RefCell::new(AST::Error {
+                                            name: name.clone(),
+                                            span: skipped_span(&skipped, token),
+                                            skipped,
+                                        })));
+                                    }
+                                    drop(ast);
+                                    stack.push(rc.clone());
+                                    break;
+                                }
 
-                            if let Some(next) = buffer.next() {
-                                next_token = next;
-                            } else {
-                                return Err(String::from("Unexpected end of stream."));
+                                if token.name == EOF || self.is_synchronizing(&name, &token.name) {
+                                    *ast = AST::Error {
+                                        name: name.clone(),
+                                        span: skipped_span(&skipped, token),
+                                        skipped,
+                                    };
+                                    break;
+                                }
+
+                                skipped.push(Rc::new(RefCell::new(token.into())));
+                                position += 1;
                             }
-                        } else {
-                            return unexpected_token(&next_token);
                         }
                     }
                 }
-            } else {
-                return unexpected_token(&next_token);
+                AST::Token { name, .. } => {
+                    if *name == next_token.name {
+                        if name == EOF {
+                            break;
+                        }
+
+                        *ast = next_token.into();
+                        position += 1;
+
+                        if position >= tokens.len() {
+                            errors.push(ParseError::UnexpectedEndOfStream {
+                                span: next_token.span,
+                            });
+                            return None;
+                        }
+                    } else {
+                        report(
+                            errors,
+                            unexpected_token(next_token, vec![name.clone()], None),
+                        );
+                        if !recover {
+                            return None;
+                        }
+
+                        if name == EOF {
+                            // Everything after a complete parse is reported once and dropped.
+                            break;
+                        }
+
+                        *ast = AST::Error {
+                            name: name.clone(),
+                            skipped: vec![],
+                            span: next_token.span.empty(),
+                        };
+                    }
+                }
+                AST::Error { .. } => {}
             }
         }
 
+        finish_spans(&root);
+        if !self.helpers.is_empty() {
+            recursion::reassociate(&root, &self.helpers);
+            splice_helpers(&root, &self.helpers);
+        }
+        if !self.operators.expressions.is_empty() {
+            precedence::fold(&root, &self.operators);
+        }
+
         if let AST::Grammar { ref children, .. } = root.borrow_mut().deref_mut() {
             // Remove ROOT grammar from the AST since it's an internal grammar used for parsing.
-            return Ok(children.first().unwrap().clone());
+            return children.first().cloned();
         }
 
-        Ok(root)
+        Some(root)
+    }
+
+    fn is_synchronizing(&self, grammar: &str, token: &str) -> bool {
+        self.options.sync.contains(token)
+            || self
+                .follow
+                .get(grammar)
+                .is_some_and(|tokens| tokens.contains(token))
     }
 }
 
-fn build_first(
-    grammar_name: &GrammarName,
-    grammars: &IndexMap<String, GrammarVariants>,
-    first: &mut FirstSet,
-) {
-    if let Some(_) = first.get(grammar_name) {
-        return;
+impl From<&NodeType> for AST {
+    fn from(value: &NodeType) -> Self {
+        match value {
+            NodeType::Token { name, .. } => AST::Token {
+                name: name.clone(),
+                value: String::new(),
+                span: Span::default(),
+            },
+            NodeType::Grammar { name } => AST::Grammar {
+                name: name.clone(),
+                children: vec![],
+                span: Span::default(),
+            },
+        }
     }
+}
 
-    let mut nodes = IndexSet::new();
-    if let Some(variants) = grammars.get(grammar_name) {
+impl From<&Token> for AST {
+    fn from(value: &Token) -> Self {
+        AST::Token {
+            name: value.name.clone(),
+            value: value.value.clone(),
+            span: value.span,
+        }
+    }
+}
+
+/// Derives spans of grammar and error nodes from their children once the tree is complete.
+fn finish_spans(node: &Rc<RefCell<AST>>) -> Span {
+    let mut ast = node.borrow_mut();
+    match ast.deref_mut() {
+        AST::Token { span, .. } => *span,
+        AST::Grammar { children, span, .. }
+        | AST::Error {
+            skipped: children,
+            span,
+            ..
+        } => {
+            let spans: Vec<Span> = children.iter().map(finish_spans).collect();
+            if let Some(covered) = cover(&spans) {
+                *span = covered;
+            }
+            *span
+        }
+    }
+}
+
+/// Replaces helper grammars with their children, so `X*` produces a flat list of `X` nodes.
+pub(crate) fn splice_helpers(node: &Rc<RefCell<AST>>, helpers: &IndexMap<GrammarName, Helper>) {
+    let mut ast = node.borrow_mut();
+    let children = match ast.deref_mut() {
+        AST::Token { .. } => return,
+        AST::Grammar { children, .. }
+        | AST::Error {
+            skipped: children, ..
+        } => children,
+    };
+
+    let mut pending: Vec<Rc<RefCell<AST>>> = children.drain(..).rev().collect();
+    while let Some(child) = pending.pop() {
+        let spliced = match child.borrow_mut().deref_mut() {
+            AST::Grammar { name, children, .. } if helpers.get(name) == Some(&Helper::Inline) => {
+                Some(std::mem::take(children))
+            }
+            _ => None,
+        };
+
+        match spliced {
+            Some(grandchildren) => pending.extend(grandchildren.into_iter().rev()),
+            None => {
+                splice_helpers(&child, helpers);
+                children.push(child);
+            }
+        }
+    }
+}
+
+/// Returns a span from the first to the last non-empty span, or the first span if all are empty.
+pub(crate) fn cover(spans: &[Span]) -> Option<Span> {
+    let mut non_empty = spans.iter().filter(|span| span.start != span.end);
+    match (non_empty.next(), non_empty.next_back()) {
+        (Some(first), Some(last)) => Some(first.to(last)),
+        (Some(first), None) => Some(*first),
+        _ => spans.first().copied(),
+    }
+}
+
+/// Adds the error unless the previous one is at the same token, which happens when recovery
+/// moves on to the next grammar without consuming input.
+fn report(errors: &mut Vec<ParseError>, error: ParseError) {
+    let span = error.span();
+    if span.is_none() || errors.last().and_then(ParseError::span) != span {
+        errors.push(error);
+    }
+}
+
+fn skipped_span(skipped: &[Rc<RefCell<AST>>], next_token: &Token) -> Span {
+    let spans: Vec<Span> = skipped.iter().map(|node| node.borrow().span()).collect();
+    cover(&spans).unwrap_or(next_token.span.empty())
+}
+
+/// Checks that every grammar used in a variant is declared.
+pub(crate) fn validate(
+    grammars: &IndexMap<GrammarName, GrammarVariants>,
+) -> Result<(), GrammarError> {
+    for (grammar, variants) in grammars.iter() {
         for variant in variants.iter() {
-            if let Some(node) = variant.iter().next() {
-                match node {
-                    NodeType::Token { name, .. } => {
-                        nodes.insert(name.clone());
-                    }
-                    NodeType::Grammar { name, .. } => {
-                        build_first(name, grammars, first);
-                        if let Some(children) = first.get(name) {
-                            for child in children.iter() {
-                                nodes.insert(child.clone());
-                            }
-                        }
+            for node in variant.iter() {
+                if let NodeType::Grammar { name } = node {
+                    if !grammars.contains_key(name) {
+                        return Err(GrammarError::UndefinedGrammar {
+                            name: name.clone(),
+                            rule: grammar.clone(),
+                        });
                     }
                 }
             }
         }
     }
 
-    first.insert(grammar_name.clone(), nodes);
+    Ok(())
+}
+
+pub(crate) fn build_nullable(grammars: &IndexMap<String, GrammarVariants>) -> NullableSet {
+    let mut nullable = IndexSet::new();
+
+    loop {
+        let mut changed = false;
+
+        for (grammar, variants) in grammars.iter() {
+            if nullable.contains(grammar) {
+                continue;
+            }
+
+            let derives_epsilon = variants.iter().any(|variant| {
+                variant.iter().all(|node| match node {
+                    NodeType::Token { name, .. } => name == EPSILON,
+                    NodeType::Grammar { name } => nullable.contains(name),
+                })
+            });
+
+            if derives_epsilon {
+                nullable.insert(grammar.clone());
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return nullable;
+        }
+    }
+}
+
+/// Returns the tokens each grammar may start with, and epsilon for nullable grammars.
+/// These are the FIRST_k sets for k = 1.
+pub(crate) fn build_first(grammars: &IndexMap<String, GrammarVariants>) -> FirstSet {
+    build_first_k(grammars, 1)
+        .into_iter()
+        .map(|(grammar, sequences)| (grammar, single_tokens(sequences)))
+        .collect()
 }
 
-fn build_follow(
-    grammar_name: &GrammarName,
+/// Returns the tokens that may follow each grammar. These are the FOLLOW_k sets for k = 1.
+pub(crate) fn build_follow(
     grammars: &IndexMap<String, GrammarVariants>,
     first: &FirstSet,
-    follow: &mut FollowSet,
-) {
-    if let Some(_) = follow.get(grammar_name) {
-        return;
-    }
+) -> FollowSet {
+    let first: LookaheadSet = first
+        .iter()
+        .map(|(grammar, tokens)| {
+            let sequences = tokens
+                .iter()
+                .map(|token| match token == EPSILON {
+                    true => vec![],
+                    false => vec![token.clone()],
+                })
+                .collect();
+            (grammar.clone(), sequences)
+        })
+        .collect();
 
-    let mut nodes: IndexSet<String> = IndexSet::new();
-    for (grammar, variants) in grammars.iter() {
-        for variant in variants.iter() {
-            let mut found = false;
-            let mut resolved = false;
-
-            for (index, node) in variant.iter().enumerate() {
-                match node {
-                    NodeType::Token { name, .. } => {
-                        if name == EPSILON {
-                            // Epsilon tokens cannot be included in follow sets
-                            continue;
-                        }
+    build_follow_k(grammars, &first, 1)
+        .into_iter()
+        .map(|(grammar, sequences)| (grammar, single_tokens(sequences)))
+        .collect()
+}
 
-                        let found_after_grammar = found && name != "$";
-                        let found_as_last_token_in_grammar = grammar == grammar_name
-                            && index == variant.len() - 1
-                            && variant.len() != 1;
+/// Turns sequences of at most one token into tokens, with epsilon last for the empty one.
+fn single_tokens(sequences: IndexSet<Lookahead>) -> IndexSet<TokenName> {
+    let nullable = sequences.contains(&vec![]);
+    let mut tokens: IndexSet<TokenName> = sequences.into_iter().flatten().collect();
+    if nullable {
+        tokens.insert(EPSILON.to_string());
+    }
+    tokens
+}
 
-                        if found_after_grammar || found_as_last_token_in_grammar {
-                            nodes.insert(name.clone());
-                            resolved = true;
-                            break;
-                        }
-                    }
-                    NodeType::Grammar { name, .. } => {
-                        if name == grammar_name {
-                            found = true;
-                        } else if found {
-                            resolved = true;
-
-                            if let Some(first_nodes) = first.get(name) {
-                                for node in first_nodes.iter() {
-                                    if node == EPSILON {
-                                        // Epsilon tokens cannot be included in follow sets
-                                        continue;
-                                    }
-                                    nodes.insert(node.clone());
-                                }
-                            }
+/// Returns the tokens a sequence of nodes may start with and whether it may derive epsilon.
+pub(crate) fn sequence_first(
+    nodes: &[NodeType],
+    nullable: &NullableSet,
+    first: &FirstSet,
+) -> (IndexSet<TokenName>, bool) {
+    let mut tokens = IndexSet::new();
 
-                            if has_epsilon(variants) {
-                                if let Some(follow_nodes) = follow.get(grammar) {
-                                    for node in follow_nodes.iter() {
-                                        nodes.insert(node.clone());
-                                    }
-                                }
-                            }
-                        }
-                    }
+    for node in nodes.iter() {
+        match node {
+            NodeType::Token { name, .. } => {
+                if name == EPSILON {
+                    continue;
                 }
+
+                tokens.insert(name.clone());
+                return (tokens, false);
             }
+            NodeType::Grammar { name } => {
+                if let Some(nodes) = first.get(name) {
+                    tokens.extend(nodes.iter().filter(|token| *token != EPSILON).cloned());
+                }
 
-            if found && !resolved && grammar.ne(grammar_name) {
-                if let Some(follow_nodes) = follow.get(grammar) {
-                    for node in follow_nodes.iter() {
-                        nodes.insert(node.clone());
-                    }
+                if !nullable.contains(name) {
+                    return (tokens, false);
                 }
             }
         }
     }
 
-    follow.insert(grammar_name.clone(), nodes);
+    (tokens, true)
 }
 
-fn has_epsilon(variants: &GrammarVariants) -> bool {
-    for variant in variants.iter() {
-        if is_epsilon(variant) {
-            return true;
+/// Checks whether the node is the `epsilon` token.
+pub(crate) fn is_epsilon(node: &NodeType) -> bool {
+    matches!(node, NodeType::Token { name, .. } if name == EPSILON)
+}
+
+/// Checks whether the variant only derives epsilon, without any token or grammar.
+pub(crate) fn is_epsilon_variant(variant: &[NodeType]) -> bool {
+    variant.iter().all(is_epsilon)
+}
+
+/// Returns the sequences of at most k tokens each grammar may start with.
+/// Shorter sequences are whole derivations; nullable grammars start with an empty one.
+fn build_first_k(grammars: &IndexMap<String, GrammarVariants>, k: usize) -> LookaheadSet {
+    let mut first: LookaheadSet = grammars
+        .keys()
+        .map(|grammar| (grammar.clone(), IndexSet::new()))
+        .collect();
+
+    loop {
+        let mut changed = false;
+
+        for (grammar, variants) in grammars.iter() {
+            for variant in variants.iter() {
+                let sequences = sequence_first_k(variant, &first, k);
+
+                let nodes = first.get_mut(grammar).unwrap();
+                for sequence in sequences.into_iter() {
+                    changed |= nodes.insert(sequence);
+                }
+            }
+        }
+
+        if !changed {
+            return first;
         }
     }
-    false
 }
 
-fn is_epsilon(variant: &GrammarVariant) -> bool {
-    if variant.len() == 1 {
-        let node = variant.first().unwrap();
-        if let NodeType::Token { name, .. } = node {
-            if name == EPSILON {
-                return true;
+/// Returns the sequences of k tokens that may follow each grammar, or fewer ending with EOF.
+fn build_follow_k(
+    grammars: &IndexMap<String, GrammarVariants>,
+    first: &LookaheadSet,
+    k: usize,
+) -> LookaheadSet {
+    let mut follow: LookaheadSet = grammars
+        .keys()
+        .map(|grammar| (grammar.clone(), IndexSet::new()))
+        .collect();
+
+    loop {
+        let mut changed = false;
+
+        for (grammar, variants) in grammars.iter() {
+            for variant in variants.iter() {
+                for (index, node) in variant.iter().enumerate() {
+                    let NodeType::Grammar { name } = node else {
+                        continue;
+                    };
+
+                    let rest = sequence_first_k(&variant[index + 1..], first, k);
+                    let sequences = concat_k(&rest, &follow[grammar], k);
+
+                    if let Some(nodes) = follow.get_mut(name) {
+                        for sequence in sequences.into_iter() {
+                            changed |= nodes.insert(sequence);
+                        }
+                    }
+                }
             }
         }
+
+        if !changed {
+            return follow;
+        }
     }
-    false
 }
 
+/// Returns the sequences of at most k tokens a sequence of nodes may start with.
+fn sequence_first_k(nodes: &[NodeType], first: &LookaheadSet, k: usize) -> IndexSet<Lookahead> {
+    let mut sequences = IndexSet::from([vec![]]);
+
+    for node in nodes.iter() {
+        if sequences.iter().all(|sequence| is_complete(sequence, k)) {
+            break;
+        }
+
+        sequences = match node {
+            NodeType::Token { name, .. } if name == EPSILON => continue,
+            NodeType::Token { name, .. } => {
+                concat_k(&sequences, &IndexSet::from([vec![name.clone()]]), k)
+            }
+            NodeType::Grammar { name } => match first.get(name) {
+                Some(tokens) => concat_k(&sequences, tokens, k),
+                None => IndexSet::new(),
+            },
+        };
+    }
+
+    sequences
+}
+
+/// Appends each sequence on the right to the incomplete sequences on the left, keeping k tokens.
+fn concat_k(
+    left: &IndexSet<Lookahead>,
+    right: &IndexSet<Lookahead>,
+    k: usize,
+) -> IndexSet<Lookahead> {
+    let mut sequences = IndexSet::new();
+
+    for sequence in left.iter() {
+        if is_complete(sequence, k) {
+            sequences.insert(sequence.clone());
+            continue;
+        }
+
+        for suffix in right.iter() {
+            let mut joined = sequence.clone();
+            joined.extend(suffix.iter().take(k - sequence.len()).cloned());
+            sequences.insert(joined);
+        }
+    }
+
+    sequences
+}
+
+/// Whether nothing after the sequence can change which variant it selects.
+fn is_complete(sequence: &[TokenName], k: usize) -> bool {
+    sequence.len() >= k || sequence.last().is_some_and(|token| token == EOF)
+}
+
+/// Builds the LL(k) table from the FIRST_k and FOLLOW_k sets of the grammars.
 fn build_parsing_table(
     grammars: &IndexMap<String, Vec<GrammarVariant>>,
-    first: &FirstSet,
-    follow: &FollowSet,
-) -> ParsingTable {
-    let mut table: ParsingTable = IndexMap::new();
+    k: usize,
+    policy: ConflictPolicy,
+) -> (ParsingTable, Vec<Conflict>) {
+    let first = build_first_k(grammars, k);
+    let follow = build_follow_k(grammars, &first, k);
+
+    let mut candidates: IndexMap<(GrammarName, Lookahead), Vec<Candidate>> = IndexMap::new();
 
     for (grammar, variants) in grammars.iter() {
         for variant in variants.iter() {
-            if is_epsilon(variant) {
-                if let Some(tokens) = follow.get(grammar) {
-                    insert_parsing_table_row(&mut table, &grammar, tokens, variant);
-                }
-            } else if let Some(tokens) = first.get(grammar) {
-                if let Some(NodeType::Token { name, .. }) = variant.first() {
-                    for token in tokens.iter() {
-                        if token == name {
-                            insert_parsing_table_row(
-                                &mut table,
-                                &grammar,
-                                &IndexSet::from([token.clone()]),
-                                variant,
-                            );
-                            break;
-                        }
-                    }
-                } else {
-                    insert_parsing_table_row(&mut table, &grammar, tokens, variant);
-                }
+            let (complete, partial): (IndexSet<Lookahead>, IndexSet<Lookahead>) =
+                sequence_first_k(variant, &first, k)
+                    .into_iter()
+                    .partition(|sequence| is_complete(sequence, k));
+
+            for sequence in complete.into_iter() {
+                insert_candidate(&mut candidates, grammar, sequence, variant, false);
             }
+
+            for sequence in concat_k(&partial, &follow[grammar], k).into_iter() {
+                insert_candidate(&mut candidates, grammar, sequence, variant, true);
+            }
+        }
+    }
+
+    let mut table: ParsingTable = IndexMap::new();
+    let mut conflicts = vec![];
+
+    for ((grammar, lookahead), cell) in candidates.into_iter() {
+        if cell.len() > 1 {
+            let kind = if cell.iter().any(|candidate| candidate.from_follow) {
+                ConflictKind::FirstFollow
+            } else {
+                ConflictKind::FirstFirst
+            };
+
+            conflicts.push(Conflict {
+                kind,
+                grammar: grammar.clone(),
+                lookahead: lookahead.clone(),
+                variants: cell
+                    .iter()
+                    .map(|candidate| candidate.variant.clone())
+                    .collect(),
+            });
+        }
+
+        let chosen = match policy {
+            ConflictPolicy::Reject | ConflictPolicy::LastWins => cell.last(),
+            ConflictPolicy::FirstWins => cell.first(),
+        };
+
+        if let Some(candidate) = chosen {
+            table.insert((grammar, lookahead), candidate.variant.clone());
         }
     }
 
-    table
+    (table, conflicts)
+}
+
+/// Returns the first lookahead in the range for which the table has no conflicts.
+/// A grammar that is LL(k) is also LL(k + 1), so the first one found is the smallest.
+fn minimum_lookahead(
+    grammars: &IndexMap<String, Vec<GrammarVariant>>,
+    range: RangeInclusive<usize>,
+) -> Option<usize> {
+    range.into_iter().find(|&k| {
+        let (_, conflicts) = build_parsing_table(grammars, k, ConflictPolicy::Reject);
+        conflicts.is_empty()
+    })
+}
+
+/// A variant that claims a parsing table cell before conflicts are resolved.
+struct Candidate {
+    variant: GrammarVariant,
+    from_follow: bool,
 }
 
-fn insert_parsing_table_row(
-    table: &mut ParsingTable,
-    grammar: &String,
-    tokens: &IndexSet<TokenName>,
+fn insert_candidate(
+    candidates: &mut IndexMap<(GrammarName, Lookahead), Vec<Candidate>>,
+    grammar: &str,
+    lookahead: Lookahead,
     variant: &GrammarVariant,
+    from_follow: bool,
 ) {
-    for token in tokens.iter() {
-        if token != EPSILON {
-            let mut nodes: GrammarVariant = vec![];
-            for node in variant.iter() {
-                nodes.push(node.clone());
-            }
-            table.insert((grammar.clone(), token.clone()), nodes);
-        }
+    candidates
+        .entry((grammar.to_string(), lookahead))
+        .or_default()
+        .push(Candidate {
+            variant: variant.clone(),
+            from_follow,
+        });
+}
+
+pub(crate) fn unexpected_token(
+    token: &Token,
+    expected: Vec<TokenName>,
+    rule: Option<&str>,
+) -> ParseError {
+    ParseError::UnexpectedToken {
+        token: Box::new(token.clone()),
+        expected,
+        rule: rule.map(String::from),
     }
 }
 
-fn unexpected_token(token: &Token) -> Result<Rc<RefCell<AST>>, String> {
-    Err(format!(
-        "Unexpected token {} on line {}, column {}.",
-        token.value.escape_default(), token.line, token.column
-    ))
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser(name: &str, conflicts: ConflictPolicy) -> Result<Parser, GrammarError> {
+        let tokens = read_to_string(format!("data/{name}/tokens.txt")).unwrap();
+        let grammar = read_to_string(format!("data/{name}/grammar.txt")).unwrap();
+        let options = ParserOptions {
+            conflicts,
+            ..ParserOptions::default()
+        };
+        Parser::from_str_with_options(&grammar, tokens.parse().unwrap(), options)
+    }
+
+    fn names(variant: &GrammarVariant) -> Vec<String> {
+        variant.iter().map(|node| node.to_string()).collect()
+    }
+
+    #[test]
+    fn shared_prefix_conflict() {
+        let error = parser("conflict", ConflictPolicy::Reject).err().unwrap();
+        assert!(matches!(
+            &error,
+            GrammarError::Conflicts { lookahead: 1, minimum: Some(2), conflicts } if conflicts.len() == 1
+        ));
+        assert_eq!(
+            error.to_string(),
+            "The grammar is not LL(1):\nFIRST/FIRST conflict in S on a: a | a b\nIt is LL(2)."
+        );
+
+        let first_wins = parser("conflict", ConflictPolicy::FirstWins).unwrap();
+        assert_eq!(first_wins.conflicts().len(), 1);
+        let conflict = &first_wins.conflicts()[0];
+        assert_eq!(conflict.kind, ConflictKind::FirstFirst);
+        assert_eq!(conflict.grammar, "S");
+        assert_eq!(conflict.lookahead, ["a"]);
+        let variants: Vec<Vec<String>> = conflict.variants.iter().map(names).collect();
+        assert_eq!(variants, [vec!["a"], vec!["a", "b"]]);
+        let cell = (String::from("S"), vec![String::from("a")]);
+        assert_eq!(names(&first_wins.table[&cell]), ["a"]);
+
+        let last_wins = parser("conflict", ConflictPolicy::LastWins).unwrap();
+        assert_eq!(names(&last_wins.table[&cell]), ["a", "b"]);
+    }
+
+    fn sets(expected: &[(&str, &[&str])]) -> IndexMap<GrammarName, IndexSet<TokenName>> {
+        expected
+            .iter()
+            .map(|(grammar, tokens)| {
+                let tokens = tokens.iter().map(|token| token.to_string()).collect();
+                (grammar.to_string(), tokens)
+            })
+            .collect()
+    }
+
+    fn nullable(expected: &[&str]) -> NullableSet {
+        expected.iter().map(|grammar| grammar.to_string()).collect()
+    }
+
+    #[test]
+    fn expression_grammar() {
+        let parser = parser("example", ConflictPolicy::Reject).unwrap();
+
+        assert_eq!(parser.nullable(), &nullable(&["E'", "T'"]));
+        assert_eq!(
+            parser.first,
+            sets(&[
+                (ROOT, &["(", "int"]),
+                ("E", &["(", "int"]),
+                ("E'", &["+", EPSILON]),
+                ("T", &["(", "int"]),
+                ("T'", &["*", EPSILON]),
+                ("F", &["(", "int"]),
+            ])
+        );
+        assert_eq!(
+            parser.follow,
+            sets(&[
+                (ROOT, &[]),
+                ("E", &[EOF, ")"]),
+                ("E'", &[EOF, ")"]),
+                ("T", &["+", EOF, ")"]),
+                ("T'", &["+", EOF, ")"]),
+                ("F", &["*", "+", EOF, ")"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn nullable_prefix() {
+        let parser = parser("nullable", ConflictPolicy::Reject).unwrap();
+
+        assert_eq!(parser.nullable(), &nullable(&["A", "B", "C"]));
+        assert_eq!(
+            parser.first,
+            sets(&[
+                (ROOT, &["a", "b", "c", "d"]),
+                ("S", &["a", "b", "c", "d"]),
+                ("A", &["a", EPSILON]),
+                ("B", &["b", "d", EPSILON]),
+                ("C", &["d", EPSILON]),
+            ])
+        );
+        assert_eq!(
+            parser.follow,
+            sets(&[
+                (ROOT, &[]),
+                ("S", &[EOF]),
+                ("A", &["b", "c", "d"]),
+                ("B", &["c"]),
+                ("C", &["c"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn mutual_recursion() {
+        let parser = parser("mutual", ConflictPolicy::Reject).unwrap();
+
+        assert_eq!(parser.nullable(), &nullable(&["P", "L"]));
+        assert_eq!(
+            parser.first,
+            sets(&[
+                (ROOT, &["x", EOF]),
+                ("P", &["x", EPSILON]),
+                ("L", &["x", EPSILON]),
+                ("R", &["y", "z"]),
+            ])
+        );
+        assert_eq!(
+            parser.follow,
+            sets(&[(ROOT, &[]), ("P", &[EOF]), ("L", &[EOF]), ("R", &[EOF])])
+        );
+    }
+
+    fn inline(tokens: &str, grammar: &str) -> Parser {
+        Parser::from_str(grammar, tokens.parse().unwrap()).unwrap()
+    }
+
+    fn variants(parser: &Parser, grammar: &str) -> Vec<Vec<String>> {
+        parser.grammars()[grammar].iter().map(names).collect()
+    }
+
+    #[test]
+    fn arrow_literal() {
+        let parser = inline("id = [a-z]+\n%skip whitespace = \\s+", "F -> id '->' id");
+
+        assert_eq!(variants(&parser, "F"), [["id", "->", "id"]]);
+        assert!(parser.parse("a -> b").is_ok());
+        assert!(parser.parse("a b").is_err());
+    }
+
+    #[test]
+    fn bar_literal() {
+        let parser = inline("id = [a-z]+\n%skip whitespace = \\s+", "F -> id '|' id");
+
+        assert_eq!(variants(&parser, "F"), [["id", "|", "id"]]);
+        assert!(parser.parse("a | b").is_ok());
+        assert!(parser.parse("a").is_err());
+    }
+
+    #[test]
+    fn expected_tokens() {
+        let parser = inline(
+            "id = [a-z]+\n%skip whitespace = \\s+",
+            "L -> '[' id Items ']'\nItems -> ',' id Items | epsilon",
+        );
+
+        let error = parser.parse("[a b]").unwrap_err();
+        let ParseError::UnexpectedToken {
+            token,
+            expected,
+            rule,
+        } = &error
+        else {
+            panic!("{:?} is not an unexpected token", error);
+        };
+        assert_eq!(token.value, "b");
+        assert_eq!(expected, &[",", "]"]);
+        assert_eq!(rule.as_deref(), Some("Items"));
+        assert_eq!(
+            error.to_string(),
+            "Unexpected token 'b' at 1:4, expected one of: ',', ']'"
+        );
+
+        // Matching a token lists only that token.
+        let error = parser.parse("a").unwrap_err();
+        assert!(matches!(
+            &error,
+            ParseError::UnexpectedToken { expected, .. } if expected == &["["]
+        ));
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("unreadable"))
+        }
+    }
+
+    fn grammars(parser: &Parser) -> Vec<(String, Vec<Vec<String>>)> {
+        let grammars = parser.grammars().keys();
+        grammars
+            .map(|name| (name.clone(), variants(parser, name)))
+            .collect()
+    }
+
+    #[test]
+    fn constructors() {
+        let tokenizer = || Tokenizer::from_file("data/example/tokens.txt").unwrap();
+        let path = "data/example/grammar.txt";
+        let content = read_to_string(path).unwrap();
+        let from_str = Parser::from_str(&content, tokenizer()).unwrap();
+        let from_reader = Parser::from_reader(content.as_bytes(), tokenizer()).unwrap();
+        let from_file = Parser::from_file(path, tokenizer()).unwrap();
+
+        assert_eq!(grammars(&from_str), grammars(&from_reader));
+        assert_eq!(grammars(&from_str), grammars(&from_file));
+        for parser in [&from_str, &from_reader, &from_file] {
+            assert!(parser.parse("(1 + 2) * 3").is_ok());
+        }
+
+        let err = Parser::from_reader(FailingReader, tokenizer())
+            .err()
+            .unwrap();
+        assert!(matches!(err, GrammarError::Io(e) if e.to_string() == "unreadable"));
+        assert!(matches!(
+            Parser::from_file("data/missing/grammar.txt", tokenizer()),
+            Err(GrammarError::Io(..))
+        ));
+    }
+
+    /// Writes the tree as `Grammar(children)`, with `!Grammar(skipped)` for errors.
+    fn shape(ast: &AST) -> String {
+        let nodes = |nodes: &[Rc<RefCell<AST>>]| {
+            let nodes: Vec<String> = nodes.iter().map(|node| shape(&node.borrow())).collect();
+            nodes.join(" ")
+        };
+        match ast {
+            AST::Token { value, .. } => value.clone(),
+            AST::Grammar { name, children, .. } => format!("{name}({})", nodes(children)),
+            AST::Error { name, skipped, .. } => format!("!{name}({})", nodes(skipped)),
+        }
+    }
+
+    /// The DSL example grammar, which needs two tokens of lookahead.
+    fn dsl() -> Parser {
+        let tokens = read_to_string("data/dsl/tokens.txt").unwrap();
+        let grammar = read_to_string("data/dsl/grammar.txt").unwrap();
+        let options = ParserOptions {
+            lookahead: 2,
+            ..ParserOptions::default()
+        };
+        Parser::from_str_with_options(&grammar, tokens.parse().unwrap(), options).unwrap()
+    }
+
+    #[test]
+    fn recovery() {
+        let parser = dsl();
+        let input = "model schema 1.1\n\
+                     type doc relations define a: [user,, b] define c [x]\n\
+                     type d";
+        let recovered = parser.parse_recovering(input);
+
+        let errors: Vec<String> = recovered.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "Unexpected token ',' at 2:36, expected one of: identifier",
+                "Unexpected token '[' at 2:50, expected one of: ':'",
+            ]
+        );
+        assert_eq!(
+            shape(&recovered.ast.unwrap().borrow()),
+            "Document(ModelDef(model schema Version(1 . 1)) \
+             TypeDefs(TypeDef(type doc RelationsDef(relations \
+             DefineList(DefineDecl(define a : [ Role(user !Role'()) \
+             RoleList(!RoleList(,) , Role(b Role'())) ]) \
+             DefineList(DefineDecl(define c !:() [ Role(x Role'()) RoleList() ]) DefineList())))) \
+             TypeDefs(TypeDef(type d RelationsDef()) TypeDefs())))"
+        );
+        assert!(parser.parse(input).is_err());
+    }
+
+    #[test]
+    fn recovery_terminates() {
+        let parser = dsl();
+
+        for input in [
+            "",
+            "]",
+            "model",
+            "] ] define : [",
+            "type type type",
+            "model schema 1 . . ]",
+        ] {
+            let recovered = parser.parse_recovering(input);
+            assert!(recovered.ast.is_some(), "{input}");
+            assert!(!recovered.errors.is_empty(), "{input}");
+            let end = input.len();
+            assert!(recovered
+                .errors
+                .iter()
+                .all(|e| e.span().is_some_and(|span| span.end <= end)));
+        }
+    }
+
+    fn span(ast: &Rc<RefCell<AST>>) -> (usize, usize, usize, usize) {
+        let span = ast.borrow().span();
+        (span.start, span.end, span.line, span.column)
+    }
+
+    fn child(ast: &Rc<RefCell<AST>>, index: usize) -> Rc<RefCell<AST>> {
+        match &*ast.borrow() {
+            AST::Grammar { children, .. } => children[index].clone(),
+            other => panic!("{other:?} has no children"),
+        }
+    }
+
+    #[test]
+    fn spans() {
+        let parser = inline(
+            "id = [a-z]+\nnum = [0-9]+\n%skip whitespace = \\s+",
+            "S -> id A B\nA -> num | epsilon\nB -> id id",
+        );
+
+        let ast = parser.parse("ab 12\n  ef\ngh").unwrap();
+        assert_eq!(span(&ast), (0, 13, 1, 1));
+        assert_eq!(span(&child(&ast, 0)), (0, 2, 1, 1));
+        assert_eq!(span(&child(&ast, 1)), (3, 5, 1, 4));
+        // B covers its tokens across lines, starting where the first one starts.
+        let b = child(&ast, 2);
+        assert_eq!(span(&b), (8, 13, 2, 3));
+        assert_eq!(span(&child(&b, 1)), (11, 13, 3, 1));
+
+        // Epsilon gets an empty span at the next token.
+        let ast = parser.parse("ab\ncd ef").unwrap();
+        assert_eq!(span(&child(&ast, 1)), (3, 3, 2, 1));
+        assert_eq!(span(&ast), (0, 8, 1, 1));
+    }
+
+    fn lookahead(
+        tokens: &str,
+        grammar: &str,
+        lookahead: usize,
+        conflicts: ConflictPolicy,
+    ) -> Result<Parser, GrammarError> {
+        let options = ParserOptions {
+            lookahead,
+            conflicts,
+            ..ParserOptions::default()
+        };
+        Parser::from_str_with_options(grammar, tokens.parse().unwrap(), options)
+    }
+
+    #[test]
+    fn dsl_lookahead() {
+        let tokens = read_to_string("data/dsl/tokens.txt").unwrap();
+        let grammar = read_to_string("data/dsl/grammar.txt").unwrap();
+        let content = read_to_string("data/dsl/example.txt").unwrap();
+
+        let Err(error) = lookahead(&tokens, &grammar, 1, ConflictPolicy::Reject) else {
+            panic!("the dsl grammar is LL(1)");
+        };
+        assert!(matches!(
+            error,
+            GrammarError::Conflicts {
+                lookahead: 1,
+                minimum: Some(2),
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "The grammar is not LL(1):\n\
+             FIRST/FIRST conflict in Version on number: number | number . number\n\
+             It is LL(2)."
+        );
+
+        let parser = dsl();
+        assert!(parser.conflicts().is_empty());
+        assert!(parser.parse(&content).is_ok());
+        assert_eq!(parser.minimum_lookahead(), Some(2));
+
+        let first_wins = lookahead(&tokens, &grammar, 1, ConflictPolicy::FirstWins).unwrap();
+        assert_eq!(first_wins.minimum_lookahead(), Some(2));
+        // More lookahead than needed still reports the smallest one.
+        let wider = lookahead(&tokens, &grammar, 3, ConflictPolicy::Reject).unwrap();
+        assert_eq!(wider.minimum_lookahead(), Some(2));
+        assert_eq!(
+            inline(&tokens, "Version -> number").minimum_lookahead(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn max_lookahead() {
+        let tokens = "a = a\nb = b\nc = c\n%skip whitespace = \\s+";
+        let minimum = |grammar: &str| match lookahead(tokens, grammar, 1, ConflictPolicy::Reject) {
+            Err(GrammarError::Conflicts { minimum, .. }) => minimum,
+            Err(err) => panic!("{grammar} has another error: {err}"),
+            Ok(..) => panic!("{grammar} is LL(1)"),
+        };
+
+        assert_eq!(MAX_LOOKAHEAD, 4);
+        assert_eq!(minimum("S -> a a a b | a a a c"), Some(4));
+        let beyond = "S -> a a a a b | a a a a c";
+        assert_eq!(minimum(beyond), None);
+        let Err(error) = lookahead(tokens, beyond, 1, ConflictPolicy::Reject) else {
+            unreachable!();
+        };
+        assert!(error
+            .to_string()
+            .ends_with("It is not LL(k) for any k up to 4."));
+
+        // A larger lookahead can still be asked for, and is searched up to.
+        let parser = lookahead(tokens, beyond, 5, ConflictPolicy::Reject).unwrap();
+        assert_eq!(parser.minimum_lookahead(), Some(5));
+        let first_wins = lookahead(tokens, beyond, 1, ConflictPolicy::FirstWins).unwrap();
+        assert_eq!(first_wins.minimum_lookahead(), None);
+
+        let unbounded = "S -> A b | A c\nA -> a A | a";
+        let Err(error) = lookahead(tokens, unbounded, 6, ConflictPolicy::Reject) else {
+            panic!("{unbounded} is LL(6)");
+        };
+        assert!(error
+            .to_string()
+            .ends_with("It is not LL(k) for any k up to 6."));
+    }
+}
@@ -0,0 +1,570 @@
+use std::cell::RefCell;
+use std::fs::read_to_string;
+use std::rc::Rc;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::error::{GrammarError, ParseError};
+use crate::grammar;
+use crate::parser::{
+    build_nullable, cover, splice_helpers, unexpected_token, validate, GrammarName, GrammarVariant,
+    GrammarVariants, Helper, NodeType, TokenName, AST, EOF,
+};
+use crate::precedence::{self, Operators};
+use crate::recursion::find_cycle;
+use crate::tokenizer::{end_span, PatternKind, Span, Token, Tokenizer, EPSILON};
+
+/// Syntactic predicates, which look ahead without consuming any input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Predicate {
+    /// `&X` succeeds where `X` does.
+    And,
+    /// `!X` succeeds where `X` fails.
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PegOptions {
+    /// Match token patterns against the characters where a rule expects them instead of
+    /// splitting the input with the tokenizer first, so the same text can be a keyword in
+    /// one rule and a name in another. Synthetic tokens like `INDENT` never match.
+    pub scannerless: bool,
+}
+
+/// A parsing expression grammar interpretation of the grammar file: `|` is ordered choice,
+/// so the first variant that matches wins, and `&X` and `!X` look ahead. Results of each
+/// grammar at each position are memoized, so parsing takes linear time.
+pub struct PegParser {
+    grammars: IndexMap<GrammarName, GrammarVariants>,
+    tokenizer: Tokenizer,
+    options: PegOptions,
+    predicates: IndexMap<GrammarName, Predicate>,
+    helpers: IndexMap<GrammarName, Helper>,
+    operators: Operators,
+}
+
+impl PegParser {
+    pub fn from_file(path: &str, tokenizer: Tokenizer) -> Result<PegParser, GrammarError> {
+        PegParser::from_file_with_options(path, tokenizer, PegOptions::default())
+    }
+
+    pub fn from_file_with_options(
+        path: &str,
+        tokenizer: Tokenizer,
+        options: PegOptions,
+    ) -> Result<PegParser, GrammarError> {
+        let content = match read_to_string(path) {
+            Err(e) => return Err(GrammarError::Io(e)),
+            Ok(f) => f,
+        };
+
+        PegParser::from_str_with_options(&content, tokenizer, options)
+    }
+
+    pub fn from_str(content: &str, tokenizer: Tokenizer) -> Result<PegParser, GrammarError> {
+        PegParser::from_str_with_options(content, tokenizer, PegOptions::default())
+    }
+
+    /// Reads grammars in the same format as
+    /// [Parser::from_str_with_options](crate::Parser::from_str_with_options), along with
+    /// `&X` and `!X` predicates. Left recursion never terminates with ordered choice,
+    /// so it is reported instead.
+    pub fn from_str_with_options(
+        content: &str,
+        mut tokenizer: Tokenizer,
+        options: PegOptions,
+    ) -> Result<PegParser, GrammarError> {
+        let declared = grammar::read_peg(content, &mut tokenizer)?;
+        declared.reject_annotations()?;
+        if declared.declared.is_empty() {
+            return Err(GrammarError::Empty);
+        }
+        validate(&declared.declared)?;
+
+        // Predicates don't consume input, so they are left edges that can be skipped.
+        let mut consumed = declared.declared.clone();
+        for predicate in declared.predicates.keys() {
+            let epsilon = NodeType::Token {
+                name: String::from(EPSILON),
+                pattern: Tokenizer::epsilon(),
+            };
+            consumed.insert(predicate.clone(), vec![vec![epsilon]]);
+        }
+        let nullable = build_nullable(&consumed);
+        if let Some(cycle) = find_cycle(&declared.declared, &nullable) {
+            return Err(GrammarError::LeftRecursion { cycle });
+        }
+
+        Ok(PegParser {
+            grammars: declared.declared,
+            tokenizer,
+            options,
+            predicates: declared.predicates,
+            helpers: declared
+                .helpers
+                .into_iter()
+                .map(|name| (name, Helper::Inline))
+                .collect(),
+            operators: declared.operators,
+        })
+    }
+
+    /// Grammars in declaration order; the first one is the start grammar.
+    pub fn grammars(&self) -> &IndexMap<GrammarName, GrammarVariants> {
+        &self.grammars
+    }
+
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
+    pub fn parse(&self, content: &str) -> Result<Rc<RefCell<AST>>, ParseError> {
+        let Some(start) = self.grammars.keys().next() else {
+            return Err(ParseError::NoGrammars);
+        };
+
+        let input = match self.options.scannerless {
+            true => Input::Characters(Lines::new(content)),
+            false => Input::Tokens(self.tokenizer.parse(content)?),
+        };
+        let mut packrat = Packrat {
+            parser: self,
+            content,
+            input,
+            memo: IndexMap::new(),
+            unreported: IndexSet::new(),
+            farthest: 0,
+            expected: IndexSet::new(),
+            lookahead: 0,
+        };
+
+        let end = packrat.grammar(start, 0);
+        match end.map(|end| packrat.skip(end)) {
+            Some(end) if end == packrat.length() => {}
+            end => {
+                if let Some(end) = end.filter(|end| *end > packrat.farthest) {
+                    packrat.farthest = end;
+                    packrat.expected.clear();
+                }
+                return Err(unexpected_token(
+                    &packrat.found(),
+                    packrat.expected.into_iter().collect(),
+                    None,
+                ));
+            }
+        }
+
+        let root = packrat.build(start, 0);
+        if !self.helpers.is_empty() {
+            splice_helpers(&root, &self.helpers);
+        }
+        if !self.operators.expressions.is_empty() {
+            precedence::fold(&root, &self.operators);
+        }
+        Ok(root)
+    }
+}
+
+enum Input<'a> {
+    /// Positions are indices of tokens.
+    Tokens(Vec<Token>),
+    /// Positions are byte offsets.
+    Characters(Lines<'a>),
+}
+
+#[derive(Debug, Clone)]
+struct Match<'a> {
+    end: usize,
+    children: Vec<Child<'a>>,
+}
+
+#[derive(Debug, Clone)]
+enum Child<'a> {
+    Token(Token),
+    /// A grammar matched at a position, built from the memo.
+    Grammar(&'a str, usize),
+}
+
+struct Packrat<'a> {
+    parser: &'a PegParser,
+    content: &'a str,
+    input: Input<'a>,
+    memo: IndexMap<(&'a str, usize), Option<Match<'a>>>,
+    /// Failures memoized inside predicates, whose expected tokens weren't recorded.
+    unreported: IndexSet<(&'a str, usize)>,
+    /// The farthest position where a token failed to match, and the tokens tried there.
+    farthest: usize,
+    expected: IndexSet<TokenName>,
+    /// Number of predicates being evaluated; their failures aren't reported.
+    lookahead: usize,
+}
+
+impl<'a> Packrat<'a> {
+    fn grammar(&mut self, name: &'a str, position: usize) -> Option<usize> {
+        if let Some(result) = self.memo.get(&(name, position)) {
+            // A failure inside a predicate is matched again to report what it expected.
+            if self.lookahead > 0 || !self.unreported.contains(&(name, position)) {
+                return result.as_ref().map(|matched| matched.end);
+            }
+        }
+        // Left recursion is rejected up front; failing here keeps a cycle from looping.
+        self.memo.insert((name, position), None);
+
+        let parser = self.parser;
+        let variants = &parser.grammars[name];
+        let result = match parser.predicates.get(name) {
+            Some(predicate) => {
+                self.lookahead += 1;
+                let matched = self.sequence(&variants[0], position).is_some();
+                self.lookahead -= 1;
+
+                match (predicate, matched) {
+                    (Predicate::And, true) | (Predicate::Not, false) => Some(Match {
+                        end: position,
+                        children: vec![],
+                    }),
+                    _ => None,
+                }
+            }
+            None => variants
+                .iter()
+                .find_map(|variant| self.sequence(variant, position)),
+        };
+
+        let end = result.as_ref().map(|matched| matched.end);
+        match end.is_none() && self.lookahead > 0 {
+            true => self.unreported.insert((name, position)),
+            false => self.unreported.shift_remove(&(name, position)),
+        };
+        self.memo.insert((name, position), result);
+        end
+    }
+
+    fn sequence(&mut self, variant: &'a GrammarVariant, position: usize) -> Option<Match<'a>> {
+        let mut matched = Match {
+            end: position,
+            children: vec![],
+        };
+
+        for node in variant.iter() {
+            match node {
+                NodeType::Token { name, .. } if name == EPSILON => {}
+                NodeType::Token { name, .. } => {
+                    let (token, end) = self.token(name, matched.end)?;
+                    matched.children.push(Child::Token(token));
+                    matched.end = end;
+                }
+                NodeType::Grammar { name } => {
+                    let end = self.grammar(name, matched.end)?;
+                    matched.children.push(Child::Grammar(name, matched.end));
+                    matched.end = end;
+                }
+            }
+        }
+
+        Some(matched)
+    }
+
+    fn token(&mut self, name: &str, position: usize) -> Option<(Token, usize)> {
+        let matched = match &self.input {
+            Input::Tokens(tokens) => tokens
+                .get(position)
+                .filter(|token| token.name == name)
+                .map(|token| (token.clone(), position + 1)),
+            Input::Characters(lines) => {
+                let start = self.skip(position);
+                self.parser
+                    .tokenizer
+                    .patterns
+                    .iter()
+                    .filter(|pattern| pattern.name == name && pattern.kind == PatternKind::Token)
+                    .find_map(|pattern| pattern.value.find(&self.content[start..]))
+                    .filter(|found| found.end() > 0)
+                    .map(|found| {
+                        let end = start + found.end();
+                        let token = Token {
+                            name: String::from(name),
+                            value: String::from(found.as_str()),
+                            span: lines.span(start, end),
+                        };
+                        (token, end)
+                    })
+            }
+        };
+
+        if matched.is_none() && self.lookahead == 0 {
+            let position = self.skip(position);
+            if position > self.farthest {
+                self.farthest = position;
+                self.expected.clear();
+            }
+            if position == self.farthest {
+                self.expected.insert(String::from(name));
+            }
+        }
+        matched
+    }
+
+    /// Moves past the text matched by `%skip` patterns, or whitespace if there are none.
+    fn skip(&self, position: usize) -> usize {
+        let Input::Characters(_) = self.input else {
+            return position;
+        };
+
+        let mut position = position;
+        loop {
+            let skipped = self
+                .parser
+                .tokenizer
+                .patterns
+                .iter()
+                .filter(|pattern| pattern.kind == PatternKind::Skip)
+                .filter_map(|pattern| pattern.value.find(&self.content[position..]))
+                .map(|found| found.end())
+                .max()
+                .unwrap_or(0);
+            let skipped = match self.content[position..].chars().next() {
+                Some(c) if c.is_whitespace() && self.parser.tokenizer.skips_whitespace() => {
+                    c.len_utf8()
+                }
+                _ => skipped,
+            };
+            if skipped == 0 {
+                return position;
+            }
+            position += skipped;
+        }
+    }
+
+    fn length(&self) -> usize {
+        match &self.input {
+            Input::Tokens(tokens) => tokens.len(),
+            Input::Characters(_) => self.content.len(),
+        }
+    }
+
+    /// The token at the farthest position reached, for error messages.
+    fn found(&self) -> Token {
+        let eof = Token {
+            name: EOF.to_string(),
+            value: EOF.to_string(),
+            span: end_span(self.content),
+        };
+
+        match &self.input {
+            Input::Tokens(tokens) => tokens.get(self.farthest).cloned().unwrap_or(eof),
+            Input::Characters(lines) => {
+                let rest = &self.content[self.farthest..];
+                let length = match self.parser.tokenizer.longest_match(rest) {
+                    Some((_, length)) => length,
+                    None => rest.chars().next().map_or(0, char::len_utf8),
+                };
+                if length == 0 {
+                    return eof;
+                }
+
+                let end = self.farthest + length;
+                Token {
+                    name: String::from(&rest[..length]),
+                    value: String::from(&rest[..length]),
+                    span: lines.span(self.farthest, end),
+                }
+            }
+        }
+    }
+
+    fn empty_span(&self, position: usize) -> Span {
+        match &self.input {
+            Input::Tokens(tokens) => match tokens.get(position) {
+                Some(token) => token.span.empty(),
+                None => end_span(self.content),
+            },
+            Input::Characters(lines) => {
+                let position = self.skip(position);
+                lines.span(position, position)
+            }
+        }
+    }
+
+    /// Builds the node of a grammar matched at a position. Predicates leave no nodes.
+    fn build(&self, name: &str, position: usize) -> Rc<RefCell<AST>> {
+        let children: Vec<Rc<RefCell<AST>>> = match &self.memo[&(name, position)] {
+            Some(matched) => matched
+                .children
+                .iter()
+                .filter_map(|child| match child {
+                    Child::Token(token) => Some(Rc::new(RefCell::new(token.into()))),
+                    Child::Grammar(name, _) if self.parser.predicates.contains_key(*name) => None,
+                    Child::Grammar(name, position) => Some(self.build(name, *position)),
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        let spans: Vec<Span> = children.iter().map(|child| child.borrow().span()).collect();
+        Rc::new(RefCell::new(AST::Grammar {
+            name: String::from(name),
+            span: cover(&spans).unwrap_or(self.empty_span(position)),
+            children,
+        }))
+    }
+}
+
+/// Line starts of the input, to locate byte offsets matched without the tokenizer.
+struct Lines<'a> {
+    content: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(content: &'a str) -> Lines<'a> {
+        let mut starts = vec![0];
+        starts.extend(content.match_indices('\n').map(|(index, _)| index + 1));
+        Lines { content, starts }
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        let line = self
+            .starts
+            .partition_point(|line_start| *line_start <= start);
+        let line_start = self.starts[line - 1];
+        let column = self.content[line_start..start]
+            .chars()
+            .filter(|c| *c != '\r')
+            .count();
+
+        Span {
+            start,
+            end,
+            line,
+            column: column + 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LETTERS: &str = "a = a\nb = b\nc = c\n%skip whitespace = \\s+";
+
+    fn parser(tokens: &str, grammar: &str) -> PegParser {
+        PegParser::from_str(grammar, tokens.parse().unwrap()).unwrap()
+    }
+
+    fn scannerless(tokens: &str, grammar: &str) -> PegParser {
+        let options = PegOptions { scannerless: true };
+        PegParser::from_str_with_options(grammar, tokens.parse().unwrap(), options).unwrap()
+    }
+
+    fn shape(ast: &AST) -> String {
+        match ast {
+            AST::Token { value, .. } => value.clone(),
+            AST::Grammar { name, children, .. } => {
+                let children: Vec<String> = children.iter().map(|c| shape(&c.borrow())).collect();
+                format!("{name}({})", children.join(" "))
+            }
+            AST::Error { name, .. } => format!("!{name}"),
+        }
+    }
+
+    fn parse(parser: &PegParser, content: &str) -> String {
+        let ast = parser.parse(content).unwrap();
+        let ast = ast.borrow();
+        shape(&ast)
+    }
+
+    #[test]
+    fn ordered_choice() {
+        // The first variant of A matches, so `a b` is never tried.
+        let shorter = parser(LETTERS, "S -> A\nA -> a | a b");
+        assert_eq!(parse(&shorter, "a"), "S(A(a))");
+        let Err(error) = shorter.parse("a b") else {
+            panic!("a b was accepted");
+        };
+        assert_eq!(error.to_string(), "Unexpected token 'b' at 1:3");
+
+        let longer = parser(LETTERS, "S -> A\nA -> a b | a");
+        assert_eq!(parse(&longer, "a b"), "S(A(a b))");
+        assert_eq!(parse(&longer, "a"), "S(A(a))");
+
+        // Ambiguous for other parsers, the first variant wins here.
+        let ambiguous = parser(LETTERS, "S -> A | B\nA -> a\nB -> a");
+        assert_eq!(parse(&ambiguous, "a"), "S(A(a))");
+    }
+
+    #[test]
+    fn predicates_consume_nothing() {
+        let and = parser(LETTERS, "S -> &a A\nA -> a | b");
+        assert_eq!(parse(&and, "a"), "S(A(a))");
+        assert!(and.parse("b").is_err());
+
+        let not = parser(LETTERS, "S -> !b A\nA -> a | b");
+        assert_eq!(parse(&not, "a"), "S(A(a))");
+        assert!(not.parse("b").is_err());
+
+        // Both look at the same token the variant then matches.
+        let both = parser(LETTERS, "S -> &a !b a c");
+        let ast = both.parse("a c").unwrap();
+        assert_eq!(shape(&ast.borrow()), "S(a c)");
+        let span = ast.borrow().span();
+        assert_eq!((span.start, span.end), (0, 3));
+    }
+
+    #[test]
+    fn scannerless_keywords() {
+        let tokens = "if = if\nname = [a-z]+";
+        let grammar = "S -> if name";
+
+        // The tokenizer splits `if` into the keyword both times.
+        assert!(parser(tokens, grammar).parse("if if").is_err());
+        // Without %skip patterns, whitespace is skipped.
+        let implicit = scannerless(tokens, grammar);
+        assert_eq!(parse(&implicit, "if if"), "S(if if)");
+        assert_eq!(parse(&implicit, " if\n  iffy "), "S(if iffy)");
+
+        // With %skip patterns, only they are skipped.
+        let comments = scannerless(&format!("{tokens}\n%skip comment = #[a-z]*#"), grammar);
+        assert_eq!(parse(&comments, "if#x#if"), "S(if if)");
+        assert!(comments.parse("if if").is_err());
+
+        let skipped = scannerless(&format!("{tokens}\n%skip whitespace = \\s+"), grammar);
+        assert_eq!(parse(&skipped, "if  if"), "S(if if)");
+    }
+
+    #[test]
+    fn left_recursion() {
+        let cycle = |grammar: &str| match PegParser::from_str(grammar, LETTERS.parse().unwrap()) {
+            Err(GrammarError::LeftRecursion { cycle }) => cycle,
+            Err(err) => panic!("{grammar} has another error: {err}"),
+            Ok(..) => panic!("{grammar} was accepted"),
+        };
+
+        assert_eq!(cycle("E -> E a | b"), ["E", "E"]);
+        assert_eq!(cycle("A -> B a | a\nB -> A b"), ["A", "B", "A"]);
+        // Predicates don't consume input, so they don't stop the recursion.
+        assert_eq!(cycle("A -> !b A a | a"), ["A", "A"]);
+        assert!(PegParser::from_str("A -> a A | b", LETTERS.parse().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn memoized_backtracking() {
+        // A is matched at the same position by both variants of S.
+        let shared = parser(LETTERS, "S -> A b | A c\nA -> a a | a");
+        assert_eq!(parse(&shared, "a a c"), "S(A(a a) c)");
+        assert_eq!(parse(&shared, "a a b"), "S(A(a a) b)");
+        assert_eq!(parse(&shared, "a c"), "S(A(a) c)");
+
+        // A first fails inside the predicate, then reports what it expected after it.
+        let predicate = parser(LETTERS, "S -> !A b | A c\nA -> a b");
+        assert_eq!(parse(&predicate, "b"), "S(b)");
+        assert_eq!(parse(&predicate, "a b c"), "S(A(a b) c)");
+        let Err(error) = predicate.parse("a c") else {
+            panic!("a c was accepted");
+        };
+        assert_eq!(
+            error.to_string(),
+            "Unexpected token 'c' at 1:3, expected one of: b"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::cell::RefCell;
+use std::ops::DerefMut;
+use std::rc::Rc;
+
+use indexmap::IndexMap;
+
+use crate::parser::{cover, GrammarName, TokenName, AST};
+use crate::tokenizer::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Associativity {
+    Left,
+    Right,
+}
+
+/// Operators declared with `%left`, `%right` and `%prefix`, and the expression rules using them.
+/// Operators declared on later lines bind tighter.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Operators {
+    pub(crate) binary: IndexMap<TokenName, (usize, Associativity)>,
+    pub(crate) prefix: IndexMap<TokenName, usize>,
+    /// Grammars declared as `E -> %expr Operand`, with their operand.
+    pub(crate) expressions: IndexMap<GrammarName, GrammarName>,
+}
+
+/// Rebuilds the flat children of expression nodes into nested operator nodes, so
+/// `1 + 2 * 3` becomes `E(1, +, E(2, *, 3))`. The parsing table reads an expression as a
+/// flat list of operands, prefix operators and binary operators; precedence and
+/// associativity only apply here, by precedence climbing over the parsed list.
+pub(crate) fn fold(node: &Rc<RefCell<AST>>, operators: &Operators) {
+    let mut ast = node.borrow_mut();
+    let (name, children) = match ast.deref_mut() {
+        AST::Token { .. } => return,
+        AST::Grammar { name, children, .. } => (name.clone(), children),
+        AST::Error { skipped, .. } => {
+            for child in skipped.iter() {
+                fold(child, operators);
+            }
+            return;
+        }
+    };
+
+    for child in children.iter() {
+        fold(child, operators);
+    }
+    if !operators.expressions.contains_key(&name) || children.is_empty() {
+        return;
+    }
+
+    let mut climber = Climber {
+        name: &name,
+        operators,
+        items: std::mem::take(children),
+        position: 0,
+    };
+    let folded = climber.expression(0);
+    let rest = climber.items.split_off(climber.position);
+
+    // The outermost operation reuses the expression node itself.
+    let is_operand = climber.items.iter().any(|item| Rc::ptr_eq(item, &folded));
+    *children = match folded.borrow_mut().deref_mut() {
+        AST::Grammar { children, .. } if !is_operand => std::mem::take(children),
+        _ => vec![folded.clone()],
+    };
+    children.extend(rest);
+}
+
+struct Climber<'a> {
+    name: &'a str,
+    operators: &'a Operators,
+    items: Vec<Rc<RefCell<AST>>>,
+    position: usize,
+}
+
+impl Climber<'_> {
+    fn expression(&mut self, minimum: usize) -> Rc<RefCell<AST>> {
+        let mut left = self.operand();
+
+        while let Some((precedence, associativity)) = self.binary() {
+            if precedence < minimum {
+                break;
+            }
+
+            let operator = self.next();
+            let next = match associativity {
+                Associativity::Left => precedence + 1,
+                Associativity::Right => precedence,
+            };
+            let right = self.expression(next);
+            left = self.node(vec![left, operator, right]);
+        }
+
+        left
+    }
+
+    fn operand(&mut self) -> Rc<RefCell<AST>> {
+        let precedence = self
+            .token_name(self.position)
+            .and_then(|name| self.operators.prefix.get(&name).copied());
+
+        match precedence {
+            // A prefix operator is always followed by its operand.
+            Some(precedence) if self.position + 1 < self.items.len() => {
+                let operator = self.next();
+                let operand = self.expression(precedence);
+                self.node(vec![operator, operand])
+            }
+            _ => self.next(),
+        }
+    }
+
+    fn binary(&self) -> Option<(usize, Associativity)> {
+        let name = self.token_name(self.position)?;
+        // An operator without a right operand is left as is.
+        if self.position + 1 >= self.items.len() {
+            return None;
+        }
+        self.operators.binary.get(&name).copied()
+    }
+
+    fn token_name(&self, position: usize) -> Option<TokenName> {
+        match &*self.items.get(position)?.borrow() {
+            AST::Token { name, .. } => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Rc<RefCell<AST>> {
+        self.position += 1;
+        self.items[self.position - 1].clone()
+    }
+
+    fn node(&self, children: Vec<Rc<RefCell<AST>>>) -> Rc<RefCell<AST>> {
+        let spans: Vec<Span> = children.iter().map(|child| child.borrow().span()).collect();
+        Rc::new(RefCell::new(AST::Grammar {
+            name: String::from(self.name),
+            span: cover(&spans).unwrap_or_default(),
+            children,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, AST};
+
+    const TOKENS: &str = "int = [0-9]+\n+ = \\+\n- = -\n* = \\*\n/ = /\n^ = \\^\n( = \\(\n) = \\)\n%skip whitespace = \\s+";
+    const GRAMMAR: &str =
+        "%left + -\n%left * /\n%prefix -\n%right ^\nE -> %expr F\nF -> ( E ) | int";
+
+    fn shape(ast: &AST) -> String {
+        match ast {
+            AST::Token { value, .. } => value.clone(),
+            AST::Grammar { name, children, .. } => {
+                let children: Vec<String> = children.iter().map(|c| shape(&c.borrow())).collect();
+                format!("{name}({})", children.join(" "))
+            }
+            AST::Error { name, .. } => format!("!{name}"),
+        }
+    }
+
+    fn parse(input: &str) -> String {
+        let parser = Parser::from_str(GRAMMAR, TOKENS.parse().unwrap()).unwrap();
+        let ast = parser.parse(input).unwrap();
+        let shape = shape(&ast.borrow());
+        shape
+    }
+
+    #[test]
+    fn left_associative() {
+        assert_eq!(parse("1 - 2 - 3"), "E(E(F(1) - F(2)) - F(3))");
+        assert_eq!(parse("1 * 2 / 3"), "E(E(F(1) * F(2)) / F(3))");
+    }
+
+    #[test]
+    fn right_associative() {
+        assert_eq!(parse("1 ^ 2 ^ 3"), "E(F(1) ^ E(F(2) ^ F(3)))");
+    }
+
+    #[test]
+    fn prefix_operators() {
+        // Prefix `-` binds tighter than the binary operators declared before it...
+        assert_eq!(parse("-1 + 2"), "E(E(- F(1)) + F(2))");
+        assert_eq!(parse("-1 * 2"), "E(E(- F(1)) * F(2))");
+        // ...and looser than the ones declared after it.
+        assert_eq!(parse("-2 ^ 2"), "E(- E(F(2) ^ F(2)))");
+    }
+
+    #[test]
+    fn mixed_precedence() {
+        assert_eq!(
+            parse("1 + 2 * 3 - 4 / 5 ^ 6"),
+            "E(E(F(1) + E(F(2) * F(3))) - E(F(4) / E(F(5) ^ F(6))))"
+        );
+        assert_eq!(parse("(1 + 2) * 3"), "E(F(( E(F(1) + F(2)) )) * F(3))");
+        assert_eq!(parse("1"), "E(F(1))");
+    }
+}
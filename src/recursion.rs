@@ -0,0 +1,324 @@
+use std::cell::RefCell;
+use std::ops::DerefMut;
+use std::rc::Rc;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::error::GrammarError;
+use crate::parser::{
+    build_nullable, cover, is_epsilon_variant, GrammarName, GrammarVariant, GrammarVariants,
+    Helper, NodeType, NullableSet, AST,
+};
+use crate::tokenizer::{Span, Tokenizer, EPSILON};
+
+/// Rewrites directly left-recursive grammars into the `E -> T E~tail` form:
+/// `E -> E a | b` becomes `E -> b E~tail` and `E~tail -> a E~tail | epsilon`.
+/// Returns the tails it introduced. Left recursion through other grammars or through
+/// nullable prefixes can't be rewritten and is reported with the path of the cycle.
+pub(crate) fn eliminate(
+    grammars: &mut IndexMap<GrammarName, GrammarVariants>,
+) -> Result<IndexMap<GrammarName, Helper>, GrammarError> {
+    let mut tails = IndexMap::new();
+    let names: Vec<GrammarName> = grammars.keys().cloned().collect();
+
+    for grammar in names.iter() {
+        let (recursive, base): (Vec<GrammarVariant>, Vec<GrammarVariant>) = grammars[grammar]
+            .iter()
+            .cloned()
+            .partition(|variant| starts_with(variant, grammar));
+        if recursive.is_empty() {
+            continue;
+        }
+
+        // `E -> E` and grammars without a base case never terminate.
+        if base.is_empty() || recursive.iter().any(|variant| variant.len() == 1) {
+            return Err(GrammarError::LeftRecursion {
+                cycle: vec![grammar.clone(), grammar.clone()],
+            });
+        }
+
+        let tail = format!("{grammar}~tail");
+        let tail_node = NodeType::Grammar { name: tail.clone() };
+
+        let variants = base
+            .into_iter()
+            .map(|mut variant| {
+                if is_epsilon_variant(&variant) {
+                    variant.clear();
+                }
+                variant.push(tail_node.clone());
+                variant
+            })
+            .collect();
+
+        let mut tail_variants: GrammarVariants = recursive
+            .into_iter()
+            .map(|mut variant| {
+                variant.remove(0);
+                variant.push(tail_node.clone());
+                variant
+            })
+            .collect();
+        tail_variants.push(vec![NodeType::Token {
+            name: String::from(EPSILON),
+            pattern: Tokenizer::epsilon(),
+        }]);
+
+        grammars.insert(grammar.clone(), variants);
+        grammars.insert(tail.clone(), tail_variants);
+        tails.insert(
+            tail,
+            Helper::Tail {
+                grammar: grammar.clone(),
+            },
+        );
+    }
+
+    let nullable = build_nullable(grammars);
+    if let Some(cycle) = find_cycle(grammars, &nullable) {
+        return Err(GrammarError::LeftRecursion { cycle });
+    }
+
+    Ok(tails)
+}
+
+fn starts_with(variant: &GrammarVariant, grammar: &str) -> bool {
+    matches!(variant.first(), Some(NodeType::Grammar { name }) if name == grammar)
+}
+
+/// Grammars that can be expanded without consuming a token first.
+fn left_edges<'a>(variants: &'a GrammarVariants, nullable: &NullableSet) -> Vec<&'a GrammarName> {
+    let mut edges = vec![];
+    for variant in variants.iter() {
+        for node in variant.iter() {
+            match node {
+                NodeType::Grammar { name } => {
+                    edges.push(name);
+                    if !nullable.contains(name) {
+                        break;
+                    }
+                }
+                NodeType::Token { name, .. } if name == EPSILON => {}
+                NodeType::Token { .. } => break,
+            }
+        }
+    }
+    edges
+}
+
+/// Finds a path like `A, B, A` where every grammar can start with the next one.
+pub(crate) fn find_cycle(
+    grammars: &IndexMap<GrammarName, GrammarVariants>,
+    nullable: &NullableSet,
+) -> Option<Vec<GrammarName>> {
+    let mut finished: IndexSet<&GrammarName> = IndexSet::new();
+
+    for start in grammars.keys() {
+        // Depth-first search with an explicit stack of (grammar, remaining edges).
+        let mut path: Vec<(&GrammarName, Vec<&GrammarName>)> = vec![];
+        if !finished.contains(start) {
+            path.push((start, left_edges(&grammars[start], nullable)));
+        }
+
+        while let Some((grammar, edges)) = path.last_mut() {
+            let grammar = *grammar;
+            let Some(next) = edges.pop() else {
+                finished.insert(grammar);
+                path.pop();
+                continue;
+            };
+
+            if let Some(position) = path.iter().position(|(name, _)| *name == next) {
+                let mut cycle: Vec<GrammarName> = path[position..]
+                    .iter()
+                    .map(|(name, _)| (*name).clone())
+                    .collect();
+                cycle.push(next.clone());
+                return Some(cycle);
+            }
+
+            if !finished.contains(next) && grammars.contains_key(next) {
+                path.push((next, left_edges(&grammars[next], nullable)));
+            }
+        }
+    }
+
+    None
+}
+
+/// Turns the right-nested tails of rewritten grammars back into left-nested nodes,
+/// so `1 - 2 - 3` parsed with `E -> E - T | T` reads as `(1 - 2) - 3`.
+pub(crate) fn reassociate(node: &Rc<RefCell<AST>>, helpers: &IndexMap<GrammarName, Helper>) {
+    let mut ast = node.borrow_mut();
+    let (name, children) = match ast.deref_mut() {
+        AST::Token { .. } => return,
+        AST::Grammar { name, children, .. } => (name.clone(), children),
+        AST::Error { skipped, .. } => {
+            for child in skipped.iter() {
+                reassociate(child, helpers);
+            }
+            return;
+        }
+    };
+
+    let tail = helpers.iter().find_map(|(tail, helper)| match helper {
+        Helper::Tail { grammar } if *grammar == name => Some(tail),
+        _ => None,
+    });
+    let Some(tail) = tail else {
+        for child in children.iter() {
+            reassociate(child, helpers);
+        }
+        return;
+    };
+
+    let mut next = match children.last() {
+        Some(last) if is_grammar(last, tail) => children.pop(),
+        _ => None,
+    };
+    let mut current = std::mem::take(children);
+    for child in current.iter() {
+        reassociate(child, helpers);
+    }
+
+    // Every level of the tail wraps everything parsed so far into a new node.
+    while let Some(level) = next.take() {
+        let mut level_children = match level.borrow_mut().deref_mut() {
+            AST::Grammar { children, .. } => std::mem::take(children),
+            _ => vec![],
+        };
+        if level_children.is_empty() {
+            // A tail that failed to parse is kept as is, an empty one ends the chain.
+            if matches!(&*level.borrow(), AST::Error { .. }) {
+                current.push(level);
+            }
+            break;
+        }
+
+        if level_children
+            .last()
+            .is_some_and(|last| is_grammar(last, tail))
+        {
+            next = level_children.pop();
+        }
+        for child in level_children.iter() {
+            reassociate(child, helpers);
+        }
+
+        current = [vec![grammar_node(&name, current)], level_children].concat();
+    }
+
+    *children = current;
+    if let AST::Grammar { children, span, .. } = ast.deref_mut() {
+        *span = children_span(children).unwrap_or(*span);
+    }
+}
+
+fn is_grammar(node: &Rc<RefCell<AST>>, expected: &str) -> bool {
+    matches!(&*node.borrow(), AST::Grammar { name, .. } if name == expected)
+}
+
+fn grammar_node(name: &str, children: Vec<Rc<RefCell<AST>>>) -> Rc<RefCell<AST>> {
+    let span = children_span(&children).unwrap_or_default();
+    Rc::new(RefCell::new(AST::Grammar {
+        name: String::from(name),
+        children,
+        span,
+    }))
+}
+
+fn children_span(children: &[Rc<RefCell<AST>>]) -> Option<Span> {
+    let spans: Vec<Span> = children.iter().map(|child| child.borrow().span()).collect();
+    cover(&spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn parser(grammar: &str) -> Result<Parser, GrammarError> {
+        let tokens = "int = [0-9]+\n+ = \\+\n- = -\n* = \\*\n%skip whitespace = \\s+";
+        Parser::from_str(grammar, tokens.parse().unwrap())
+    }
+
+    fn rules(parser: &Parser) -> Vec<String> {
+        let mut rules = vec![];
+        for (name, variants) in parser.grammars().iter().skip(1) {
+            for variant in variants.iter() {
+                let nodes: Vec<String> = variant.iter().map(|node| node.to_string()).collect();
+                rules.push(format!("{name} -> {}", nodes.join(" ")));
+            }
+        }
+        rules
+    }
+
+    fn shape(ast: &AST) -> String {
+        match ast {
+            AST::Token { value, .. } => value.clone(),
+            AST::Grammar { name, children, .. } => {
+                let children: Vec<String> = children.iter().map(|c| shape(&c.borrow())).collect();
+                format!("{name}({})", children.join(" "))
+            }
+            AST::Error { name, .. } => format!("!{name}"),
+        }
+    }
+
+    fn cycle(grammar: &str) -> Vec<String> {
+        match parser(grammar) {
+            Err(GrammarError::LeftRecursion { cycle }) => cycle,
+            Err(err) => panic!("{grammar} has another error: {err}"),
+            Ok(..) => panic!("{grammar} was accepted"),
+        }
+    }
+
+    #[test]
+    fn direct_left_recursion() {
+        let parser = parser("E -> E + T | E - T | T\nT -> T * int | int").unwrap();
+        assert_eq!(
+            rules(&parser),
+            [
+                "E -> T E~tail",
+                "T -> int T~tail",
+                "E~tail -> + T E~tail",
+                "E~tail -> - T E~tail",
+                "E~tail -> epsilon",
+                "T~tail -> * int T~tail",
+                "T~tail -> epsilon",
+            ]
+        );
+        let tails: Vec<(&GrammarName, &GrammarName)> = parser.tails().collect();
+        assert_eq!(tails.len(), 2);
+    }
+
+    #[test]
+    fn left_associative_trees() {
+        let parser = parser("E -> E + T | E - T | T\nT -> T * int | int").unwrap();
+
+        let ast = parser.parse("1 - 2 - 3").unwrap();
+        assert_eq!(shape(&ast.borrow()), "E(E(E(T(1)) - T(2)) - T(3))");
+        let ast = parser.parse("1 + 2 * 3 * 4").unwrap();
+        assert_eq!(shape(&ast.borrow()), "E(E(T(1)) + T(T(T(2) * 3) * 4))");
+        let ast = parser.parse("5").unwrap();
+        assert_eq!(shape(&ast.borrow()), "E(T(5))");
+    }
+
+    #[test]
+    fn indirect_left_recursion() {
+        assert_eq!(cycle("A -> B int | int\nB -> A + | -"), ["A", "B", "A"]);
+        // Hidden behind a nullable prefix.
+        assert_eq!(cycle("A -> B A int | int\nB -> epsilon | -"), ["A", "A"]);
+    }
+
+    #[test]
+    fn endless_recursion() {
+        assert_eq!(cycle("E -> E | int"), ["E", "E"]);
+        assert_eq!(cycle("E -> E +"), ["E", "E"]);
+
+        let error = parser("E -> E | int").err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Left recursion can't be eliminated: E -> E"
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::earley::EarleyParser;
+use crate::error::{GrammarError, ParseError};
+use crate::glr::{Count, GlrParser};
+use crate::lr::{Action, LrAlgorithm, LrOptions, LrParser};
+use crate::parser::{Parser, ParserOptions, AST, MAX_LOOKAHEAD};
+use crate::peg::{PegOptions, PegParser};
+use crate::tokenizer::{PatternKind, Tokenizer};
+
+/// The parsing algorithm a [report] is made with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Table-driven LL(k), with k set by [ParserOptions::lookahead].
+    #[default]
+    Ll,
+    Lr(LrAlgorithm),
+    Earley,
+    Glr,
+    Peg,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReportOptions {
+    pub backend: Backend,
+    /// Options of the LL(k) parser. The conflict policy applies to LR tables too.
+    pub parser: ParserOptions,
+    pub peg: PegOptions,
+    /// Keep parsing after errors and report all of them (LL(k) only).
+    pub recover: bool,
+    /// Write source spans of the AST nodes.
+    pub spans: bool,
+}
+
+/// Describes what the backend builds from the grammar, like the patterns, parsing tables
+/// and conflicts, followed by the tokens and the AST of the content or its parsing errors.
+pub fn report(
+    tokenizer: Tokenizer,
+    grammar: &str,
+    content: &str,
+    options: &ReportOptions,
+) -> String {
+    let mut out = String::new();
+    let written = match options.backend {
+        Backend::Ll => {
+            let parser = Parser::from_str_with_options(grammar, tokenizer, options.parser.clone());
+            section(&mut out, parser, |out, parser| {
+                ll(out, parser, content, options)
+            })
+        }
+        Backend::Lr(algorithm) => {
+            let lr_options = LrOptions {
+                algorithm,
+                conflicts: options.parser.conflicts,
+            };
+            let parser = LrParser::from_str_with_options(grammar, tokenizer, lr_options);
+            section(&mut out, parser, |out, parser| {
+                lr(out, parser, content, options)
+            })
+        }
+        Backend::Earley => {
+            let parser = EarleyParser::from_str(grammar, tokenizer);
+            section(&mut out, parser, |out, parser| {
+                tokens(out, parser.tokenizer(), content)?;
+                writeln!(out)?;
+                result(out, parser.parse(content), options.spans)
+            })
+        }
+        Backend::Glr => {
+            let parser = GlrParser::from_str(grammar, tokenizer);
+            section(&mut out, parser, |out, parser| {
+                glr(out, parser, content, options)
+            })
+        }
+        Backend::Peg => {
+            let parser = PegParser::from_str_with_options(grammar, tokenizer, options.peg);
+            section(&mut out, parser, |out, parser| {
+                tokens(out, parser.tokenizer(), content)?;
+                writeln!(out)?;
+                result(out, parser.parse(content), options.spans)
+            })
+        }
+    };
+
+    written.expect("writing to a String doesn't fail");
+    out
+}
+
+/// Writes the report of a parser, or the error that prevented building it.
+fn section<P>(
+    out: &mut String,
+    parser: Result<P, GrammarError>,
+    write: impl FnOnce(&mut String, &P) -> fmt::Result,
+) -> fmt::Result {
+    match parser {
+        Ok(parser) => write(out, &parser),
+        Err(err) => {
+            writeln!(out, "Grammar error:")?;
+            writeln!(out, "{err}")
+        }
+    }
+}
+
+/// Writes the patterns of the tokenizer, including the literals of the grammar, and the
+/// tokens of the content.
+fn tokens(out: &mut String, tokenizer: &Tokenizer, content: &str) -> fmt::Result {
+    writeln!(out, "Patterns: ")?;
+    for pattern in tokenizer.patterns.iter() {
+        match pattern.kind {
+            PatternKind::Token | PatternKind::Synthetic => {
+                writeln!(out, "{} = {}", pattern.name, pattern.value)?
+            }
+            PatternKind::Skip => writeln!(out, "%skip {} = {}", pattern.name, pattern.value)?,
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Tokens: ")?;
+    match tokenizer.parse(content) {
+        Ok(result) => {
+            for token in result.iter() {
+                writeln!(out, "{token}")?;
+            }
+        }
+        Err(err) => writeln!(out, "{err}")?,
+    }
+    Ok(())
+}
+
+fn ll(out: &mut String, parser: &Parser, content: &str, options: &ReportOptions) -> fmt::Result {
+    tokens(out, parser.tokenizer(), content)?;
+
+    writeln!(out)?;
+    writeln!(out, "Grammars: ")?;
+    for (name, variants) in parser.grammars().iter() {
+        for variant in variants.iter() {
+            let body: Vec<String> = variant.iter().map(|node| node.to_string()).collect();
+            writeln!(out, "{name} -> {}", body.join(" "))?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "NULLABLE:")?;
+    let nullable: Vec<&str> = parser.nullable().iter().map(|name| name.as_str()).collect();
+    writeln!(out, "{}", nullable.join(", "))?;
+
+    writeln!(out)?;
+    writeln!(out, "FIRST:")?;
+    for (k, v) in parser.first().iter() {
+        let values: Vec<&str> = v.iter().map(|value| value.as_str()).collect();
+        writeln!(out, "FIRST({k}) = {}", values.join(", "))?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "FOLLOW:")?;
+    for (k, v) in parser.follow().iter() {
+        let values: Vec<&str> = v.iter().map(|value| value.as_str()).collect();
+        writeln!(out, "FOLLOW({k}) = {}", values.join(", "))?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "PARSING TABLE:")?;
+    for ((grammar_name, lookahead), variant) in parser.table().iter() {
+        let nodes: Vec<String> = variant.iter().map(|node| node.to_string()).collect();
+        writeln!(
+            out,
+            "({grammar_name}, {}) = {grammar_name} -> {}",
+            lookahead.join(" "),
+            nodes.join(" ")
+        )?;
+    }
+
+    if !parser.conflicts().is_empty() {
+        writeln!(out)?;
+        writeln!(out, "CONFLICTS:")?;
+        for conflict in parser.conflicts().iter() {
+            writeln!(out, "{conflict}")?;
+        }
+        match parser.minimum_lookahead() {
+            Some(minimum) => writeln!(out, "The grammar is LL({minimum}).")?,
+            None => writeln!(
+                out,
+                "The grammar is not LL(k) for any k up to {MAX_LOOKAHEAD}."
+            )?,
+        }
+    }
+
+    writeln!(out)?;
+    if !options.recover {
+        return result(out, parser.parse(content), options.spans);
+    }
+
+    let recovered = parser.parse_recovering(content);
+    if !recovered.errors.is_empty() {
+        writeln!(out, "Parsing errors:")?;
+        for err in recovered.errors.iter() {
+            writeln!(out, "{err}")?;
+        }
+        writeln!(out)?;
+    }
+    if let Some(ast) = recovered.ast {
+        writeln!(out, "Result: ")?;
+        tree(out, &ast.borrow(), 0, options.spans)?;
+    }
+    Ok(())
+}
+
+fn lr(out: &mut String, parser: &LrParser, content: &str, options: &ReportOptions) -> fmt::Result {
+    tokens(out, parser.tokenizer(), content)?;
+
+    writeln!(out)?;
+    writeln!(out, "Productions: ")?;
+    for (index, production) in parser.productions().iter().enumerate() {
+        writeln!(out, "{index}: {production}")?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{} STATES:", parser.algorithm())?;
+    writeln!(out, "{}", parser.states())?;
+
+    writeln!(out)?;
+    writeln!(out, "ACTION:")?;
+    for ((state, token), action) in parser.action().iter() {
+        match action {
+            Action::Shift(next) => writeln!(out, "ACTION({state}, {token}) = shift {next}")?,
+            Action::Reduce(index) => writeln!(
+                out,
+                "ACTION({state}, {token}) = reduce {}",
+                parser.productions()[*index]
+            )?,
+            Action::Accept => writeln!(out, "ACTION({state}, {token}) = accept")?,
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "GOTO:")?;
+    for ((state, grammar), next) in parser.goto().iter() {
+        writeln!(out, "GOTO({state}, {grammar}) = {next}")?;
+    }
+
+    if !parser.conflicts().is_empty() {
+        writeln!(out)?;
+        writeln!(out, "CONFLICTS:")?;
+        for conflict in parser.conflicts().iter() {
+            writeln!(out, "{conflict}")?;
+        }
+    }
+
+    writeln!(out)?;
+    result(out, parser.parse(content), options.spans)
+}
+
+fn glr(
+    out: &mut String,
+    parser: &GlrParser,
+    content: &str,
+    options: &ReportOptions,
+) -> fmt::Result {
+    tokens(out, parser.tokenizer(), content)?;
+
+    writeln!(out)?;
+    writeln!(out, "LALR(1) STATES:")?;
+    writeln!(out, "{}", parser.states())?;
+
+    let mut forest = match parser.parse(content) {
+        Ok(forest) => forest,
+        Err(err) => {
+            writeln!(out)?;
+            writeln!(out, "Parsing error:")?;
+            return writeln!(out, "{err}");
+        }
+    };
+
+    writeln!(out)?;
+    writeln!(out, "DERIVATIONS:")?;
+    count(out, forest.count())?;
+    forest.disambiguate();
+    writeln!(out)?;
+    writeln!(out, "DISAMBIGUATED:")?;
+    count(out, forest.count())?;
+
+    writeln!(out)?;
+    result(out, forest.ast(), options.spans)
+}
+
+fn count(out: &mut String, count: Count) -> fmt::Result {
+    match count {
+        Count::Finite(count) => writeln!(out, "{count}"),
+        Count::Overflow => writeln!(out, "more than {}", usize::MAX),
+        Count::Infinite => writeln!(out, "infinite"),
+    }
+}
+
+fn result(
+    out: &mut String,
+    result: Result<Rc<RefCell<AST>>, ParseError>,
+    spans: bool,
+) -> fmt::Result {
+    match result {
+        Ok(ast) => {
+            writeln!(out, "Result: ")?;
+            tree(out, &ast.borrow(), 0, spans)
+        }
+        Err(ParseError::Ambiguous {
+            grammar,
+            span,
+            derivations,
+        }) => {
+            writeln!(out, "Ambiguous input:")?;
+            writeln!(
+                out,
+                "{grammar} at {}:{} has {} alternatives at its first ambiguous node",
+                span.line,
+                span.column,
+                derivations.len()
+            )?;
+            for (index, derivation) in derivations.iter().enumerate() {
+                writeln!(out)?;
+                writeln!(out, "Alternative {}:", index + 1)?;
+                tree(out, &derivation.borrow(), 0, spans)?;
+            }
+            Ok(())
+        }
+        Err(err) => {
+            writeln!(out, "Parsing error:")?;
+            writeln!(out, "{err}")
+        }
+    }
+}
+
+fn tree(out: &mut String, ast: &AST, level: usize, spans: bool) -> fmt::Result {
+    let indent = "  ".repeat(level);
+    let span = if spans {
+        let span = ast.span();
+        format!(
+            " [{}..{}] {}:{}",
+            span.start, span.end, span.line, span.column
+        )
+    } else {
+        String::new()
+    };
+
+    match &ast {
+        AST::Token { value, .. } => writeln!(out, "{indent}{value}{span}"),
+        AST::Grammar { name, children, .. } => {
+            writeln!(out, "{indent}{name}{span}")?;
+            for item in children.iter() {
+                tree(out, &item.borrow(), level + 1, spans)?;
+            }
+            Ok(())
+        }
+        AST::Error { name, skipped, .. } => {
+            writeln!(out, "{indent}<error: {name}>{span}")?;
+            for item in skipped.iter() {
+                tree(out, &item.borrow(), level + 1, spans)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokenizer() -> Tokenizer {
+        "num = [0-9]+\n%skip whitespace = \\s+".parse().unwrap()
+    }
+
+    #[test]
+    fn backends_share_the_result() {
+        let grammar = "S -> num '+' num";
+        for backend in [
+            Backend::Ll,
+            Backend::Lr(LrAlgorithm::Lalr),
+            Backend::Earley,
+            Backend::Glr,
+            Backend::Peg,
+        ] {
+            let options = ReportOptions {
+                backend,
+                ..ReportOptions::default()
+            };
+            let report = report(tokenizer(), grammar, "1 + 2", &options);
+            // The literal is registered once, by the parser.
+            assert_eq!(report.matches("\n+ = ").count(), 1, "{backend:?}");
+            assert!(
+                report.ends_with("Result: \nS\n  1\n  +\n  2\n"),
+                "{backend:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn grammar_error() {
+        let report = report(tokenizer(), "S -> T", "1", &ReportOptions::default());
+        assert_eq!(
+            report,
+            "Grammar error:\nGrammar T used in S is not defined.\n"
+        );
+    }
+
+    #[test]
+    fn recovery_with_spans() {
+        let options = ReportOptions {
+            recover: true,
+            spans: true,
+            ..ReportOptions::default()
+        };
+        let report = report(tokenizer(), "S -> num '+' num", "1 +", &options);
+        assert!(report.contains("Parsing errors:\nUnexpected token"));
+        assert!(report.ends_with("  <error: num> [3..3] 1:4\n"), "{report}");
+    }
+}
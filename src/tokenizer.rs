@@ -1,23 +1,72 @@
 use std::fmt;
 use std::fmt::Formatter;
 use std::fs::read_to_string;
+use std::io::Read;
+use std::str::FromStr;
 
 use regex::Regex;
 
-pub(crate) const EPSILON: &'static str = "epsilon";
+use crate::error::LexError;
+use crate::grammar;
+
+pub(crate) const EPSILON: &str = "epsilon";
+pub const INDENT: &str = "INDENT";
+pub const DEDENT: &str = "DEDENT";
+pub const NEWLINE: &str = "NEWLINE";
+
+const SKIP: &str = "%skip";
+const INDENTATION: &str = "%indent";
+const DEFAULT_TAB_WIDTH: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternKind {
+    /// Matched tokens are handed to the parser.
+    Token,
+    /// Matched tokens are consumed and discarded, e.g. whitespace and comments.
+    Skip,
+    /// Never matched against the input; tokens are produced by the tokenizer itself.
+    Synthetic,
+}
 
 #[derive(Clone, Debug)]
 pub struct Pattern {
     pub name: String,
     pub value: Regex,
+    pub kind: PatternKind,
 }
 
-#[derive(Debug)]
+/// Location of a fragment of the input: byte offsets plus the line and column of its start.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// An empty span located at the start of this one.
+    pub fn empty(&self) -> Span {
+        Span {
+            end: self.start,
+            ..*self
+        }
+    }
+
+    /// A span from the start of this one to the end of the other.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            end: other.end,
+            ..*self
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Token {
     pub name: String,
     pub value: String,
-    pub line: usize,
-    pub column: usize,
+    pub span: Span,
 }
 
 impl fmt::Display for Token {
@@ -26,176 +75,607 @@ impl fmt::Display for Token {
     }
 }
 
+/// Offside rule settings: indentation changes produce INDENT and DEDENT tokens
+/// and every non-blank line ends with a NEWLINE token.
+#[derive(Clone, Copy, Debug)]
+pub struct Indentation {
+    pub tab_width: usize,
+}
+
 #[derive(Debug)]
 pub struct Tokenizer {
     pub patterns: Vec<Pattern>,
+    pub indentation: Option<Indentation>,
+}
+
+impl Pattern {
+    pub fn new(name: &str, pattern: &str, kind: PatternKind) -> Result<Pattern, LexError> {
+        // Anchor at the start only: the tokenizer matches against the remaining input.
+        match Regex::new(&format!("^(?:{pattern})")) {
+            Ok(value) => Ok(Pattern {
+                name: String::from(name),
+                value,
+                kind,
+            }),
+            Err(..) => Err(LexError::InvalidPattern {
+                name: String::from(name),
+                pattern: String::from(pattern),
+            }),
+        }
+    }
 }
 
 impl Tokenizer {
-    pub fn from_file(path: &str) -> Result<Tokenizer, String> {
+    /// Creates a tokenizer from declared patterns, adding the synthetic ones it produces itself.
+    pub fn new(mut patterns: Vec<Pattern>, indentation: Option<Indentation>) -> Tokenizer {
+        if indentation.is_some() {
+            for name in [INDENT, DEDENT, NEWLINE] {
+                patterns.push(Tokenizer::synthetic(name));
+            }
+        }
+
+        patterns.push(Tokenizer::epsilon());
+        Tokenizer {
+            patterns,
+            indentation,
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Tokenizer, LexError> {
         let content = match read_to_string(path) {
-            Err(e) => return Err(format!("Unable to open the specified file: {e}")),
+            Err(e) => return Err(LexError::Io(e)),
             Ok(f) => f,
         };
 
-        let token_declaration = Regex::new(r"(?<name>.+)\s*=\s*(?<pattern>.*)").unwrap();
-        let mut patterns = vec![];
+        content.parse()
+    }
 
-        for line in content.lines().into_iter() {
-            if let Some(capture) = token_declaration.captures(line) {
-                let (_, [name, raw_pattern]) = capture.extract();
+    /// Adds patterns for the quoted words of grammar rules, like `"model"` or `'['`, unless
+    /// a token with that name is declared. They go ahead of the declared patterns, so a
+    /// keyword wins over an identifier of the same length.
+    pub fn register_literals(&mut self, grammar: &str) -> Result<(), LexError> {
+        let mut patterns = vec![];
+        for text in grammar::literals(grammar).into_iter() {
+            if self.patterns.iter().any(|pattern| pattern.name == text) {
+                continue;
+            }
 
-                let parts: Vec<String> = raw_pattern
-                    .split("|")
-                    .map(|s| format!("^{}$", s.trim()))
-                    .collect();
+            let mut pattern = regex::escape(text);
+            // Keywords don't match the start of a longer word, even without an identifier token.
+            if text.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                pattern.push_str(r"\b");
+            }
+            patterns.push(Pattern::new(text, &pattern, PatternKind::Token)?);
+        }
 
-                let name = name.trim();
-                let pattern = parts.join("|");
+        self.patterns.splice(0..0, patterns);
+        Ok(())
+    }
 
-                let regex = match Regex::new(pattern.as_str()) {
-                    Ok(r) => {
-                        r
-                    }
-                    Err(..) => {
-                        return Err(
-                            format!("Unable to parse {name} token - {pattern} is an incorrect regular expression.")
-                        )
-                    }
-                };
-                let token = Pattern {
-                    name: String::from(name),
-                    value: regex,
-                };
-                patterns.push(token);
-            } else {
-                return Err(String::from(
-                    "The file must contain token declarations with NAME = PATTERN format.",
-                ));
-            }
+    pub fn from_reader(mut reader: impl Read) -> Result<Tokenizer, LexError> {
+        let mut content = String::new();
+        if let Err(e) = reader.read_to_string(&mut content) {
+            return Err(LexError::Io(e));
         }
 
-        patterns.push(Tokenizer::epsilon());
-        Ok(Tokenizer { patterns })
+        content.parse()
     }
 
     pub fn epsilon() -> Pattern {
+        Tokenizer::synthetic(EPSILON)
+    }
+
+    fn synthetic(name: &str) -> Pattern {
         Pattern {
-            name: String::from(EPSILON),
+            name: String::from(name),
             value: Regex::new("").unwrap(),
+            kind: PatternKind::Synthetic,
         }
     }
 
-    pub fn parse(&self, s: &str) -> Result<Vec<Token>, String> {
+    /// Splits the input into tokens using the longest match among all patterns.
+    /// Patterns declared earlier win when several of them match the same length.
+    /// Tokens matched by `%skip` patterns are left out. Without any `%skip` pattern,
+    /// whitespace between tokens is skipped.
+    pub fn parse(&self, s: &str) -> Result<Vec<Token>, LexError> {
+        let tokens = self.parse_with_trivia(s)?;
+        Ok(tokens
+            .into_iter()
+            .filter(|token| !self.is_trivia(token))
+            .collect())
+    }
+
+    /// Same as [Tokenizer::parse] but keeps tokens matched by `%skip` patterns.
+    pub fn parse_with_trivia(&self, s: &str) -> Result<Vec<Token>, LexError> {
         let mut result = vec![];
+        let mut cursor = Cursor::new(s);
+        let mut indents: Vec<usize> = vec![0];
+        let mut line_start = true;
+
+        while let Some(c) = cursor.peek() {
+            let mut rest = cursor.rest();
 
-        let mut lookup: Option<Body> = None;
-        let mut unmatched = String::new();
+            if let Some(indentation) = self.indentation {
+                if line_start {
+                    let (width, length) = measure_indentation(rest, indentation.tab_width);
+                    let line = first_line(&rest[length..]);
+                    cursor.advance(length);
+
+                    if self.is_blank(line) {
+                        // Blank and comment-only lines do not affect indentation.
+                        self.push_trivia(line, &mut cursor, &mut result);
+                        if let Some(c) = cursor.peek() {
+                            cursor.advance(c.len_utf8());
+                        }
+                        continue;
+                    }
 
-        for sub in split_keep(&s) {
-            let mut matched: Option<Token> = None;
+                    let top = *indents.last().unwrap();
+                    if width > top {
+                        indents.push(width);
+                        result.push(cursor.synthetic(INDENT));
+                    } else {
+                        while width < *indents.last().unwrap() {
+                            indents.pop();
+                            result.push(cursor.synthetic(DEDENT));
+                        }
 
-            let current: Body = match &lookup {
-                Some(some) => {
-                    Body {
-                        value: some.clone().value + sub.value.as_str(),
-                        line: some.line,
-                        column: some.column,
+                        if width != *indents.last().unwrap() {
+                            return Err(LexError::InconsistentDedent {
+                                span: cursor.span(0),
+                            });
+                        }
                     }
+
+                    line_start = false;
+                    continue;
                 }
-                None => sub.clone(),
-            };
 
-            for pattern in self.patterns.iter() {
-                if pattern.value.is_match(current.value.as_str()) {
-                    matched = Some(Token {
+                if c == '\n' {
+                    result.push(cursor.synthetic(NEWLINE));
+                    cursor.advance(c.len_utf8());
+                    line_start = true;
+                    continue;
+                }
+
+                // Tokens never span several lines in the indentation mode.
+                rest = first_line(rest);
+            }
+
+            match self.longest_match(rest) {
+                Some((pattern, length)) => {
+                    result.push(Token {
                         name: pattern.name.clone(),
-                        value: current.value.clone(),
-                        line: current.line,
-                        column: current.column,
+                        value: String::from(&rest[..length]),
+                        span: cursor.span(length),
+                    });
+                    cursor.advance(length);
+                }
+                None if c.is_whitespace() && self.skips_whitespace() => {
+                    cursor.advance(c.len_utf8());
+                }
+                None => {
+                    let mut unknown: String =
+                        rest.chars().take_while(|c| !c.is_whitespace()).collect();
+                    if unknown.is_empty() {
+                        unknown.push(c);
+                    }
+                    return Err(LexError::UnknownToken {
+                        span: cursor.span(unknown.len()),
+                        value: unknown,
                     });
+                }
+            }
+        }
 
-                    break;
+        if self.indentation.is_some() {
+            if !line_start {
+                result.push(cursor.synthetic(NEWLINE));
+            }
+
+            while indents.len() > 1 {
+                indents.pop();
+                result.push(cursor.synthetic(DEDENT));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Checks whether the line consists of skipped tokens and implicitly skipped whitespace only,
+    /// like the `\r` left of a CRLF line ending.
+    fn is_blank(&self, line: &str) -> bool {
+        let mut offset = 0;
+        while let Some(c) = line[offset..].chars().next() {
+            match self.longest_match(&line[offset..]) {
+                Some((pattern, length)) if pattern.kind == PatternKind::Skip => offset += length,
+                None if c.is_whitespace() && self.skips_whitespace() => offset += c.len_utf8(),
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Consumes a blank line, keeping the tokens matched by `%skip` patterns.
+    fn push_trivia(&self, line: &str, cursor: &mut Cursor, result: &mut Vec<Token>) {
+        let mut offset = 0;
+        while let Some(c) = line[offset..].chars().next() {
+            let length = match self.longest_match(&line[offset..]) {
+                Some((pattern, length)) => {
+                    result.push(Token {
+                        name: pattern.name.clone(),
+                        value: String::from(&line[offset..offset + length]),
+                        span: cursor.span(length),
+                    });
+                    length
                 }
+                None => c.len_utf8(),
+            };
+            cursor.advance(length);
+            offset += length;
+        }
+    }
+
+    /// Whether whitespace is skipped implicitly, which is the case when no `%skip` pattern is declared.
+    pub(crate) fn skips_whitespace(&self) -> bool {
+        self.patterns
+            .iter()
+            .all(|pattern| pattern.kind != PatternKind::Skip)
+    }
+
+    /// Checks whether the token was matched by a `%skip` pattern.
+    pub fn is_trivia(&self, token: &Token) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.name == token.name && pattern.kind == PatternKind::Skip)
+    }
+
+    pub(crate) fn longest_match(&self, s: &str) -> Option<(&Pattern, usize)> {
+        let mut best: Option<(&Pattern, usize)> = None;
+
+        for pattern in self.patterns.iter() {
+            if pattern.kind == PatternKind::Synthetic {
+                continue;
             }
 
-            if let Some(token) = matched {
-                result.push(token);
-                lookup = None;
-                unmatched = String::new();
-            } else if unmatched.len() == 0 {
-                unmatched = sub.value.clone();
+            if let Some(matched) = pattern.value.find(s) {
+                let length = matched.end();
+                if length > 0 && best.is_none_or(|(_, best_length)| length > best_length) {
+                    best = Some((pattern, length));
+                }
+            }
+        }
+
+        best
+    }
+}
+
+impl FromStr for Tokenizer {
+    type Err = LexError;
+
+    /// Reads token declarations in the `NAME = PATTERN` format, one per line.
+    /// Names are a single word, so patterns like `==` may contain `=`.
+    ///
+    /// `%skip NAME = PATTERN` declares tokens that are matched and dropped, like comments.
+    /// Without any `%skip` declaration whitespace between tokens is skipped implicitly;
+    /// declaring one turns that off, so a file skipping comments usually also declares
+    /// `%skip whitespace = \s+`.
+    ///
+    /// `%indent` or `%indent TAB_WIDTH` turns on the offside rule, see [Indentation].
+    fn from_str(content: &str) -> Result<Tokenizer, LexError> {
+        let token_declaration =
+            Regex::new(r"^\s*(?<name>(?:%skip\s+)?\S+)\s*=\s*(?<pattern>.*?)\s*$").unwrap();
+        let mut patterns = vec![];
+        let mut indentation = None;
+
+        for (index, line) in content.lines().enumerate() {
+            if let Some(arguments) = line.trim().strip_prefix(INDENTATION) {
+                let tab_width = match arguments.trim() {
+                    "" => DEFAULT_TAB_WIDTH,
+                    value => match value.parse::<usize>() {
+                        Ok(width) if width > 0 => width,
+                        _ => {
+                            return Err(LexError::InvalidTabWidth {
+                                value: String::from(value),
+                            })
+                        }
+                    },
+                };
+                indentation = Some(Indentation { tab_width });
+            } else if let Some(capture) = token_declaration.captures(line) {
+                let (_, [name, raw_pattern]) = capture.extract();
+
+                let parts: Vec<&str> = raw_pattern.split("|").map(|s| s.trim()).collect();
+
+                let (kind, name) = match name.trim().split_once(char::is_whitespace) {
+                    Some((SKIP, name)) => (PatternKind::Skip, name.trim()),
+                    _ => (PatternKind::Token, name.trim()),
+                };
+
+                if raw_pattern.is_empty() {
+                    return Err(LexError::InvalidPattern {
+                        name: String::from(name),
+                        pattern: String::new(),
+                    });
+                }
+
+                patterns.push(Pattern::new(name, &parts.join("|"), kind)?);
+            } else {
+                return Err(LexError::InvalidDeclaration { line: index + 1 });
             }
         }
 
-        if unmatched.len() > 0 {
-            Err(format!("Unknown token {unmatched}."))
-        } else {
-            Ok(result)
+        Ok(Tokenizer::new(patterns, indentation))
+    }
+}
+
+/// Returns an empty span located right after the last character of the input.
+pub(crate) fn end_span(s: &str) -> Span {
+    let mut cursor = Cursor::new(s);
+    cursor.advance(s.len());
+    cursor.span(0)
+}
+
+/// Returns the width of the leading whitespace and its length in bytes.
+fn measure_indentation(s: &str, tab_width: usize) -> (usize, usize) {
+    let mut width = 0;
+    let mut length = 0;
+
+    for c in s.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += tab_width - width % tab_width,
+            _ => break,
         }
+        length += c.len_utf8();
     }
+
+    (width, length)
 }
 
-#[derive(Debug, Clone)]
-struct Body {
-    value: String,
+fn first_line(s: &str) -> &str {
+    match s.find('\n') {
+        Some(end) => &s[..end],
+        None => s,
+    }
+}
+
+/// Tracks the position of the tokenizer in the input.
+struct Cursor<'a> {
+    text: &'a str,
+    offset: usize,
     line: usize,
     column: usize,
 }
 
-fn split_keep(text: &str) -> Vec<Body> {
-    let mut result: Vec<Body> = Vec::new();
-    let mut last = 0;
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str) -> Cursor<'a> {
+        Cursor {
+            text,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
 
-    let mut line = 1;
-    let mut line_start = 0;
+    fn rest(&self) -> &'a str {
+        &self.text[self.offset..]
+    }
 
-    let mut column = 1;
-    for (index, matched) in text.match_indices(|c: char| !c.is_alphanumeric()) {
-        if last != index {
-            let last_match = &text[last..index];
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
 
-            result.push(Body {
-                value: String::from(last_match),
-                line,
-                column,
-            });
+    fn span(&self, length: usize) -> Span {
+        Span {
+            start: self.offset,
+            end: self.offset + length,
+            line: self.line,
+            column: self.column,
+        }
+    }
 
-            column += last_match.len();
+    fn synthetic(&self, name: &str) -> Token {
+        Token {
+            name: String::from(name),
+            value: String::new(),
+            span: self.span(0),
         }
+    }
 
-        match matched {
-            " " | "\t" => {
-                column += 1;
-            }
-            "\r" => {
-                column = 1;
-                line_start = index;
-            }
-            "\n" => {
-                line += 1;
-                column = 1;
-                line_start = index;
-            }
-            _ => {
-                column = index - line_start;
-                result.push(Body {
-                    value: String::from(matched),
-                    line,
-                    column,
-                });
-                column += 1;
+    fn advance(&mut self, length: usize) {
+        for c in self.text[self.offset..self.offset + length].chars() {
+            match c {
+                '\n' => {
+                    self.line += 1;
+                    self.column = 1;
+                }
+                '\r' => {}
+                _ => {
+                    self.column += 1;
+                }
             }
         }
-        last = index + matched.len();
+        self.offset += length;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_containing_equals() {
+        let tokenizer: Tokenizer = "eq = ==\nid = \\w+\n%skip whitespace = \\s+"
+            .parse()
+            .unwrap();
+        let tokens = tokenizer.parse("a == b").unwrap();
+        let names: Vec<&str> = tokens.iter().map(|token| token.name.as_str()).collect();
+        assert_eq!(names, ["id", "eq", "id"]);
+    }
+
+    #[test]
+    fn implicit_whitespace() {
+        let tokenizer: Tokenizer = "x = x\ny = y".parse().unwrap();
+        let tokens = tokenizer.parse("x y\n x").unwrap();
+        assert_eq!(tokens.len(), 3);
+
+        let tokenizer: Tokenizer = "x = x\n%skip comment = #.*".parse().unwrap();
+        let Err(LexError::UnknownToken { value, span }) = tokenizer.parse("x x") else {
+            panic!("whitespace is only skipped without %skip patterns");
+        };
+        assert_eq!(value, " ");
+        assert_eq!(span.end - span.start, 1);
+
+        let error = tokenizer.parse("x x").unwrap_err();
+        assert!(error.to_string().contains("%skip whitespace"));
+    }
+
+    fn names(tokenizer: &Tokenizer, input: &str) -> Vec<String> {
+        let tokens = tokenizer.parse_with_trivia(input).unwrap();
+        tokens.into_iter().map(|token| token.name).collect()
+    }
+
+    #[test]
+    fn indentation() {
+        let tokenizer: Tokenizer = "%indent 2\nid = [a-z]+".parse().unwrap();
+        assert_eq!(
+            names(&tokenizer, "a\n  b\n    c\n  d\ne"),
+            [
+                "id", NEWLINE, INDENT, "id", NEWLINE, INDENT, "id", NEWLINE, DEDENT, "id", NEWLINE,
+                DEDENT, "id", NEWLINE
+            ]
+        );
+        assert_eq!(
+            names(&tokenizer, "a\n  b\n    c"),
+            ["id", NEWLINE, INDENT, "id", NEWLINE, INDENT, "id", NEWLINE, DEDENT, DEDENT]
+        );
+    }
+
+    #[test]
+    fn inconsistent_dedent() {
+        let tokenizer: Tokenizer = "%indent 2\nid = [a-z]+".parse().unwrap();
+        let Err(LexError::InconsistentDedent { span }) = tokenizer.parse("a\n    b\n  c") else {
+            panic!("a dedent must return to an enclosing indentation");
+        };
+        assert_eq!((span.line, span.column), (3, 3));
+    }
+
+    #[test]
+    fn tab_width() {
+        let input = "a\n\tb\n    c\n  \td";
+        let tokenizer: Tokenizer = "%indent 4\nid = [a-z]+".parse().unwrap();
+        assert_eq!(
+            names(&tokenizer, input),
+            ["id", NEWLINE, INDENT, "id", NEWLINE, "id", NEWLINE, "id", NEWLINE, DEDENT]
+        );
+
+        let tokenizer: Tokenizer = "%indent 2\nid = [a-z]+".parse().unwrap();
+        assert_eq!(
+            names(&tokenizer, input),
+            [
+                "id", NEWLINE, INDENT, "id", NEWLINE, INDENT, "id", NEWLINE, "id", NEWLINE, DEDENT,
+                DEDENT
+            ]
+        );
+
+        let tokenizer: Tokenizer = "%indent\nid = [a-z]+".parse().unwrap();
+        assert_eq!(tokenizer.indentation.unwrap().tab_width, DEFAULT_TAB_WIDTH);
+        assert!(matches!(
+            "%indent 0".parse::<Tokenizer>(),
+            Err(LexError::InvalidTabWidth { value }) if value == "0"
+        ));
+    }
+
+    #[test]
+    fn blank_lines() {
+        let tokenizer: Tokenizer =
+            "%indent 2\nid = [a-z]+\n%skip whitespace = [ \\t]+\n%skip comment = #[^\\n]*"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            names(&tokenizer, "a\n\n      # note\n  b # tail\n   \n  c"),
+            [
+                "id",
+                NEWLINE,
+                "comment",
+                INDENT,
+                "id",
+                "whitespace",
+                "comment",
+                NEWLINE,
+                "id",
+                NEWLINE,
+                DEDENT
+            ]
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let tokenizer: Tokenizer = "%indent 2\nid = [a-z]+".parse().unwrap();
+        let expected = ["id", NEWLINE, INDENT, "id", NEWLINE, "id", NEWLINE, DEDENT];
+        assert_eq!(names(&tokenizer, "a\r\n  b\r\n\r\n  c\r\n"), expected);
+        assert_eq!(names(&tokenizer, "a\n  b\n\n  c\n"), expected);
+
+        let tokens = tokenizer.parse("a\r\n  b\r\n\r\n  c\r\n").unwrap();
+        let c = tokens.iter().find(|token| token.value == "c").unwrap();
+        assert_eq!((c.span.line, c.span.column), (4, 3));
+    }
+
+    #[test]
+    fn empty_pattern() {
+        let result = "eq =".parse::<Tokenizer>();
+        assert!(matches!(result, Err(LexError::InvalidPattern { name, .. }) if name == "eq"));
+    }
+
+    #[test]
+    fn invalid_declarations() {
+        let result = "id = [a-z]+\nnot a declaration".parse::<Tokenizer>();
+        let err = result.unwrap_err();
+        assert!(matches!(err, LexError::InvalidDeclaration { line: 2 }));
+        assert!(err.to_string().contains("line 2"));
+
+        let err = "open = (".parse::<Tokenizer>().unwrap_err();
+        assert!(matches!(&err, LexError::InvalidPattern { pattern, .. } if pattern == "("));
+        assert_eq!(
+            err.to_string(),
+            "Unable to parse open token - ( is an incorrect regular expression."
+        );
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("unreadable"))
+        }
+    }
+
+    fn patterns(tokenizer: &Tokenizer) -> Vec<(String, String, PatternKind)> {
+        let pattern = |p: &Pattern| (p.name.clone(), p.value.to_string(), p.kind);
+        tokenizer.patterns.iter().map(pattern).collect()
     }
 
-    if last < text.len() {
-        result.push(Body {
-            value: String::from(&text[last..]),
-            line,
-            column,
-        });
+    #[test]
+    fn constructors() {
+        let path = "data/example/tokens.txt";
+        let content = read_to_string(path).unwrap();
+        let from_str: Tokenizer = content.parse().unwrap();
+        let from_reader = Tokenizer::from_reader(content.as_bytes()).unwrap();
+        let from_file = Tokenizer::from_file(path).unwrap();
+
+        assert_eq!(patterns(&from_str), patterns(&from_reader));
+        assert_eq!(patterns(&from_str), patterns(&from_file));
+        let tokens = from_str.parse("(1 + 2)").unwrap();
+        let values: Vec<&str> = tokens.iter().map(|token| token.value.as_str()).collect();
+        assert_eq!(values, ["(", "1", "+", "2", ")"]);
+
+        let err = Tokenizer::from_reader(FailingReader).unwrap_err();
+        assert!(matches!(err, LexError::Io(e) if e.to_string() == "unreadable"));
+        assert!(matches!(
+            Tokenizer::from_file("data/missing/tokens.txt"),
+            Err(LexError::Io(..))
+        ));
     }
-    result
 }
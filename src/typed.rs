@@ -0,0 +1,898 @@
+use std::fmt::Write;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::parser::{GrammarName, Helper, NodeType, Parser};
+use crate::tokenizer::EPSILON;
+
+/// Conversion helpers shared by every generated module.
+const PRELUDE: &str = include_str!("typed/prelude.rs");
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "true", "try",
+    "type", "unsafe", "use", "where", "while", "yield",
+];
+
+/// Names used unqualified by the prelude and the generated code, which types must not shadow.
+const RESERVED: &[&str] = &[
+    "Box", "Cursor", "Err", "Node", "None", "Ok", "Option", "Result", "Some", "String", "TryFrom",
+    "Vec",
+];
+
+impl Parser {
+    /// Emits Rust types for the grammars: a struct for grammars with a single variant,
+    /// an enum with a variant per alternative otherwise, and `Vec` or `Option` aliases for
+    /// grammars shaped like `X -> ... X | epsilon` and `X -> ... | epsilon`.
+    /// The `*`, `+` and `?` operators and groups of grammar files become fields of their parent.
+    /// Every struct and enum implements `TryFrom<&AST>`.
+    pub fn typed_ast(&self) -> String {
+        TypedAst::new(self).generate()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Struct,
+    Enum,
+    /// `X -> items X | epsilon`
+    List,
+    /// `X -> items | epsilon`
+    Optional,
+    /// `X -> %expr Operand`
+    Expression,
+}
+
+#[derive(Clone)]
+enum Symbol {
+    Token(String),
+    Grammar(GrammarName),
+}
+
+impl Symbol {
+    fn name(&self) -> &str {
+        match self {
+            Symbol::Token(name) | Symbol::Grammar(name) => name,
+        }
+    }
+}
+
+struct TypedAst {
+    grammars: IndexMap<GrammarName, Vec<Vec<Symbol>>>,
+    shapes: IndexMap<GrammarName, Shape>,
+    /// Grammars each grammar contains without an indirection through `Vec`.
+    contains: IndexMap<GrammarName, IndexSet<GrammarName>>,
+    /// Grammars spliced into their parent, read from the parent's children.
+    helpers: IndexSet<GrammarName>,
+    /// Words of the generated type and function names of each grammar.
+    idents: IndexMap<GrammarName, Vec<String>>,
+    /// Words of the field names of helpers, describing what they contain.
+    labels: IndexMap<GrammarName, Vec<String>>,
+    /// Whether expressions may have prefix operators.
+    prefix: bool,
+}
+
+impl TypedAst {
+    fn new(parser: &Parser) -> TypedAst {
+        let mut grammars = IndexMap::new();
+        // The first grammar is the internal root.
+        for (name, variants) in parser.grammars().iter().skip(1) {
+            let variants: Vec<Vec<Symbol>> = variants
+                .iter()
+                .map(|variant| {
+                    variant
+                        .iter()
+                        .filter_map(|node| match node {
+                            NodeType::Token { name, .. } if name == EPSILON => None,
+                            NodeType::Token { name, .. } => Some(Symbol::Token(name.clone())),
+                            NodeType::Grammar { name } => Some(Symbol::Grammar(name.clone())),
+                        })
+                        .collect()
+                })
+                .collect();
+            grammars.insert(name.clone(), variants);
+        }
+
+        // The AST of left-recursive grammars is re-associated, so they read as declared:
+        // `E -> T E~tail` and `E~tail -> + T E~tail | epsilon` are back to `E -> E + T | T`.
+        for (tail, grammar) in parser.tails() {
+            let tail_variants = grammars.shift_remove(tail).unwrap_or_default();
+            let mut variants: Vec<Vec<Symbol>> = tail_variants
+                .into_iter()
+                .filter(|symbols| !symbols.is_empty())
+                .map(|mut symbols| {
+                    symbols.pop();
+                    symbols.insert(0, Symbol::Grammar(grammar.clone()));
+                    symbols
+                })
+                .collect();
+            for mut symbols in grammars[grammar].drain(..) {
+                symbols.pop();
+                variants.push(symbols);
+            }
+            grammars[grammar] = variants;
+        }
+
+        // Expressions are folded into operator nodes, so their helpers never show up in the AST.
+        let expressions = &parser.operators.expressions;
+        for (expression, operand) in expressions.iter() {
+            for symbol in grammars[expression][0].clone().iter() {
+                grammars.shift_remove(symbol.name());
+            }
+            let operand = match grammars.contains_key(operand) {
+                true => Symbol::Grammar(operand.clone()),
+                false => Symbol::Token(operand.clone()),
+            };
+            grammars[expression] = vec![vec![operand]];
+        }
+
+        let shapes: IndexMap<GrammarName, Shape> = grammars
+            .iter()
+            .map(|(name, variants)| match expressions.contains_key(name) {
+                true => (name.clone(), Shape::Expression),
+                false => (name.clone(), shape(name, variants)),
+            })
+            .collect();
+
+        let mut contains: IndexMap<GrammarName, IndexSet<GrammarName>> = IndexMap::new();
+        for (name, variants) in grammars.iter() {
+            let mut targets = IndexSet::new();
+            if shapes[name] != Shape::List {
+                for symbol in variants.iter().flatten() {
+                    if let Symbol::Grammar(target) = symbol {
+                        if shapes[target] != Shape::List {
+                            targets.insert(target.clone());
+                        }
+                    }
+                }
+            }
+            contains.insert(name.clone(), targets);
+        }
+
+        let helpers: IndexSet<GrammarName> = parser
+            .helpers
+            .iter()
+            .filter(|(name, helper)| **helper == Helper::Inline && grammars.contains_key(*name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        let mut labels: IndexMap<GrammarName, Vec<String>> = IndexMap::new();
+        let mut idents: IndexMap<GrammarName, Vec<String>> = grammars
+            .keys()
+            .filter(|name| !helpers.contains(*name))
+            .map(|name| (name.clone(), words(name)))
+            .collect();
+
+        // Helpers are declared after the helpers nested in them, so their labels are known.
+        for name in helpers.iter() {
+            let mut label = vec![];
+            for variant in grammars[name].iter().filter(|symbols| !symbols.is_empty()) {
+                if !label.is_empty() {
+                    label.push(String::from("or"));
+                }
+                // Two symbols are enough to tell helpers apart without unwieldy names.
+                for symbol in variant
+                    .iter()
+                    .filter(|symbol| symbol.name() != name)
+                    .take(2)
+                {
+                    match labels.get(symbol.name()) {
+                        Some(words) => label.extend(words.iter().cloned()),
+                        None => label.extend(words(symbol.name())),
+                    }
+                }
+            }
+            if shapes[name] == Shape::List {
+                label.push(String::from("list"));
+            }
+
+            let parent = name
+                .rsplit_once('~')
+                .map_or(name.as_str(), |(parent, _)| parent);
+            let mut ident = words(parent);
+            ident.extend(label.iter().cloned());
+            let taken = |ident: &Vec<String>| idents.values().any(|existing| existing == ident);
+            if taken(&ident) {
+                let base = ident.clone();
+                let mut count = 2;
+                while taken(&ident) {
+                    ident = base.clone();
+                    ident.push(count.to_string());
+                    count += 1;
+                }
+            }
+
+            labels.insert(name.clone(), label);
+            idents.insert(name.clone(), ident);
+        }
+
+        TypedAst {
+            grammars,
+            shapes,
+            contains,
+            helpers,
+            idents,
+            labels,
+            prefix: !parser.operators.prefix.is_empty(),
+        }
+    }
+
+    fn type_name(&self, name: &str) -> String {
+        let type_name: String = self.idents[name]
+            .iter()
+            .map(|word| capitalize(word))
+            .collect();
+        if RESERVED.contains(&type_name.as_str()) {
+            format!("{type_name}_")
+        } else {
+            type_name
+        }
+    }
+
+    fn converter(&self, name: &str) -> String {
+        format!("convert_{}", self.idents[name].join("_"))
+    }
+
+    fn reader(&self, name: &str) -> String {
+        format!("read_{}", self.idents[name].join("_"))
+    }
+
+    fn field_names(&self, symbols: &[Symbol]) -> Vec<String> {
+        let names = symbols.iter().map(|symbol| {
+            let name = match self.labels.get(symbol.name()) {
+                Some(label) => label.join("_"),
+                None => words(symbol.name()).join("_"),
+            };
+            if KEYWORDS.contains(&name.as_str()) {
+                format!("{name}_")
+            } else {
+                name
+            }
+        });
+        deduplicate(names, "_")
+    }
+
+    fn variant_names(&self, variants: &[Vec<Symbol>]) -> Vec<String> {
+        let names = variants.iter().map(|symbols| match symbols.first() {
+            Some(symbol) => match self.labels.get(symbol.name()) {
+                Some(label) => label.iter().map(|word| capitalize(word)).collect(),
+                None => words(symbol.name()).iter().map(|w| capitalize(w)).collect(),
+            },
+            None => String::from("Empty"),
+        });
+        deduplicate(names, "")
+    }
+
+    fn generate(&self) -> String {
+        let mut code = String::new();
+        writeln!(code, "// Generated by rust-parser. Do not edit.").unwrap();
+        writeln!(code).unwrap();
+        writeln!(code, "#![allow(dead_code)]").unwrap();
+        writeln!(code).unwrap();
+        code.push_str(PRELUDE);
+
+        for (name, variants) in self.grammars.iter() {
+            writeln!(code).unwrap();
+            if self.shapes[name] == Shape::Expression {
+                self.generate_expression(&mut code, name, &variants[0][0]);
+                continue;
+            }
+
+            for variant in variants.iter() {
+                let symbols: Vec<&str> = variant.iter().map(Symbol::name).collect();
+                let body = if symbols.is_empty() {
+                    String::from(EPSILON)
+                } else {
+                    symbols.join(" ")
+                };
+                writeln!(code, "/// `{name} -> {body}`").unwrap();
+            }
+
+            let helper = self.helpers.contains(name);
+            match self.shapes[name] {
+                Shape::Struct => self.generate_struct(&mut code, name, &variants[0], helper),
+                Shape::Enum => self.generate_enum(&mut code, name, variants, helper),
+                Shape::List | Shape::Optional => {
+                    self.generate_alias(&mut code, name, variants, helper)
+                }
+                Shape::Expression => unreachable!("expressions are generated separately"),
+            }
+        }
+
+        code
+    }
+
+    fn generate_struct(&self, code: &mut String, name: &str, symbols: &[Symbol], helper: bool) {
+        let type_name = self.type_name(name);
+        self.write_struct(code, name, &type_name, symbols);
+        let value = self.constructor(name, &type_name, symbols);
+
+        if helper {
+            self.write_reader(code, name);
+            writeln!(code, "    Ok({value})").unwrap();
+            writeln!(code, "}}").unwrap();
+            return;
+        }
+
+        self.write_converter(code, name);
+        let cursor = if symbols.is_empty() { "_" } else { "cursor" };
+        writeln!(code, "    sequence(ast, {name:?}, |{cursor}| Ok({value}))").unwrap();
+        writeln!(code, "}}").unwrap();
+        self.write_try_from(code, name);
+    }
+
+    fn generate_enum(&self, code: &mut String, name: &str, variants: &[Vec<Symbol>], helper: bool) {
+        let type_name = self.type_name(name);
+        let variant_names = self.variant_names(variants);
+
+        writeln!(code, "#[derive(Clone, Debug)]").unwrap();
+        writeln!(code, "pub enum {type_name} {{").unwrap();
+        for (variant_name, symbols) in variant_names.iter().zip(variants.iter()) {
+            if symbols.is_empty() {
+                writeln!(code, "    {variant_name},").unwrap();
+                continue;
+            }
+
+            writeln!(code, "    {variant_name} {{").unwrap();
+            for (field, symbol) in self.field_names(symbols).iter().zip(symbols.iter()) {
+                writeln!(code, "        {field}: {},", self.field_type(name, symbol)).unwrap();
+            }
+            writeln!(code, "    }},").unwrap();
+        }
+        writeln!(code, "}}").unwrap();
+
+        if helper {
+            // Variants are told apart by the next child, the empty one is the fallback.
+            self.write_reader(code, name);
+            let mut fallback = None;
+            for (variant_name, symbols) in variant_names.iter().zip(variants.iter()) {
+                let path = format!("{type_name}::{variant_name}");
+                let first = self.first(symbols);
+                if first.is_empty() {
+                    fallback.get_or_insert(self.constructor(name, &path, symbols));
+                    continue;
+                }
+
+                writeln!(code, "    if cursor.is_next(&{first:?}) {{").unwrap();
+                let value = self.constructor(name, &path, symbols);
+                writeln!(code, "        return Ok({value});").unwrap();
+                writeln!(code, "    }}").unwrap();
+            }
+            match fallback {
+                Some(value) => writeln!(code, "    Ok({value})").unwrap(),
+                None => writeln!(code, "    Err(cursor.unexpected({name:?}))").unwrap(),
+            }
+            writeln!(code, "}}").unwrap();
+            return;
+        }
+
+        self.write_converter(code, name);
+        writeln!(code, "    let children = grammar(ast, {name:?})?;").unwrap();
+        writeln!(code, "    let mut cursor = Cursor::new(ast, children);").unwrap();
+        writeln!(code, "    let cursor = &mut cursor;").unwrap();
+        for (index, (variant_name, symbols)) in
+            variant_names.iter().zip(variants.iter()).enumerate()
+        {
+            let condition = if symbols.iter().any(|symbol| self.is_helper(symbol)) {
+                let mut condition = format!("cursor.is_next(&{:?})", self.first(symbols));
+                if self.is_nullable(symbols) {
+                    condition.push_str(" || children.is_empty()");
+                }
+                condition
+            } else {
+                format!("matches(children, &{:?})", names(symbols))
+            };
+
+            let keyword = if index == 0 {
+                "let value = if"
+            } else {
+                "} else if"
+            };
+            writeln!(code, "    {keyword} {condition} {{").unwrap();
+            let path = format!("{type_name}::{variant_name}");
+            writeln!(code, "        {}", self.constructor(name, &path, symbols)).unwrap();
+        }
+        writeln!(code, "    }} else {{").unwrap();
+        writeln!(code, "        return Err(unexpected(ast, {name:?}));").unwrap();
+        writeln!(code, "    }};").unwrap();
+        writeln!(code, "    cursor.finish()?;").unwrap();
+        writeln!(code, "    Ok(value)").unwrap();
+        writeln!(code, "}}").unwrap();
+        self.write_try_from(code, name);
+    }
+
+    /// Expressions are trees of operator nodes with operands at the leaves.
+    fn generate_expression(&self, code: &mut String, name: &str, operand: &Symbol) {
+        let type_name = self.type_name(name);
+        let operand_name = operand.name();
+
+        writeln!(code, "/// `{name} -> %expr {operand_name}`").unwrap();
+        writeln!(code, "#[derive(Clone, Debug)]").unwrap();
+        writeln!(code, "pub enum {type_name} {{").unwrap();
+        writeln!(code, "    Binary {{").unwrap();
+        writeln!(code, "        left: Box<{type_name}>,").unwrap();
+        writeln!(code, "        operator: ::rust_parser::Token,").unwrap();
+        writeln!(code, "        right: Box<{type_name}>,").unwrap();
+        writeln!(code, "    }},").unwrap();
+        if self.prefix {
+            writeln!(code, "    Prefix {{").unwrap();
+            writeln!(code, "        operator: ::rust_parser::Token,").unwrap();
+            writeln!(code, "        operand: Box<{type_name}>,").unwrap();
+            writeln!(code, "    }},").unwrap();
+        }
+        let variant_name = &self.variant_names(&[vec![operand.clone()]])[0];
+        let field = &self.field_names(std::slice::from_ref(operand))[0];
+        writeln!(code, "    {variant_name} {{").unwrap();
+        writeln!(code, "        {field}: {},", self.field_type(name, operand)).unwrap();
+        writeln!(code, "    }},").unwrap();
+        writeln!(code, "}}").unwrap();
+
+        // Operands are children of operator nodes as they are, without an expression node.
+        let value = match operand {
+            Symbol::Token(_) => String::from("token(ast)?"),
+            Symbol::Grammar(_) => format!("{}(ast)?", self.converter(operand_name)),
+        };
+        let value = match self.field_type(name, operand).starts_with("Box") {
+            true => format!("Box::new({value})"),
+            false => value,
+        };
+        let child = |expected: &str| format!("&cursor.next({expected:?})?.borrow()");
+
+        self.write_converter(code, name);
+        writeln!(code, "    if name(ast) == {operand_name:?} {{").unwrap();
+        writeln!(
+            code,
+            "        return Ok({type_name}::{variant_name} {{ {field}: {value} }});"
+        )
+        .unwrap();
+        writeln!(code, "    }}").unwrap();
+        writeln!(code, "    let children = grammar(ast, {name:?})?;").unwrap();
+        writeln!(code, "    let mut cursor = Cursor::new(ast, children);").unwrap();
+        writeln!(code, "    let cursor = &mut cursor;").unwrap();
+        writeln!(code, "    let value = match children.len() {{").unwrap();
+        writeln!(
+            code,
+            "        3 => {type_name}::Binary {{ left: Box::new({}({})?), operator: token({})?, right: Box::new({}({})?) }},",
+            self.converter(name),
+            child(name),
+            child("an operator"),
+            self.converter(name),
+            child(name),
+        )
+        .unwrap();
+        if self.prefix {
+            writeln!(
+                code,
+                "        2 => {type_name}::Prefix {{ operator: token({})?, operand: Box::new({}({})?) }},",
+                child("an operator"),
+                self.converter(name),
+                child(name),
+            )
+            .unwrap();
+        }
+        let path = format!("{type_name}::{variant_name}");
+        writeln!(
+            code,
+            "        _ => {},",
+            self.constructor(name, &path, std::slice::from_ref(operand))
+        )
+        .unwrap();
+        writeln!(code, "    }};").unwrap();
+        writeln!(code, "    cursor.finish()?;").unwrap();
+        writeln!(code, "    Ok(value)").unwrap();
+        writeln!(code, "}}").unwrap();
+        self.write_try_from(code, name);
+    }
+
+    fn generate_alias(
+        &self,
+        code: &mut String,
+        name: &str,
+        variants: &[Vec<Symbol>],
+        helper: bool,
+    ) {
+        let type_name = self.type_name(name);
+        let shape = self.shapes[name];
+        let symbols = variants.iter().find(|symbols| !symbols.is_empty()).unwrap();
+        let items = match shape {
+            Shape::List => &symbols[..symbols.len() - 1],
+            _ => &symbols[..],
+        };
+
+        let (item_type, item) = if items.len() == 1 {
+            (
+                self.field_type(name, &items[0]),
+                self.field_value(name, &items[0]),
+            )
+        } else {
+            let item_type = format!("{type_name}Item");
+            let item = self.constructor(name, &item_type, items);
+            (item_type, item)
+        };
+
+        writeln!(
+            code,
+            "pub type {type_name} = {}<{item_type}>;",
+            wrapper(shape)
+        )
+        .unwrap();
+        if items.len() > 1 {
+            writeln!(code).unwrap();
+            self.write_struct(code, name, &item_type, items);
+        }
+
+        if helper {
+            self.write_reader(code, name);
+            let first = self.first(items);
+            match shape {
+                Shape::List => {
+                    writeln!(code, "    let mut items = vec![];").unwrap();
+                    writeln!(code, "    while cursor.is_next(&{first:?}) {{").unwrap();
+                    writeln!(code, "        items.push({item});").unwrap();
+                    writeln!(code, "    }}").unwrap();
+                    writeln!(code, "    Ok(items)").unwrap();
+                }
+                _ => {
+                    writeln!(code, "    if cursor.is_next(&{first:?}) {{").unwrap();
+                    writeln!(code, "        return Ok(Some({item}));").unwrap();
+                    writeln!(code, "    }}").unwrap();
+                    writeln!(code, "    Ok(None)").unwrap();
+                }
+            }
+            writeln!(code, "}}").unwrap();
+            return;
+        }
+
+        // Items are wrapped in `Ok` unless they are a single fallible conversion already.
+        let item = match item.strip_suffix('?') {
+            Some(result) => String::from(result),
+            None => format!("Ok({item})"),
+        };
+        self.write_converter(code, name);
+        let function = match shape {
+            Shape::List => "list",
+            _ => "optional",
+        };
+        writeln!(code, "    {function}(ast, {name:?}, |cursor| {item})").unwrap();
+        writeln!(code, "}}").unwrap();
+    }
+
+    fn write_struct(&self, code: &mut String, grammar: &str, type_name: &str, symbols: &[Symbol]) {
+        writeln!(code, "#[derive(Clone, Debug)]").unwrap();
+        if symbols.is_empty() {
+            writeln!(code, "pub struct {type_name};").unwrap();
+            return;
+        }
+
+        writeln!(code, "pub struct {type_name} {{").unwrap();
+        for (field, symbol) in self.field_names(symbols).iter().zip(symbols.iter()) {
+            writeln!(
+                code,
+                "    pub {field}: {},",
+                self.field_type(grammar, symbol)
+            )
+            .unwrap();
+        }
+        writeln!(code, "}}").unwrap();
+    }
+
+    /// Builds a struct or an enum variant by reading the symbols from `cursor`.
+    fn constructor(&self, grammar: &str, path: &str, symbols: &[Symbol]) -> String {
+        if symbols.is_empty() {
+            return String::from(path);
+        }
+
+        let fields: Vec<String> = self
+            .field_names(symbols)
+            .iter()
+            .zip(symbols.iter())
+            .map(|(field, symbol)| format!("{field}: {}", self.field_value(grammar, symbol)))
+            .collect();
+        format!("{path} {{ {} }}", fields.join(", "))
+    }
+
+    fn field_type(&self, grammar: &str, symbol: &Symbol) -> String {
+        match symbol {
+            Symbol::Token(_) => String::from("::rust_parser::Token"),
+            Symbol::Grammar(target) if self.is_recursive(grammar, target) => {
+                format!("Box<{}>", self.type_name(target))
+            }
+            Symbol::Grammar(target) => self.type_name(target),
+        }
+    }
+
+    fn field_value(&self, grammar: &str, symbol: &Symbol) -> String {
+        let value = match symbol {
+            Symbol::Token(name) => format!("token(&cursor.expect({name:?})?.borrow())?"),
+            Symbol::Grammar(name) if self.helpers.contains(name) => {
+                format!("{}(cursor)?", self.reader(name))
+            }
+            Symbol::Grammar(name) => {
+                format!(
+                    "{}(&cursor.expect({name:?})?.borrow())?",
+                    self.converter(name)
+                )
+            }
+        };
+
+        match symbol {
+            Symbol::Grammar(target) if self.is_recursive(grammar, target) => {
+                format!("Box::new({value})")
+            }
+            _ => value,
+        }
+    }
+
+    fn is_helper(&self, symbol: &Symbol) -> bool {
+        matches!(symbol, Symbol::Grammar(name) if self.helpers.contains(name))
+    }
+
+    /// Names of the nodes that can come first among the children for these symbols.
+    /// Helpers are spliced into their parent, so they contribute their own first nodes.
+    fn first(&self, symbols: &[Symbol]) -> Vec<String> {
+        let mut first = IndexSet::new();
+        self.collect_first(symbols, &mut first, &mut IndexSet::new());
+        first.into_iter().collect()
+    }
+
+    fn collect_first(
+        &self,
+        symbols: &[Symbol],
+        first: &mut IndexSet<String>,
+        visited: &mut IndexSet<GrammarName>,
+    ) {
+        for symbol in symbols.iter() {
+            let Symbol::Grammar(name) = symbol else {
+                first.insert(String::from(symbol.name()));
+                return;
+            };
+            if !self.helpers.contains(name) {
+                first.insert(name.clone());
+                return;
+            }
+
+            if visited.insert(name.clone()) {
+                for variant in self.grammars[name].iter() {
+                    self.collect_first(variant, first, visited);
+                }
+            }
+            if !self.is_nullable(std::slice::from_ref(symbol)) {
+                return;
+            }
+        }
+    }
+
+    /// Checks whether the symbols can produce no children at all.
+    fn is_nullable(&self, symbols: &[Symbol]) -> bool {
+        self.is_nullable_with(symbols, &mut IndexSet::new())
+    }
+
+    fn is_nullable_with(&self, symbols: &[Symbol], visiting: &mut IndexSet<GrammarName>) -> bool {
+        symbols.iter().all(|symbol| match symbol {
+            Symbol::Grammar(name)
+                if self.helpers.contains(name) && visiting.insert(name.clone()) =>
+            {
+                let nullable = self.grammars[name]
+                    .iter()
+                    .any(|variant| self.is_nullable_with(variant, visiting));
+                visiting.swap_remove(name);
+                nullable
+            }
+            _ => false,
+        })
+    }
+
+    /// Checks whether storing the target inline in the grammar would make its type infinite.
+    fn is_recursive(&self, grammar: &str, target: &str) -> bool {
+        if self.shapes[target] == Shape::List || self.shapes[grammar] == Shape::List {
+            return false;
+        }
+
+        let mut visited = IndexSet::new();
+        let mut pending = vec![target];
+        while let Some(name) = pending.pop() {
+            if name == grammar {
+                return true;
+            }
+            if visited.insert(name) {
+                pending.extend(self.contains[name].iter().map(|name| name.as_str()));
+            }
+        }
+        false
+    }
+}
+
+fn shape(name: &str, variants: &[Vec<Symbol>]) -> Shape {
+    if variants.len() == 1 {
+        return Shape::Struct;
+    }
+
+    if variants.len() == 2 {
+        let empty = variants.iter().filter(|symbols| symbols.is_empty()).count();
+        let symbols = variants.iter().find(|symbols| !symbols.is_empty());
+
+        if let (1, Some(symbols)) = (empty, symbols) {
+            let recursive = |symbol: &Symbol| matches!(symbol, Symbol::Grammar(n) if n == name);
+            let (last, items) = symbols.split_last().unwrap();
+
+            if !items.is_empty() && recursive(last) && !items.iter().any(recursive) {
+                return Shape::List;
+            }
+            if !symbols.iter().any(recursive) {
+                return Shape::Optional;
+            }
+        }
+    }
+
+    Shape::Enum
+}
+
+fn wrapper(shape: Shape) -> &'static str {
+    match shape {
+        Shape::List => "Vec",
+        _ => "Option",
+    }
+}
+
+impl TypedAst {
+    fn write_converter(&self, code: &mut String, name: &str) {
+        writeln!(
+            code,
+            "\nfn {}(ast: &::rust_parser::AST) -> Result<{}, ::rust_parser::AstError> {{",
+            self.converter(name),
+            self.type_name(name)
+        )
+        .unwrap();
+    }
+
+    fn write_reader(&self, code: &mut String, name: &str) {
+        writeln!(
+            code,
+            "\nfn {}(cursor: &mut Cursor) -> Result<{}, ::rust_parser::AstError> {{",
+            self.reader(name),
+            self.type_name(name)
+        )
+        .unwrap();
+    }
+
+    fn write_try_from(&self, code: &mut String, name: &str) {
+        let type_name = self.type_name(name);
+        writeln!(
+            code,
+            "\nimpl TryFrom<&::rust_parser::AST> for {type_name} {{"
+        )
+        .unwrap();
+        writeln!(code, "    type Error = ::rust_parser::AstError;").unwrap();
+        writeln!(code).unwrap();
+        writeln!(
+            code,
+            "    fn try_from(ast: &::rust_parser::AST) -> Result<{type_name}, ::rust_parser::AstError> {{"
+        )
+        .unwrap();
+        writeln!(code, "        {}(ast)", self.converter(name)).unwrap();
+        writeln!(code, "    }}").unwrap();
+        writeln!(code, "}}").unwrap();
+    }
+}
+
+fn names(symbols: &[Symbol]) -> Vec<&str> {
+    symbols.iter().map(Symbol::name).collect()
+}
+
+/// Numbers repeated names, e.g. `number, dot, number_2`.
+fn deduplicate(names: impl Iterator<Item = String>, separator: &str) -> Vec<String> {
+    let mut counts: IndexMap<String, usize> = IndexMap::new();
+    names
+        .map(|name| {
+            let count = counts.entry(name.clone()).or_default();
+            *count += 1;
+            match *count {
+                1 => name,
+                count => format!("{name}{separator}{count}"),
+            }
+        })
+        .collect()
+}
+
+/// Splits a grammar or token name into lowercase words, spelling out punctuation.
+fn words(name: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut current = String::new();
+    let mut previous_lowercase = false;
+
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            if c.is_uppercase() && previous_lowercase && !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            previous_lowercase = c.is_lowercase() || c.is_numeric();
+            current.extend(c.to_lowercase());
+            continue;
+        }
+
+        if !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        previous_lowercase = false;
+        if let Some(word) = punctuation(c) {
+            words.push(String::from(word));
+        }
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+    if words.is_empty() {
+        words.push(String::from("symbol"));
+    }
+    if words[0].starts_with(|c: char| c.is_numeric()) {
+        words.insert(0, String::from("n"));
+    }
+    words
+}
+
+fn punctuation(c: char) -> Option<&'static str> {
+    let word = match c {
+        '\'' => "prime",
+        '+' => "plus",
+        '-' => "minus",
+        '*' => "star",
+        '/' => "slash",
+        '\\' => "backslash",
+        '%' => "percent",
+        '^' => "caret",
+        '(' => "lparen",
+        ')' => "rparen",
+        '[' => "lbracket",
+        ']' => "rbracket",
+        '{' => "lbrace",
+        '}' => "rbrace",
+        '<' => "lt",
+        '>' => "gt",
+        '=' => "eq",
+        '!' => "bang",
+        '?' => "question",
+        '.' => "dot",
+        ',' => "comma",
+        ':' => "colon",
+        ';' => "semicolon",
+        '#' => "hash",
+        '@' => "at",
+        '&' => "amp",
+        '|' => "pipe",
+        '~' => "tilde",
+        '$' => "dollar",
+        '"' => "quote",
+        _ => return None,
+    };
+    Some(word)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, Tokenizer};
+
+    #[test]
+    fn reserved_type_names() {
+        let tokenizer: Tokenizer = "str = \"[^\"]*\"\nnum = \\d+\n, = ,".parse().unwrap();
+        let parser = Parser::from_str(
+            "Value -> String | Node | Cursor\nString -> str\nNode -> num\nCursor -> , num",
+            tokenizer,
+        )
+        .unwrap();
+
+        let code = parser.typed_ast();
+        assert!(code.contains("pub struct String_ {"));
+        assert!(code.contains("pub struct Node_ {"));
+        assert!(code.contains("pub struct Cursor_ {"));
+        assert!(code.contains("String::from(expected)"));
+    }
+}
@@ -0,0 +1,178 @@
+type Node = std::rc::Rc<std::cell::RefCell<::rust_parser::AST>>;
+
+fn name(ast: &::rust_parser::AST) -> &str {
+    match ast {
+        ::rust_parser::AST::Token { name, .. }
+        | ::rust_parser::AST::Grammar { name, .. }
+        | ::rust_parser::AST::Error { name, .. } => name,
+    }
+}
+
+fn unexpected(ast: &::rust_parser::AST, expected: &str) -> ::rust_parser::AstError {
+    let found = match ast {
+        ::rust_parser::AST::Error { name, .. } => format!("an error in {name}"),
+        _ => String::from(name(ast)),
+    };
+
+    ::rust_parser::AstError::UnexpectedNode {
+        expected: String::from(expected),
+        found,
+        span: ast.span(),
+    }
+}
+
+fn grammar<'a>(
+    ast: &'a ::rust_parser::AST,
+    expected: &str,
+) -> Result<&'a [Node], ::rust_parser::AstError> {
+    match ast {
+        ::rust_parser::AST::Grammar { name, children, .. } if name == expected => Ok(children),
+        _ => Err(unexpected(ast, expected)),
+    }
+}
+
+fn matches(children: &[Node], symbols: &[&str]) -> bool {
+    children.len() == symbols.len()
+        && children
+            .iter()
+            .zip(symbols.iter())
+            .all(|(child, symbol)| name(&child.borrow()) == *symbol)
+}
+
+fn token(ast: &::rust_parser::AST) -> Result<::rust_parser::Token, ::rust_parser::AstError> {
+    match ast {
+        ::rust_parser::AST::Token { name, value, span } => Ok(::rust_parser::Token {
+            name: name.clone(),
+            value: value.clone(),
+            span: *span,
+        }),
+        _ => Err(unexpected(ast, "a token")),
+    }
+}
+
+/// Reads the children of a grammar node one symbol at a time.
+struct Cursor<'a> {
+    parent: &'a ::rust_parser::AST,
+    children: &'a [Node],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(parent: &'a ::rust_parser::AST, children: &'a [Node]) -> Cursor<'a> {
+        Cursor {
+            parent,
+            children,
+            position: 0,
+        }
+    }
+
+    fn is_next(&self, names: &[&str]) -> bool {
+        self.children
+            .get(self.position)
+            .is_some_and(|child| names.contains(&name(&child.borrow())))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<&'a Node, ::rust_parser::AstError> {
+        match self.children.get(self.position) {
+            Some(child) if name(&child.borrow()) == expected => {
+                self.position += 1;
+                Ok(child)
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<&'a Node, ::rust_parser::AstError> {
+        match self.children.get(self.position) {
+            Some(child) => {
+                self.position += 1;
+                Ok(child)
+            }
+            None => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ::rust_parser::AstError {
+        match self.children.get(self.position) {
+            Some(child) => unexpected(&child.borrow(), expected),
+            None => ::rust_parser::AstError::UnexpectedNode {
+                expected: String::from(expected),
+                found: format!("the end of {}", name(self.parent)),
+                span: self.parent.span(),
+            },
+        }
+    }
+
+    fn finish(&self) -> Result<(), ::rust_parser::AstError> {
+        match self.children.get(self.position) {
+            Some(child) => Err(unexpected(
+                &child.borrow(),
+                &format!("the end of {}", name(self.parent)),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Converts a grammar node with a single variant.
+fn sequence<T>(
+    ast: &::rust_parser::AST,
+    expected: &str,
+    item: fn(&mut Cursor) -> Result<T, ::rust_parser::AstError>,
+) -> Result<T, ::rust_parser::AstError> {
+    let mut cursor = Cursor::new(ast, grammar(ast, expected)?);
+    let value = item(&mut cursor)?;
+    cursor.finish()?;
+    Ok(value)
+}
+
+/// Flattens `X -> items X | epsilon` into the list of items.
+fn list<T>(
+    ast: &::rust_parser::AST,
+    expected: &str,
+    item: fn(&mut Cursor) -> Result<T, ::rust_parser::AstError>,
+) -> Result<Vec<T>, ::rust_parser::AstError> {
+    let mut items = vec![];
+    let mut next = list_item(ast, expected, item, &mut items)?;
+    while let Some(node) = next {
+        next = list_item(&node.borrow(), expected, item, &mut items)?;
+    }
+    Ok(items)
+}
+
+fn list_item<T>(
+    ast: &::rust_parser::AST,
+    expected: &str,
+    item: fn(&mut Cursor) -> Result<T, ::rust_parser::AstError>,
+    items: &mut Vec<T>,
+) -> Result<Option<Node>, ::rust_parser::AstError> {
+    let Some((last, children)) = grammar(ast, expected)?.split_last() else {
+        return Ok(None);
+    };
+
+    let mut cursor = Cursor::new(ast, children);
+    items.push(item(&mut cursor)?);
+    cursor.finish()?;
+
+    if name(&last.borrow()) != expected {
+        return Err(unexpected(&last.borrow(), expected));
+    }
+    Ok(Some(last.clone()))
+}
+
+/// Converts `X -> items | epsilon`.
+fn optional<T>(
+    ast: &::rust_parser::AST,
+    expected: &str,
+    item: fn(&mut Cursor) -> Result<T, ::rust_parser::AstError>,
+) -> Result<Option<T>, ::rust_parser::AstError> {
+    let children = grammar(ast, expected)?;
+    if children.is_empty() {
+        return Ok(None);
+    }
+
+    let mut cursor = Cursor::new(ast, children);
+    let value = item(&mut cursor)?;
+    cursor.finish()?;
+    Ok(Some(value))
+}
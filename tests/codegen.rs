@@ -0,0 +1,64 @@
+use std::fs::read_to_string;
+
+use rust_parser::{Parser, Tokenizer, AST};
+
+/// Output of `rust-parser codegen data/precedence/tokens.txt data/precedence/grammar.txt`,
+/// compiled here so the runtime shared by generated modules is type-checked.
+#[rustfmt::skip]
+#[path = "generated/precedence.rs"]
+mod precedence;
+
+fn parser() -> Parser {
+    let tokenizer = Tokenizer::from_file("data/precedence/tokens.txt").unwrap();
+    Parser::from_file("data/precedence/grammar.txt", tokenizer).unwrap()
+}
+
+fn shape(ast: &AST) -> String {
+    match ast {
+        AST::Token { value, span, .. } => format!("{value}@{}", span.start),
+        AST::Grammar { name, children, .. } => {
+            let children: Vec<String> = children.iter().map(|c| shape(&c.borrow())).collect();
+            format!("{name}({})", children.join(" "))
+        }
+        AST::Error { name, .. } => format!("!{name}"),
+    }
+}
+
+fn generated_shape(node: &precedence::Node) -> String {
+    match node {
+        precedence::Node::Token { value, span, .. } => format!("{value}@{}", span.start),
+        precedence::Node::Grammar { name, children, .. } => {
+            let children: Vec<String> = children.iter().map(generated_shape).collect();
+            format!("{name}({})", children.join(" "))
+        }
+    }
+}
+
+#[test]
+fn generated_module_is_up_to_date() {
+    let generated = read_to_string("tests/generated/precedence.rs").unwrap();
+    assert!(
+        parser().codegen() == generated,
+        "regenerate tests/generated/precedence.rs with `cargo run -- codegen \
+         data/precedence/tokens.txt data/precedence/grammar.txt -o tests/generated/precedence.rs`"
+    );
+}
+
+#[test]
+fn generated_module_parses_like_the_parser() {
+    let parser = parser();
+    let input = read_to_string("data/precedence/example.txt").unwrap();
+    for input in [input.as_str(), "1", "-(2 ^ 3) * 4 - 5"] {
+        let expected = shape(&parser.parse(input).unwrap().borrow());
+        assert_eq!(
+            generated_shape(&precedence::parse(input).unwrap()),
+            expected
+        );
+    }
+
+    let error = precedence::parse("1 + * 2").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        parser.parse("1 + * 2").unwrap_err().to_string()
+    );
+}
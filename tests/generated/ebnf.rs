@@ -0,0 +1,397 @@
+// Generated by rust-parser. Do not edit.
+
+#![allow(dead_code)]
+
+type Node = std::rc::Rc<std::cell::RefCell<::rust_parser::AST>>;
+
+fn name(ast: &::rust_parser::AST) -> &str {
+    match ast {
+        ::rust_parser::AST::Token { name, .. }
+        | ::rust_parser::AST::Grammar { name, .. }
+        | ::rust_parser::AST::Error { name, .. } => name,
+    }
+}
+
+fn unexpected(ast: &::rust_parser::AST, expected: &str) -> ::rust_parser::AstError {
+    let found = match ast {
+        ::rust_parser::AST::Error { name, .. } => format!("an error in {name}"),
+        _ => String::from(name(ast)),
+    };
+
+    ::rust_parser::AstError::UnexpectedNode {
+        expected: String::from(expected),
+        found,
+        span: ast.span(),
+    }
+}
+
+fn grammar<'a>(
+    ast: &'a ::rust_parser::AST,
+    expected: &str,
+) -> Result<&'a [Node], ::rust_parser::AstError> {
+    match ast {
+        ::rust_parser::AST::Grammar { name, children, .. } if name == expected => Ok(children),
+        _ => Err(unexpected(ast, expected)),
+    }
+}
+
+fn matches(children: &[Node], symbols: &[&str]) -> bool {
+    children.len() == symbols.len()
+        && children
+            .iter()
+            .zip(symbols.iter())
+            .all(|(child, symbol)| name(&child.borrow()) == *symbol)
+}
+
+fn token(ast: &::rust_parser::AST) -> Result<::rust_parser::Token, ::rust_parser::AstError> {
+    match ast {
+        ::rust_parser::AST::Token { name, value, span } => Ok(::rust_parser::Token {
+            name: name.clone(),
+            value: value.clone(),
+            span: *span,
+        }),
+        _ => Err(unexpected(ast, "a token")),
+    }
+}
+
+/// Reads the children of a grammar node one symbol at a time.
+struct Cursor<'a> {
+    parent: &'a ::rust_parser::AST,
+    children: &'a [Node],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(parent: &'a ::rust_parser::AST, children: &'a [Node]) -> Cursor<'a> {
+        Cursor {
+            parent,
+            children,
+            position: 0,
+        }
+    }
+
+    fn is_next(&self, names: &[&str]) -> bool {
+        self.children
+            .get(self.position)
+            .is_some_and(|child| names.contains(&name(&child.borrow())))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<&'a Node, ::rust_parser::AstError> {
+        match self.children.get(self.position) {
+            Some(child) if name(&child.borrow()) == expected => {
+                self.position += 1;
+                Ok(child)
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<&'a Node, ::rust_parser::AstError> {
+        match self.children.get(self.position) {
+            Some(child) => {
+                self.position += 1;
+                Ok(child)
+            }
+            None => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> ::rust_parser::AstError {
+        match self.children.get(self.position) {
+            Some(child) => unexpected(&child.borrow(), expected),
+            None => ::rust_parser::AstError::UnexpectedNode {
+                expected: String::from(expected),
+                found: format!("the end of {}", name(self.parent)),
+                span: self.parent.span(),
+            },
+        }
+    }
+
+    fn finish(&self) -> Result<(), ::rust_parser::AstError> {
+        match self.children.get(self.position) {
+            Some(child) => Err(unexpected(
+                &child.borrow(),
+                &format!("the end of {}", name(self.parent)),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Converts a grammar node with a single variant.
+fn sequence<T>(
+    ast: &::rust_parser::AST,
+    expected: &str,
+    item: fn(&mut Cursor) -> Result<T, ::rust_parser::AstError>,
+) -> Result<T, ::rust_parser::AstError> {
+    let mut cursor = Cursor::new(ast, grammar(ast, expected)?);
+    let value = item(&mut cursor)?;
+    cursor.finish()?;
+    Ok(value)
+}
+
+/// Flattens `X -> items X | epsilon` into the list of items.
+fn list<T>(
+    ast: &::rust_parser::AST,
+    expected: &str,
+    item: fn(&mut Cursor) -> Result<T, ::rust_parser::AstError>,
+) -> Result<Vec<T>, ::rust_parser::AstError> {
+    let mut items = vec![];
+    let mut next = list_item(ast, expected, item, &mut items)?;
+    while let Some(node) = next {
+        next = list_item(&node.borrow(), expected, item, &mut items)?;
+    }
+    Ok(items)
+}
+
+fn list_item<T>(
+    ast: &::rust_parser::AST,
+    expected: &str,
+    item: fn(&mut Cursor) -> Result<T, ::rust_parser::AstError>,
+    items: &mut Vec<T>,
+) -> Result<Option<Node>, ::rust_parser::AstError> {
+    let Some((last, children)) = grammar(ast, expected)?.split_last() else {
+        return Ok(None);
+    };
+
+    let mut cursor = Cursor::new(ast, children);
+    items.push(item(&mut cursor)?);
+    cursor.finish()?;
+
+    if name(&last.borrow()) != expected {
+        return Err(unexpected(&last.borrow(), expected));
+    }
+    Ok(Some(last.clone()))
+}
+
+/// Converts `X -> items | epsilon`.
+fn optional<T>(
+    ast: &::rust_parser::AST,
+    expected: &str,
+    item: fn(&mut Cursor) -> Result<T, ::rust_parser::AstError>,
+) -> Result<Option<T>, ::rust_parser::AstError> {
+    let children = grammar(ast, expected)?;
+    if children.is_empty() {
+        return Ok(None);
+    }
+
+    let mut cursor = Cursor::new(ast, children);
+    let value = item(&mut cursor)?;
+    cursor.finish()?;
+    Ok(Some(value))
+}
+
+/// `Document -> ModelDef Document~1`
+#[derive(Clone, Debug)]
+pub struct Document {
+    pub model_def: ModelDef,
+    pub type_def_list: DocumentTypeDefList,
+}
+
+fn convert_document(ast: &::rust_parser::AST) -> Result<Document, ::rust_parser::AstError> {
+    sequence(ast, "Document", |cursor| Ok(Document { model_def: convert_model_def(&cursor.expect("ModelDef")?.borrow())?, type_def_list: read_document_type_def_list(cursor)? }))
+}
+
+impl TryFrom<&::rust_parser::AST> for Document {
+    type Error = ::rust_parser::AstError;
+
+    fn try_from(ast: &::rust_parser::AST) -> Result<Document, ::rust_parser::AstError> {
+        convert_document(ast)
+    }
+}
+
+/// `Document~1 -> TypeDef Document~1`
+/// `Document~1 -> epsilon`
+pub type DocumentTypeDefList = Vec<TypeDef>;
+
+fn read_document_type_def_list(cursor: &mut Cursor) -> Result<DocumentTypeDefList, ::rust_parser::AstError> {
+    let mut items = vec![];
+    while cursor.is_next(&["TypeDef"]) {
+        items.push(convert_type_def(&cursor.expect("TypeDef")?.borrow())?);
+    }
+    Ok(items)
+}
+
+/// `ModelDef -> model schema Version`
+#[derive(Clone, Debug)]
+pub struct ModelDef {
+    pub model: ::rust_parser::Token,
+    pub schema: ::rust_parser::Token,
+    pub version: Version,
+}
+
+fn convert_model_def(ast: &::rust_parser::AST) -> Result<ModelDef, ::rust_parser::AstError> {
+    sequence(ast, "ModelDef", |cursor| Ok(ModelDef { model: token(&cursor.expect("model")?.borrow())?, schema: token(&cursor.expect("schema")?.borrow())?, version: convert_version(&cursor.expect("Version")?.borrow())? }))
+}
+
+impl TryFrom<&::rust_parser::AST> for ModelDef {
+    type Error = ::rust_parser::AstError;
+
+    fn try_from(ast: &::rust_parser::AST) -> Result<ModelDef, ::rust_parser::AstError> {
+        convert_model_def(ast)
+    }
+}
+
+/// `Version -> number Version~1`
+#[derive(Clone, Debug)]
+pub struct Version {
+    pub number: ::rust_parser::Token,
+    pub dot_number: VersionDotNumber,
+}
+
+fn convert_version(ast: &::rust_parser::AST) -> Result<Version, ::rust_parser::AstError> {
+    sequence(ast, "Version", |cursor| Ok(Version { number: token(&cursor.expect("number")?.borrow())?, dot_number: read_version_dot_number(cursor)? }))
+}
+
+impl TryFrom<&::rust_parser::AST> for Version {
+    type Error = ::rust_parser::AstError;
+
+    fn try_from(ast: &::rust_parser::AST) -> Result<Version, ::rust_parser::AstError> {
+        convert_version(ast)
+    }
+}
+
+/// `Version~1 -> . number`
+/// `Version~1 -> epsilon`
+pub type VersionDotNumber = Option<VersionDotNumberItem>;
+
+#[derive(Clone, Debug)]
+pub struct VersionDotNumberItem {
+    pub dot: ::rust_parser::Token,
+    pub number: ::rust_parser::Token,
+}
+
+fn read_version_dot_number(cursor: &mut Cursor) -> Result<VersionDotNumber, ::rust_parser::AstError> {
+    if cursor.is_next(&["."]) {
+        return Ok(Some(VersionDotNumberItem { dot: token(&cursor.expect(".")?.borrow())?, number: token(&cursor.expect("number")?.borrow())? }));
+    }
+    Ok(None)
+}
+
+/// `TypeDef -> type identifier TypeDef~2`
+#[derive(Clone, Debug)]
+pub struct TypeDef {
+    pub type_: ::rust_parser::Token,
+    pub identifier: ::rust_parser::Token,
+    pub relations_define_decl: TypeDefRelationsDefineDecl,
+}
+
+fn convert_type_def(ast: &::rust_parser::AST) -> Result<TypeDef, ::rust_parser::AstError> {
+    sequence(ast, "TypeDef", |cursor| Ok(TypeDef { type_: token(&cursor.expect("type")?.borrow())?, identifier: token(&cursor.expect("identifier")?.borrow())?, relations_define_decl: read_type_def_relations_define_decl(cursor)? }))
+}
+
+impl TryFrom<&::rust_parser::AST> for TypeDef {
+    type Error = ::rust_parser::AstError;
+
+    fn try_from(ast: &::rust_parser::AST) -> Result<TypeDef, ::rust_parser::AstError> {
+        convert_type_def(ast)
+    }
+}
+
+/// `TypeDef~1 -> DefineDecl TypeDef~1`
+/// `TypeDef~1 -> epsilon`
+pub type TypeDefDefineDeclList = Vec<DefineDecl>;
+
+fn read_type_def_define_decl_list(cursor: &mut Cursor) -> Result<TypeDefDefineDeclList, ::rust_parser::AstError> {
+    let mut items = vec![];
+    while cursor.is_next(&["DefineDecl"]) {
+        items.push(convert_define_decl(&cursor.expect("DefineDecl")?.borrow())?);
+    }
+    Ok(items)
+}
+
+/// `TypeDef~2 -> relations DefineDecl TypeDef~1`
+/// `TypeDef~2 -> epsilon`
+pub type TypeDefRelationsDefineDecl = Option<TypeDefRelationsDefineDeclItem>;
+
+#[derive(Clone, Debug)]
+pub struct TypeDefRelationsDefineDeclItem {
+    pub relations: ::rust_parser::Token,
+    pub define_decl: DefineDecl,
+    pub define_decl_list: TypeDefDefineDeclList,
+}
+
+fn read_type_def_relations_define_decl(cursor: &mut Cursor) -> Result<TypeDefRelationsDefineDecl, ::rust_parser::AstError> {
+    if cursor.is_next(&["relations"]) {
+        return Ok(Some(TypeDefRelationsDefineDeclItem { relations: token(&cursor.expect("relations")?.borrow())?, define_decl: convert_define_decl(&cursor.expect("DefineDecl")?.borrow())?, define_decl_list: read_type_def_define_decl_list(cursor)? }));
+    }
+    Ok(None)
+}
+
+/// `DefineDecl -> define identifier : [ Role DefineDecl~1 ]`
+#[derive(Clone, Debug)]
+pub struct DefineDecl {
+    pub define: ::rust_parser::Token,
+    pub identifier: ::rust_parser::Token,
+    pub colon: ::rust_parser::Token,
+    pub lbracket: ::rust_parser::Token,
+    pub role: Role,
+    pub comma_role_list: DefineDeclCommaRoleList,
+    pub rbracket: ::rust_parser::Token,
+}
+
+fn convert_define_decl(ast: &::rust_parser::AST) -> Result<DefineDecl, ::rust_parser::AstError> {
+    sequence(ast, "DefineDecl", |cursor| Ok(DefineDecl { define: token(&cursor.expect("define")?.borrow())?, identifier: token(&cursor.expect("identifier")?.borrow())?, colon: token(&cursor.expect(":")?.borrow())?, lbracket: token(&cursor.expect("[")?.borrow())?, role: convert_role(&cursor.expect("Role")?.borrow())?, comma_role_list: read_define_decl_comma_role_list(cursor)?, rbracket: token(&cursor.expect("]")?.borrow())? }))
+}
+
+impl TryFrom<&::rust_parser::AST> for DefineDecl {
+    type Error = ::rust_parser::AstError;
+
+    fn try_from(ast: &::rust_parser::AST) -> Result<DefineDecl, ::rust_parser::AstError> {
+        convert_define_decl(ast)
+    }
+}
+
+/// `DefineDecl~1 -> , Role DefineDecl~1`
+/// `DefineDecl~1 -> epsilon`
+pub type DefineDeclCommaRoleList = Vec<DefineDeclCommaRoleListItem>;
+
+#[derive(Clone, Debug)]
+pub struct DefineDeclCommaRoleListItem {
+    pub comma: ::rust_parser::Token,
+    pub role: Role,
+}
+
+fn read_define_decl_comma_role_list(cursor: &mut Cursor) -> Result<DefineDeclCommaRoleList, ::rust_parser::AstError> {
+    let mut items = vec![];
+    while cursor.is_next(&[","]) {
+        items.push(DefineDeclCommaRoleListItem { comma: token(&cursor.expect(",")?.borrow())?, role: convert_role(&cursor.expect("Role")?.borrow())? });
+    }
+    Ok(items)
+}
+
+/// `Role -> identifier Role~1`
+#[derive(Clone, Debug)]
+pub struct Role {
+    pub identifier: ::rust_parser::Token,
+    pub hash_identifier: RoleHashIdentifier,
+}
+
+fn convert_role(ast: &::rust_parser::AST) -> Result<Role, ::rust_parser::AstError> {
+    sequence(ast, "Role", |cursor| Ok(Role { identifier: token(&cursor.expect("identifier")?.borrow())?, hash_identifier: read_role_hash_identifier(cursor)? }))
+}
+
+impl TryFrom<&::rust_parser::AST> for Role {
+    type Error = ::rust_parser::AstError;
+
+    fn try_from(ast: &::rust_parser::AST) -> Result<Role, ::rust_parser::AstError> {
+        convert_role(ast)
+    }
+}
+
+/// `Role~1 -> # identifier`
+/// `Role~1 -> epsilon`
+pub type RoleHashIdentifier = Option<RoleHashIdentifierItem>;
+
+#[derive(Clone, Debug)]
+pub struct RoleHashIdentifierItem {
+    pub hash: ::rust_parser::Token,
+    pub identifier: ::rust_parser::Token,
+}
+
+fn read_role_hash_identifier(cursor: &mut Cursor) -> Result<RoleHashIdentifier, ::rust_parser::AstError> {
+    if cursor.is_next(&["#"]) {
+        return Ok(Some(RoleHashIdentifierItem { hash: token(&cursor.expect("#")?.borrow())?, identifier: token(&cursor.expect("identifier")?.borrow())? }));
+    }
+    Ok(None)
+}
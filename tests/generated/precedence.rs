@@ -0,0 +1,638 @@
+// Generated by rust-parser. Do not edit.
+
+#![allow(dead_code)]
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+const EOF: &str = "$";
+const START: &str = "E";
+const LOOKAHEAD: usize = 1;
+const INDENTATION: Option<usize> = None;
+const HELPERS: &[&str] = &["E~1", "E~2"];
+const TAILS: &[(&str, &str)] = &[];
+const EXPRESSIONS: &[&str] = &["E"];
+
+/// Binary operators: token, precedence and whether they are right-associative.
+const BINARY: &[(&str, usize, bool)] = &[("+", 1, false), ("-", 1, false), ("*", 2, false), ("/", 2, false), ("^", 4, true)];
+const PREFIX: &[(&str, usize)] = &[("-", 3)];
+
+/// Token name, anchored pattern and whether matched tokens are skipped.
+const PATTERNS: &[(&str, &str, bool)] = &[
+    ("+", "^(?:\\+)", false),
+    ("-", "^(?:-)", false),
+    ("*", "^(?:\\*)", false),
+    ("/", "^(?:/)", false),
+    ("^", "^(?:\\^)", false),
+    ("(", "^(?:\\()", false),
+    (")", "^(?:\\))", false),
+    ("int", "^(?:\\d+)", false),
+    ("whitespace", "^(?:\\s+)", true),
+];
+
+fn variant(grammar: &str, lookahead: &[&str]) -> Option<&'static [Symbol]> {
+    match (grammar, lookahead) {
+        ("E", ["-"]) => Some(&[Symbol::Grammar("E~1"), Symbol::Grammar("E~2")]),
+        ("E", ["("]) => Some(&[Symbol::Grammar("E~1"), Symbol::Grammar("E~2")]),
+        ("E", ["int"]) => Some(&[Symbol::Grammar("E~1"), Symbol::Grammar("E~2")]),
+        ("E~1", ["-"]) => Some(&[Symbol::Token("-"), Symbol::Grammar("E~1")]),
+        ("E~1", ["("]) => Some(&[Symbol::Grammar("F")]),
+        ("E~1", ["int"]) => Some(&[Symbol::Grammar("F")]),
+        ("E~2", ["+"]) => Some(&[Symbol::Token("+"), Symbol::Grammar("E~1"), Symbol::Grammar("E~2")]),
+        ("E~2", ["-"]) => Some(&[Symbol::Token("-"), Symbol::Grammar("E~1"), Symbol::Grammar("E~2")]),
+        ("E~2", ["*"]) => Some(&[Symbol::Token("*"), Symbol::Grammar("E~1"), Symbol::Grammar("E~2")]),
+        ("E~2", ["/"]) => Some(&[Symbol::Token("/"), Symbol::Grammar("E~1"), Symbol::Grammar("E~2")]),
+        ("E~2", ["^"]) => Some(&[Symbol::Token("^"), Symbol::Grammar("E~1"), Symbol::Grammar("E~2")]),
+        ("E~2", ["$"]) => Some(&[]),
+        ("E~2", [")"]) => Some(&[]),
+        ("F", ["("]) => Some(&[Symbol::Token("("), Symbol::Grammar("E"), Symbol::Token(")")]),
+        ("F", ["int"]) => Some(&[Symbol::Token("int")]),
+        _ => None,
+    }
+}
+
+fn lookaheads(grammar: &str) -> &'static [&'static [&'static str]] {
+    match grammar {
+        "E" => &[&["-"], &["("], &["int"]],
+        "E~1" => &[&["-"], &["("], &["int"]],
+        "E~2" => &[&["+"], &["-"], &["*"], &["/"], &["^"], &["$"], &[")"]],
+        "F" => &[&["("], &["int"]],
+        _ => &[],
+    }
+}
+
+/// Location of a fragment of the input: byte offsets plus the line and column of its start.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub name: &'static str,
+    pub value: String,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub enum Node {
+    Token {
+        name: &'static str,
+        value: String,
+        span: Span,
+    },
+    Grammar {
+        name: &'static str,
+        children: Vec<Node>,
+        span: Span,
+    },
+}
+
+impl Node {
+    pub fn span(&self) -> Span {
+        match self {
+            Node::Token { span, .. } | Node::Grammar { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownToken {
+        value: String,
+        span: Span,
+    },
+    InconsistentDedent {
+        span: Span,
+    },
+    UnexpectedToken {
+        value: String,
+        span: Span,
+        expected: Vec<&'static str>,
+    },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnknownToken { value, .. } => write!(f, "Unknown token {value}."),
+            Error::InconsistentDedent { span } => write!(
+                f,
+                "Inconsistent dedent on line {}, column {}.",
+                span.line, span.column
+            ),
+            Error::UnexpectedToken {
+                value,
+                span,
+                expected,
+            } => {
+                write!(
+                    f,
+                    "Unexpected token '{}' at {}:{}",
+                    value.escape_default(),
+                    span.line,
+                    span.column
+                )?;
+                if !expected.is_empty() {
+                    let names: Vec<String> = expected.iter().map(|name| quote(name)).collect();
+                    write!(f, ", expected one of: {}", names.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Quotes token names made of punctuation so they stand out in messages.
+fn quote(name: &str) -> String {
+    if name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        String::from(name)
+    } else {
+        format!("'{name}'")
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Symbol {
+    Token(&'static str),
+    Grammar(&'static str),
+}
+
+fn patterns() -> &'static [Regex] {
+    static COMPILED: OnceLock<Vec<Regex>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        PATTERNS
+            .iter()
+            .map(|(_, pattern, _)| Regex::new(pattern).expect("patterns are validated"))
+            .collect()
+    })
+}
+
+/// Splits the input into tokens using the longest match among all patterns,
+/// leaving out tokens matched by `%skip` patterns, or whitespace if there are none.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut result = vec![];
+    let mut cursor = Cursor::new(input);
+    let mut indents: Vec<usize> = vec![0];
+    let mut line_start = true;
+
+    while let Some(c) = cursor.peek() {
+        let mut rest = cursor.rest();
+
+        if let Some(tab_width) = INDENTATION {
+            if line_start {
+                let (width, length) = measure_indentation(rest, tab_width);
+                let line = first_line(&rest[length..]);
+                cursor.advance(length);
+
+                if is_blank(line) {
+                    cursor.advance(line.len());
+                    if let Some(c) = cursor.peek() {
+                        cursor.advance(c.len_utf8());
+                    }
+                    continue;
+                }
+
+                if width > *indents.last().unwrap() {
+                    indents.push(width);
+                    result.push(cursor.synthetic("INDENT"));
+                } else {
+                    while width < *indents.last().unwrap() {
+                        indents.pop();
+                        result.push(cursor.synthetic("DEDENT"));
+                    }
+
+                    if width != *indents.last().unwrap() {
+                        return Err(Error::InconsistentDedent {
+                            span: cursor.span(0),
+                        });
+                    }
+                }
+
+                line_start = false;
+                continue;
+            }
+
+            if c == '\n' {
+                result.push(cursor.synthetic("NEWLINE"));
+                cursor.advance(c.len_utf8());
+                line_start = true;
+                continue;
+            }
+
+            rest = first_line(rest);
+        }
+
+        match longest_match(rest) {
+            Some((index, length)) => {
+                let (name, _, skip) = PATTERNS[index];
+                if !skip {
+                    result.push(Token {
+                        name,
+                        value: String::from(&rest[..length]),
+                        span: cursor.span(length),
+                    });
+                }
+                cursor.advance(length);
+            }
+            None if c.is_whitespace() && PATTERNS.iter().all(|(_, _, skip)| !skip) => {
+                cursor.advance(c.len_utf8());
+            }
+            None => {
+                let mut unknown: String = rest.chars().take_while(|c| !c.is_whitespace()).collect();
+                if unknown.is_empty() {
+                    unknown.push(c);
+                }
+                return Err(Error::UnknownToken {
+                    span: cursor.span(unknown.len()),
+                    value: unknown,
+                });
+            }
+        }
+    }
+
+    if INDENTATION.is_some() {
+        if !line_start {
+            result.push(cursor.synthetic("NEWLINE"));
+        }
+
+        while indents.len() > 1 {
+            indents.pop();
+            result.push(cursor.synthetic("DEDENT"));
+        }
+    }
+
+    result.push(cursor.synthetic(EOF));
+    Ok(result)
+}
+
+/// Parses the input starting from the first grammar.
+pub fn parse(input: &str) -> Result<Node, Error> {
+    let tokens = tokenize(input)?;
+    let mut position = 0;
+
+    // The outermost frame only wraps the start grammar.
+    let mut stack = vec![Frame::new(START, &[Symbol::Grammar(START)], tokens[0].span)];
+    let root = loop {
+        let frame = stack.last_mut().unwrap();
+
+        let Some(symbol) = frame.symbols.get(frame.next).copied() else {
+            let frame = stack.pop().unwrap();
+            let node = frame.finish();
+            match (stack.last_mut(), node) {
+                // Helpers of EBNF operators are spliced into their parent.
+                (Some(parent), Node::Grammar { name, children, .. }) if HELPERS.contains(&name) => {
+                    parent.children.extend(children)
+                }
+                (Some(parent), node) => parent.children.push(node),
+                (None, node) => break node,
+            }
+            continue;
+        };
+        frame.next += 1;
+
+        let token = &tokens[position];
+        match symbol {
+            Symbol::Token(name) if name == token.name => {
+                frame.children.push(Node::Token {
+                    name,
+                    value: token.value.clone(),
+                    span: token.span,
+                });
+                position += 1;
+            }
+            Symbol::Token(name) => return Err(unexpected(token, vec![name])),
+            Symbol::Grammar(name) => {
+                let lookahead: Vec<&str> = tokens[position..]
+                    .iter()
+                    .take(LOOKAHEAD)
+                    .map(|token| token.name)
+                    .collect();
+                match variant(name, &lookahead) {
+                    Some(symbols) => stack.push(Frame::new(name, symbols, token.span)),
+                    None => {
+                        let (offset, expected) = mismatch(lookaheads(name), &lookahead);
+                        return Err(unexpected(&tokens[position + offset], expected));
+                    }
+                }
+            }
+        }
+    };
+
+    let token = &tokens[position];
+    if token.name != EOF {
+        return Err(unexpected(token, vec![EOF]));
+    }
+
+    match root {
+        Node::Grammar { mut children, .. } => Ok(children.remove(0)),
+        node => Ok(node),
+    }
+}
+
+/// Finds the first lookahead token that no entry accepts and the tokens the entries accept there.
+fn mismatch(entries: &[&[&'static str]], lookahead: &[&str]) -> (usize, Vec<&'static str>) {
+    let mut offset = 0;
+    loop {
+        let mut expected = vec![];
+        for entry in entries.iter() {
+            if entry.len() > offset
+                && entry[..offset] == lookahead[..offset]
+                && !expected.contains(&entry[offset])
+            {
+                expected.push(entry[offset]);
+            }
+        }
+
+        if offset + 1 < lookahead.len() && expected.contains(&lookahead[offset]) {
+            offset += 1;
+            continue;
+        }
+
+        return (offset, expected);
+    }
+}
+
+fn unexpected(token: &Token, expected: Vec<&'static str>) -> Error {
+    Error::UnexpectedToken {
+        value: token.value.clone(),
+        span: token.span,
+        expected,
+    }
+}
+
+/// A grammar being expanded: the variant chosen for it and the children parsed so far.
+struct Frame {
+    name: &'static str,
+    symbols: &'static [Symbol],
+    next: usize,
+    children: Vec<Node>,
+    start: Span,
+}
+
+impl Frame {
+    fn new(name: &'static str, symbols: &'static [Symbol], start: Span) -> Frame {
+        Frame {
+            name,
+            symbols,
+            next: 0,
+            children: vec![],
+            start,
+        }
+    }
+
+    fn finish(self) -> Node {
+        let mut children = self.children;
+        if let Some((tail, _)) = TAILS.iter().find(|(_, grammar)| *grammar == self.name) {
+            children = fold(self.name, tail, children, self.start);
+        }
+        if EXPRESSIONS.contains(&self.name) && !children.is_empty() {
+            children = climb(self.name, children, self.start);
+        }
+        grammar_node(self.name, children, self.start)
+    }
+}
+
+/// Turns the tail of a left-recursive grammar back into left-nested nodes,
+/// so `1 - 2 - 3` parsed with `E -> E - T | T` reads as `(1 - 2) - 3`.
+fn fold(name: &'static str, tail: &str, mut children: Vec<Node>, start: Span) -> Vec<Node> {
+    let mut next = match children.last() {
+        Some(Node::Grammar { name, .. }) if *name == tail => children.pop(),
+        _ => None,
+    };
+
+    while let Some(Node::Grammar {
+        children: mut level,
+        ..
+    }) = next.take()
+    {
+        if level.is_empty() {
+            break;
+        }
+        if matches!(level.last(), Some(Node::Grammar { name, .. }) if *name == tail) {
+            next = level.pop();
+        }
+
+        let mut nested = vec![grammar_node(name, children, start)];
+        nested.append(&mut level);
+        children = nested;
+    }
+
+    children
+}
+
+/// Rebuilds the flat operands and operators of an expression into nested operator nodes,
+/// so `1 + 2 * 3` becomes `E(1, +, E(2, *, 3))`.
+fn climb(name: &'static str, children: Vec<Node>, start: Span) -> Vec<Node> {
+    let count = children.len();
+    let mut climber = Climber {
+        name,
+        items: children.into_iter().rev().collect(),
+        start,
+    };
+    let folded = climber.expression(0);
+
+    // The outermost operation reuses the expression node itself, a lone operand stays a child.
+    let mut children = match folded {
+        Node::Grammar { children, .. } if count - climber.items.len() > 1 => children,
+        operand => vec![operand],
+    };
+    children.extend(climber.items.into_iter().rev());
+    children
+}
+
+/// Precedence climbing over the children of an expression, stored in reverse order.
+struct Climber {
+    name: &'static str,
+    items: Vec<Node>,
+    start: Span,
+}
+
+impl Climber {
+    fn expression(&mut self, minimum: usize) -> Node {
+        let mut left = self.operand();
+
+        while let Some((precedence, right)) = self.operator(|name| {
+            BINARY
+                .iter()
+                .find(|(operator, ..)| *operator == name)
+                .map(|(_, precedence, right)| (*precedence, *right))
+        }) {
+            if precedence < minimum {
+                break;
+            }
+
+            let operator = self.items.pop().unwrap();
+            let next = if right { precedence } else { precedence + 1 };
+            let operand = self.expression(next);
+            left = grammar_node(self.name, vec![left, operator, operand], self.start);
+        }
+
+        left
+    }
+
+    fn operand(&mut self) -> Node {
+        let prefix = self.operator(|name| {
+            PREFIX
+                .iter()
+                .find(|(operator, _)| *operator == name)
+                .map(|(_, precedence)| *precedence)
+        });
+
+        match prefix {
+            Some(precedence) => {
+                let operator = self.items.pop().unwrap();
+                let operand = self.expression(precedence);
+                grammar_node(self.name, vec![operator, operand], self.start)
+            }
+            None => self.items.pop().unwrap(),
+        }
+    }
+
+    /// Looks up the next token if an operand follows it.
+    fn operator<T>(&self, lookup: impl Fn(&str) -> Option<T>) -> Option<T> {
+        if self.items.len() < 2 {
+            return None;
+        }
+        match self.items.last() {
+            Some(Node::Token { name, .. }) => lookup(name),
+            _ => None,
+        }
+    }
+}
+
+/// Builds a grammar node whose span covers its non-empty children.
+fn grammar_node(name: &'static str, children: Vec<Node>, start: Span) -> Node {
+    let spans: Vec<Span> = children
+        .iter()
+        .map(Node::span)
+        .filter(|span| span.end > span.start)
+        .collect();
+    let span = match (spans.first(), spans.last()) {
+        (Some(first), Some(last)) => Span {
+            end: last.end,
+            ..*first
+        },
+        _ => Span {
+            end: start.start,
+            ..start
+        },
+    };
+
+    Node::Grammar {
+        name,
+        children,
+        span,
+    }
+}
+
+fn longest_match(s: &str) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize)> = None;
+    for (index, pattern) in patterns().iter().enumerate() {
+        if let Some(matched) = pattern.find(s) {
+            let length = matched.end();
+            if length > 0 && best.is_none_or(|(_, best_length)| length > best_length) {
+                best = Some((index, length));
+            }
+        }
+    }
+    best
+}
+
+/// Checks whether the line consists of skipped tokens only.
+fn is_blank(line: &str) -> bool {
+    let mut offset = 0;
+    while offset < line.len() {
+        match longest_match(&line[offset..]) {
+            Some((index, length)) if PATTERNS[index].2 => offset += length,
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Returns the width of the leading whitespace and its length in bytes.
+fn measure_indentation(s: &str, tab_width: usize) -> (usize, usize) {
+    let mut width = 0;
+    let mut length = 0;
+    for c in s.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += tab_width - width % tab_width,
+            _ => break,
+        }
+        length += c.len_utf8();
+    }
+    (width, length)
+}
+
+fn first_line(s: &str) -> &str {
+    match s.find('\n') {
+        Some(end) => &s[..end],
+        None => s,
+    }
+}
+
+/// Tracks the position of the tokenizer in the input.
+struct Cursor<'a> {
+    text: &'a str,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str) -> Cursor<'a> {
+        Cursor {
+            text,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.offset..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn span(&self, length: usize) -> Span {
+        Span {
+            start: self.offset,
+            end: self.offset + length,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn synthetic(&self, name: &'static str) -> Token {
+        Token {
+            name,
+            value: String::new(),
+            span: self.span(0),
+        }
+    }
+
+    fn advance(&mut self, length: usize) {
+        for c in self.text[self.offset..self.offset + length].chars() {
+            match c {
+                '\n' => {
+                    self.line += 1;
+                    self.column = 1;
+                }
+                '\r' => {}
+                _ => self.column += 1,
+            }
+        }
+        self.offset += length;
+    }
+}
@@ -0,0 +1,63 @@
+use std::fs::read_to_string;
+
+use rust_parser::{Parser, Tokenizer};
+
+/// Output of `rust-parser types data/ebnf/tokens.txt data/ebnf/grammar.txt`, compiled here
+/// so the prelude shared by generated types is type-checked.
+#[rustfmt::skip]
+#[path = "generated/ebnf.rs"]
+mod ebnf;
+
+fn parser() -> Parser {
+    let tokenizer = Tokenizer::from_file("data/ebnf/tokens.txt").unwrap();
+    Parser::from_file("data/ebnf/grammar.txt", tokenizer).unwrap()
+}
+
+#[test]
+fn generated_types_are_up_to_date() {
+    let generated = read_to_string("tests/generated/ebnf.rs").unwrap();
+    assert!(
+        parser().typed_ast() == generated,
+        "regenerate tests/generated/ebnf.rs with `cargo run -- types \
+         data/ebnf/tokens.txt data/ebnf/grammar.txt -o tests/generated/ebnf.rs`"
+    );
+}
+
+#[test]
+fn generated_types_convert_the_ast() {
+    let input = read_to_string("data/ebnf/example.txt").unwrap();
+    let ast = parser().parse(&input).unwrap();
+    let document = ebnf::Document::try_from(&*ast.borrow()).unwrap();
+
+    let version = &document.model_def.version;
+    assert_eq!(version.number.value, "1");
+    assert_eq!(version.dot_number.as_ref().unwrap().number.value, "1");
+
+    let names: Vec<&str> = document
+        .type_def_list
+        .iter()
+        .map(|type_def| type_def.identifier.value.as_str())
+        .collect();
+    assert_eq!(names, ["document", "domain", "user"]);
+
+    let relations = document.type_def_list[0].relations_define_decl.as_ref();
+    let relations = relations.unwrap();
+    assert_eq!(relations.define_decl.identifier.value, "viewer");
+    assert_eq!(relations.define_decl_list.len(), 3);
+    let role = &relations.define_decl.role;
+    assert_eq!(role.identifier.value, "domain");
+    assert_eq!(
+        role.hash_identifier.as_ref().unwrap().identifier.value,
+        "member"
+    );
+    assert!(document.type_def_list[2].relations_define_decl.is_none());
+}
+
+#[test]
+fn generated_types_reject_other_grammars() {
+    let ast = parser().parse("model schema 1").unwrap();
+    let error = ebnf::TypeDef::try_from(&*ast.borrow()).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Expected TypeDef at 1:1, found Document"));
+}